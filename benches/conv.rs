@@ -20,7 +20,7 @@ fn mk(sizes: &[usize]) -> Tensor {
 
 fn conv(bencher: &mut Criterion) {
     let stride = 1;
-    let conv = Conv2D::<f32>::new(LocalPatch::valid(stride, stride));
+    let conv = Conv2D::<f32>::new(LocalPatch::valid(stride, stride), 1);
     let inputs = vec![mk(&[1, 82, 1, 40]).into(), mk(&[41, 1, 40, 128]).into()];
     conv.eval(inputs.clone()).unwrap();
     bencher.bench_function("Conv2D<f32>(1x82x1x40 41x1x40x128)", move |b| {
@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate criterion;
+extern crate tfdeploy;
+
+use criterion::Criterion;
+
+use tfdeploy::*;
+
+fn plus3(bencher: &mut Criterion) {
+    let model = Model::for_path("tests/models/plus3.pb").unwrap();
+    let input = model.node_id_by_name("input").unwrap();
+    let output = model.node_id_by_name("output").unwrap();
+    let data = Tensor::f32s(&[1024], &[1.0; 1024]).unwrap();
+    bencher.bench_function("plus3(1024)", move |b| {
+        b.iter(|| model.run(vec![(input, data.clone())], output).unwrap())
+    });
+}
+
+criterion_group!(benches, plus3);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+#[macro_use]
+extern crate criterion;
+extern crate ndarray;
+extern crate tfdeploy;
+
+use criterion::Criterion;
+
+use tfdeploy::ops::nn::conv2d::*;
+use tfdeploy::ops::nn::local_patch::*;
+use tfdeploy::ops::{Op, OpBuilder};
+use tfdeploy::tfpb;
+use tfdeploy::tfpb::types::DataType::{DT_FLOAT, DT_INT32};
+use tfdeploy::*;
+
+fn mk(sizes: &[usize]) -> Tensor {
+    let data = ::ndarray::Array::range(1f32, sizes.iter().product::<usize>() as f32 + 1.0, 1.0)
+        .into_shape(sizes)
+        .unwrap();
+    Tensor::F32(data)
+}
+
+/// Builds an operator the way the model loader does.
+fn op(node: tfpb::node_def::NodeDef) -> Box<Op> {
+    OpBuilder::new().build(&node).unwrap()
+}
+
+fn add(bencher: &mut Criterion) {
+    let add = op(tfpb::node().op("Add").attr("T", DT_FLOAT));
+    for &(a, b) in &[
+        (&[64, 64][..], &[64, 64][..]),
+        (&[1024, 1024][..], &[1024, 1024][..]),
+        (&[1024, 1024][..], &[1024][..]),
+        (&[1024, 1024][..], &[][..]),
+    ] {
+        let inputs = vec![mk(a).into(), mk(b).into()];
+        let add = add.clone();
+        bencher.bench_function(&format!("Add<f32>({:?} {:?})", a, b), move |be| {
+            be.iter(|| add.eval(inputs.clone()).unwrap())
+        });
+    }
+}
+
+fn pad(bencher: &mut Criterion) {
+    let pad = op(tfpb::node()
+        .op("Pad")
+        .attr("T", DT_FLOAT)
+        .attr("Tpaddings", DT_INT32));
+    for shape in &[[1, 64, 64, 16], [1, 256, 256, 32]] {
+        let paddings = Tensor::i32s(&[4, 2], &[0, 0, 1, 1, 1, 1, 0, 0]).unwrap();
+        let inputs = vec![mk(shape).into(), paddings.into()];
+        let pad = pad.clone();
+        bencher.bench_function(&format!("Pad<f32>({:?})", shape), move |b| {
+            b.iter(|| pad.eval(inputs.clone()).unwrap())
+        });
+    }
+}
+
+fn matmul(bencher: &mut Criterion) {
+    for &(a, b, transpose_a, transpose_b) in &[
+        ([64, 64], [64, 64], false, false),
+        ([256, 256], [256, 256], false, false),
+        ([256, 256], [256, 256], true, true),
+        ([1, 1024], [1024, 1024], false, false),
+    ] {
+        let matmul = op(tfpb::node()
+            .op("MatMul")
+            .attr("T", DT_FLOAT)
+            .attr("transpose_a", transpose_a)
+            .attr("transpose_b", transpose_b));
+        let inputs = vec![mk(&a).into(), mk(&b).into()];
+        bencher.bench_function(
+            &format!("MatMul<f32>({:?} {:?} {} {})", a, b, transpose_a, transpose_b),
+            move |be| be.iter(|| matmul.eval(inputs.clone()).unwrap()),
+        );
+    }
+}
+
+fn conv(bencher: &mut Criterion) {
+    for &(data, filter, padding) in &[
+        ([1, 82, 1, 40], [41, 1, 40, 128], Padding::Valid),
+        ([1, 32, 32, 16], [3, 3, 16, 32], Padding::Same),
+        ([1, 8, 8, 256], [1, 1, 256, 256], Padding::Valid),
+    ] {
+        let patch = LocalPatch {
+            padding,
+            h_stride: 1,
+            v_stride: 1,
+            _data_format: DataFormat::NHWC,
+        };
+        let conv = Conv2D::<f32>::new(patch, 1);
+        let inputs = vec![mk(&data).into(), mk(&filter).into()];
+        bencher.bench_function(
+            &format!("Conv2D<f32>({:?} {:?} {:?})", data, filter, padding),
+            move |b| b.iter(|| conv.eval(inputs.clone()).unwrap()),
+        );
+    }
+}
+
+criterion_group!(benches, add, pad, matmul, conv);
+criterion_main!(benches);
@@ -9,12 +9,12 @@ use {OutputParameters, Parameters};
 
 /// Handles the `compare` subcommand.
 #[cfg(not(feature = "tensorflow"))]
-pub fn handle(_params: Parameters, _: OutputParameters) -> Result<()> {
+pub fn handle(_params: Parameters, _nan_equal: bool, _: OutputParameters) -> Result<()> {
     bail!("Comparison requires the `tensorflow` feature.")
 }
 
 #[cfg(feature = "tensorflow")]
-pub fn handle(params: Parameters, output_params: OutputParameters) -> Result<()> {
+pub fn handle(params: Parameters, nan_equal: bool, output_params: OutputParameters) -> Result<()> {
     use colored::Colorize;
     use format::Row;
 
@@ -91,7 +91,7 @@ pub fn handle(params: Parameters, output_params: OutputParameters) -> Result<()>
             _ => {
                 let tfd_output = state.outputs[n].as_ref().unwrap();
                 let views = tfd_output.iter().map(|m| &**m).collect::<Vec<&Tensor>>();
-                match compare_outputs(&tf_output, &views) {
+                match compare_outputs(&tf_output, &views, nan_equal) {
                     Err(_) => {
                         failures += 1;
                         let mismatches = tfd_output
@@ -101,18 +101,28 @@ pub fn handle(params: Parameters, output_params: OutputParameters) -> Result<()>
                                 let header = format!("Output {} TFD", n).yellow().bold();
 
                                 let reason = if n >= tf_output.len() {
-                                    "Too many outputs"
+                                    "Too many outputs".to_string()
                                 } else if tf_output[n].shape() != data.shape() {
-                                    "Wrong shape"
-                                } else if !tf_output[n].close_enough(data) {
-                                    "Too far away"
+                                    "Wrong shape".to_string()
                                 } else {
-                                    "Other error"
+                                    match close_enough(&tf_output[n], data, nan_equal) {
+                                        Ok(false) => "Too far away".to_string(),
+                                        Ok(true) => "Other error".to_string(),
+                                        Err(e) => format!("Can not compare the values: {}", e),
+                                    }
                                 };
 
-                                let infos = data.partial_dump(false).unwrap();
-
-                                format!("{} {} {}", header, reason.red().bold().to_string(), infos)
+                                let mut infos = data.partial_dump(false).unwrap();
+                                if let Some(Ok((error, index))) =
+                                    tf_output.get(n).map(|tf| tf.max_rel_error(data))
+                                {
+                                    infos.push_str(&format!(
+                                        " (max relative error {:e} at {:?})",
+                                        error, index
+                                    ));
+                                }
+
+                                format!("{} {} {}", header, reason.as_str().red().bold().to_string(), infos)
                             })
                             .collect::<Vec<_>>();
                         dn.more_lines.extend(mismatches);
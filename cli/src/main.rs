@@ -56,6 +56,7 @@ mod format;
 mod graphviz;
 mod profile;
 mod prune;
+mod run;
 mod rusage;
 mod utils;
 mod web;
@@ -95,7 +96,12 @@ fn main() {
         );
 
     let compare = clap::SubCommand::with_name("compare")
-        .help("Compares the output of tfdeploy and tensorflow on randomly generated input.");
+        .help("Compares the output of tfdeploy and tensorflow on randomly generated input.")
+        .arg(
+            Arg::with_name("nan_equal")
+                .long("nan-equal")
+                .help("Considers two NaN values at the same index as equal."),
+        );
     app = app.subcommand(output_options(compare));
 
     let dump = clap::SubCommand::with_name("dump")
@@ -121,6 +127,16 @@ fn main() {
         );
     app = app.subcommand(output_options(profile));
 
+    let run = clap::SubCommand::with_name("run")
+        .help("Runs tfdeploy on the given or randomly generated input and dumps the output.")
+        .arg(
+            Arg::with_name("until")
+                .long("until")
+                .takes_value(true)
+                .help("Stops the evaluation at the given node and dumps its output."),
+        );
+    app = app.subcommand(run);
+
     let analyse = clap::SubCommand::with_name("analyse")
         .help("Analyses the graph to infer properties about tensors (experimental).");
     app = app.subcommand(output_options(analyse));
@@ -262,9 +278,10 @@ impl InputParameters {
             "f64" => DataType::F64,
             "f32" => DataType::F32,
             "i32" => DataType::I32,
+            "i64" => DataType::I64,
             "i8" => DataType::I8,
             "u8" => DataType::U8,
-            _ => bail!("Type of the input should be f64, f32, i32, i8 or u8."),
+            _ => bail!("Type of the input should be f64, f32, i32, i64, i8 or u8."),
         };
 
         Ok(InputParameters {
@@ -308,6 +325,7 @@ impl InputParameters {
             DataType::F64 => for_type!(f64).into(),
             DataType::F32 => for_type!(f32).into(),
             DataType::I32 => for_type!(i32).into(),
+            DataType::I64 => for_type!(i64).into(),
             DataType::I8 => for_type!(i8).into(),
             DataType::U8 => for_type!(u8).into(),
             _ => unimplemented!(),
@@ -421,7 +439,11 @@ fn handle(matches: clap::ArgMatches) -> Result<()> {
         .unwrap_or(false);
 
     match matches.subcommand() {
-        ("compare", Some(m)) => compare::handle(params, OutputParameters::from_clap(m)?),
+        ("compare", Some(m)) => compare::handle(
+            params,
+            m.is_present("nan_equal"),
+            OutputParameters::from_clap(m)?,
+        ),
 
         ("dump", Some(m)) => dump::handle(params, OutputParameters::from_clap(m)?),
 
@@ -431,6 +453,8 @@ fn handle(matches: clap::ArgMatches) -> Result<()> {
             OutputParameters::from_clap(m)?,
         ),
 
+        ("run", Some(m)) => run::handle(params, m.value_of("until")),
+
         ("analyse", Some(m)) => analyse::handle(params, false, OutputParameters::from_clap(m)?),
 
         ("optimize", Some(m)) => analyse::handle(params, true, OutputParameters::from_clap(m)?),
@@ -188,8 +188,10 @@ pub fn handle_buffering(params: Parameters, output_params: OutputParameters) ->
         Tensor::F64(m) => split_inner!(Tensor::F64, m),
         Tensor::F32(m) => split_inner!(Tensor::F32, m),
         Tensor::I32(m) => split_inner!(Tensor::I32, m),
+        Tensor::I64(m) => split_inner!(Tensor::I64, m),
         Tensor::I8(m) => split_inner!(Tensor::I8, m),
         Tensor::U8(m) => split_inner!(Tensor::U8, m),
+        Tensor::Bool(m) => split_inner!(Tensor::Bool, m),
         Tensor::String(m) => split_inner!(Tensor::String, m),
     };
 
@@ -0,0 +1,98 @@
+use errors::*;
+use utils::random_tensor;
+use Parameters;
+
+use tfdeploy::{Model, Tensor};
+
+/// Handles the `run` subcommand.
+///
+/// With `until`, the evaluation stops at the given node and its outputs are
+/// dumped instead of the ones of the output node, which helps bisecting the
+/// graph for the node where the results go wrong.
+pub fn handle(params: Parameters, until: Option<&str>) -> Result<()> {
+    let ref model = params.tfd_model;
+    let input = params
+        .input
+        .as_ref()
+        .ok_or("Exactly one of <size> or <data> must be specified.")?;
+    let shape = input
+        .shape
+        .iter()
+        .cloned()
+        .collect::<Option<Vec<_>>>()
+        .ok_or("The run command doesn't support streaming dimensions.")?;
+
+    let inputs = params
+        .input_node_ids
+        .iter()
+        .map(|&id| {
+            let data = match input.data {
+                Some(ref data) => data.clone(),
+                None => random_tensor(shape.clone(), input.datatype),
+            };
+            (id, data)
+        })
+        .collect();
+
+    let target = match until {
+        Some(name) => model.node_id_by_name(name)?,
+        None => params.output_node_id,
+    };
+    let outputs = run_until(model, inputs, params.output_node_id, target)?;
+
+    let node = model.get_node_by_id(target)?;
+    for (ix, output) in outputs.iter().enumerate() {
+        println!("{}:{} ({}): {:?}", node.name, ix, node.op_name, output);
+    }
+
+    Ok(())
+}
+
+/// Runs the model following the evaluation order of `output`, but stops
+/// right after computing `until`, and returns the outputs of `until`.
+pub fn run_until(
+    model: &Model,
+    inputs: Vec<(usize, Tensor)>,
+    output: usize,
+    until: usize,
+) -> Result<Vec<Tensor>> {
+    let order = model.get_node_by_id(output)?.eval_order(model)?;
+    let position = order.iter().position(|&n| n == until).ok_or(format!(
+        "Node {} is not evaluated before the output node {}.",
+        model.get_node_by_id(until)?.name,
+        model.get_node_by_id(output)?.name
+    ))?;
+
+    let mut state = model.state();
+    for (id, data) in inputs {
+        state.set_value(id, data)?;
+    }
+    for &node in &order[..position + 1] {
+        // The inputs are already set.
+        if state.outputs[node].is_none() {
+            state.compute_one(node)?;
+        }
+    }
+
+    Ok(state.take(until)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfdeploy;
+
+    #[test]
+    fn until_input() {
+        let model = tfdeploy::for_path("../tests/models/plus3.pb").unwrap();
+        let input = model.node_id_by_name("input").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let data = Tensor::f32s(&[3], &[1.0, 2.0, 3.0]).unwrap();
+
+        let found = run_until(&model, vec![(input, data.clone())], output, input).unwrap();
+        assert_eq!(found, vec![data.clone()]);
+
+        let found = run_until(&model, vec![(input, data)], output, output).unwrap();
+        assert_eq!(found, vec![Tensor::f32s(&[3], &[4.0, 5.0, 6.0]).unwrap()]);
+    }
+}
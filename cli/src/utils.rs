@@ -5,9 +5,24 @@ use rand;
 use rand::Rng;
 use tfdeploy::{DataType, Tensor};
 
+/// Compares two tensors, optionally considering NaNs at the same index as
+/// equal.
+#[cfg(feature = "tensorflow")]
+pub fn close_enough(a: &Tensor, b: &Tensor, nan_equal: bool) -> CliResult<bool> {
+    if nan_equal {
+        Ok(a.close_enough_nan_equal(b)?)
+    } else {
+        Ok(a.close_enough(b)?)
+    }
+}
+
 /// Compares the outputs of a node in tfdeploy and tensorflow.
 #[cfg(feature = "tensorflow")]
-pub fn compare_outputs<Tensor1, Tensor2>(rtf: &[Tensor1], rtfd: &[Tensor2]) -> CliResult<()>
+pub fn compare_outputs<Tensor1, Tensor2>(
+    rtf: &[Tensor1],
+    rtfd: &[Tensor2],
+    nan_equal: bool,
+) -> CliResult<()>
 where
     Tensor1: ::std::borrow::Borrow<Tensor>,
     Tensor2: ::std::borrow::Borrow<Tensor>,
@@ -29,7 +44,7 @@ where
                 mtfd.borrow().shape()
             )
         } else {
-            if !mtf.borrow().close_enough(mtfd.borrow()) {
+            if !close_enough(mtf.borrow(), mtfd.borrow(), nan_equal)? {
                 bail!(
                     "Data mismatch: tf={:?}, tfd={:?}",
                     mtf.borrow(),
@@ -55,6 +70,7 @@ pub fn random_tensor(sizes: Vec<usize>, datatype: DataType) -> Tensor {
         DataType::F64 => for_type!(f64).into(),
         DataType::F32 => for_type!(f32).into(),
         DataType::I32 => for_type!(i32).into(),
+        DataType::I64 => for_type!(i64).into(),
         DataType::I8 => for_type!(i8).into(),
         DataType::U8 => for_type!(u8).into(),
         _ => unimplemented!("missing type"),
@@ -41,8 +41,10 @@ enum TensorHolder {
     F64(Tensor<f64>),
     F32(Tensor<f32>),
     I32(Tensor<i32>),
+    I64(Tensor<i64>),
     U8(Tensor<u8>),
     I8(Tensor<i8>),
+    Bool(Tensor<bool>),
     String(Tensor<i8>),
 }
 
@@ -61,8 +63,10 @@ impl From<TfdTensor> for TensorHolder {
             TfdTensor::F64(a) => TensorHolder::F64(Self::to_tensor(a)),
             TfdTensor::F32(a) => TensorHolder::F32(Self::to_tensor(a)),
             TfdTensor::I32(a) => TensorHolder::I32(Self::to_tensor(a)),
+            TfdTensor::I64(a) => TensorHolder::I64(Self::to_tensor(a)),
             TfdTensor::U8(a) => TensorHolder::U8(Self::to_tensor(a)),
             TfdTensor::I8(a) => TensorHolder::I8(Self::to_tensor(a)),
+            TfdTensor::Bool(a) => TensorHolder::Bool(Self::to_tensor(a)),
             TfdTensor::String(a) => TensorHolder::String(Self::to_tensor(a)),
         }
     }
@@ -92,8 +96,10 @@ impl Tensorflow {
                 TensorHolder::F64(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::F32(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::I32(ref it) => step.add_feed(&op, 0, &it),
+                TensorHolder::I64(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::U8(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::I8(ref it) => step.add_feed(&op, 0, &it),
+                TensorHolder::Bool(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::String(ref it) => step.add_feed(&op, 0, &it),
             }
         }
@@ -127,8 +133,10 @@ impl Tensorflow {
                 TensorHolder::F64(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::F32(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::I32(ref it) => step.add_feed(&op, 0, &it),
+                TensorHolder::I64(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::U8(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::I8(ref it) => step.add_feed(&op, 0, &it),
+                TensorHolder::Bool(ref it) => step.add_feed(&op, 0, &it),
                 TensorHolder::String(ref it) => step.add_feed(&op, 0, &it),
             }
         }
@@ -177,6 +185,8 @@ fn convert_output(
         DataType::Int8 => convert!(I8),
         DataType::String => convert!(String),
         DataType::Int32 => convert!(I32),
+        DataType::Int64 => convert!(I64),
+        DataType::Bool => convert!(Bool),
         t => bail!("Missing Tensor to TfdTensor for type {:?}", t),
     };
 
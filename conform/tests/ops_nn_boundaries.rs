@@ -0,0 +1,106 @@
+#![allow(non_snake_case)]
+extern crate conform;
+extern crate ndarray;
+extern crate tensorflow;
+extern crate tfdeploy;
+
+use conform::*;
+use ndarray::prelude::*;
+use tfdeploy::tfpb;
+use tfdeploy::tfpb::types::DataType::DT_FLOAT;
+use tfdeploy::Tensor as TfdTensor;
+
+// Deterministic sweep over the shapes where SAME padding is asymmetric, that
+// is when the stride does not divide the input size.
+const SIZES: &[usize] = &[1, 2, 3, 4, 5, 7, 8];
+const KERNELS: &[usize] = &[1, 2, 3, 4];
+const STRIDES: &[usize] = &[1, 2, 3];
+
+fn ramp(shape: (usize, usize, usize, usize)) -> TfdTensor {
+    let len = shape.0 * shape.1 * shape.2 * shape.3;
+    let data = (0..len).map(|i| (i % 17) as f32 - 8.0).collect();
+    Array::from_vec(data).into_shape(shape).unwrap().into()
+}
+
+fn sweep<F>(mut check: F)
+where
+    F: FnMut(usize, usize, usize, usize, &str),
+{
+    for &h in SIZES {
+        for &w in SIZES {
+            for &k in KERNELS {
+                for &stride in STRIDES {
+                    for padding in &["SAME", "VALID"] {
+                        if *padding == "VALID" && (k > h || k > w) {
+                            continue;
+                        }
+                        check(h, w, k, stride, padding);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pool(op: &str) {
+    sweep(|h, w, k, stride, padding| {
+        let graph = tfpb::graph()
+            .node(placeholder_f32("data"))
+            .node(
+                tfpb::node()
+                    .name("pool")
+                    .op(op)
+                    .input("data")
+                    .attr("T", DT_FLOAT)
+                    .attr("strides", vec![1, stride as i64, stride as i64, 1])
+                    .attr("ksize", vec![1, k as i64, k as i64, 1])
+                    .attr("padding", padding),
+            )
+            .write_to_bytes()
+            .unwrap();
+        if let Err(e) = compare(&graph, vec![("data", ramp((1, h, w, 2)))], "pool") {
+            panic!(
+                "{} input {}x{} kernel {} stride {} {}: {:?}",
+                op, h, w, k, stride, padding, e
+            );
+        }
+    })
+}
+
+#[test]
+fn maxpool_boundaries() {
+    pool("MaxPool")
+}
+
+#[test]
+fn avgpool_boundaries() {
+    pool("AvgPool")
+}
+
+#[test]
+fn conv_boundaries() {
+    sweep(|h, w, k, stride, padding| {
+        let graph = tfpb::graph()
+            .node(placeholder_f32("data"))
+            .node(placeholder_f32("kernel"))
+            .node(
+                tfpb::node()
+                    .name("conv")
+                    .op("Conv2D")
+                    .input("data")
+                    .input("kernel")
+                    .attr("T", DT_FLOAT)
+                    .attr("strides", vec![1, stride as i64, stride as i64, 1])
+                    .attr("padding", padding),
+            )
+            .write_to_bytes()
+            .unwrap();
+        let inputs = vec![("data", ramp((1, h, w, 2))), ("kernel", ramp((k, k, 2, 3)))];
+        if let Err(e) = compare(&graph, inputs, "conv") {
+            panic!(
+                "Conv2D input {}x{} kernel {} stride {} {}: {:?}",
+                h, w, k, stride, padding, e
+            );
+        }
+    })
+}
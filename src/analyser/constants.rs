@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use super::prelude::*;
 use super::Result;
 use ops::OpBuilder;
@@ -110,7 +112,7 @@ fn build_const_node(id: usize, name: String, tensor: TensorProto) -> Node {
         name,
         op_name: "Const".to_string(),
         inputs: vec![],
-        op: OpBuilder::new().build(&node_def).unwrap(),
+        op: Arc::new(OpBuilder::new().build(&node_def).unwrap()),
     }
 }
 
@@ -176,9 +178,9 @@ pub fn propagate_constants(analyser: &mut Analyser) -> Result<()> {
                 let predecessors = &mut analyser.nodes[edge.to_node.unwrap()].inputs;
                 let position = predecessors
                     .iter()
-                    .position(|&(i, _)| i == old_node_id)
+                    .position(|&(i, port)| i == old_node_id && port == Some(edge.from_out))
                     .unwrap();
-                predecessors[position] = (node_id, None);
+                predecessors[position] = (node_id, Some(0));
             }
 
             // Attach the edge to its new source.
@@ -258,8 +258,13 @@ where
         } else {
             // We want to set k * x <- m, where k and m != 0, so we will try
             // to set x <- m / k using a checked division. This way, if m is
-            // not divisible by k, we will return Err instead of panicking.
-            let div = m.checked_div(&(*k).into()).ok_or(format!(
+            // not divisible by k, we will return Err instead of panicking or
+            // silently truncating the quotient.
+            let div = match m {
+                IntFact::Only(m) if *k != 0 && m % k != 0 => None,
+                _ => m.checked_div(&(*k).into()),
+            };
+            let div = div.ok_or(format!(
                 "Cannot set the value of ({:?}, _) to {:?} because \
                  {:?} is not divisible by {:?}.",
                 k, m, m, k
@@ -367,3 +372,36 @@ where
         ProductExpression(k as isize, e.into_expr())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::interface::TensorsProxy;
+    use analyser::types::TensorFact;
+
+    #[test]
+    fn test_product_expression() {
+        let inputs = TensorsProxy::new(vec![0].into());
+        let double: ProductExpression<_> = (2, &inputs[0].rank).into_expr();
+        let mut context = Context::new(vec![TensorFact::new()], vec![]);
+
+        assert_eq!(double.get(&context).unwrap(), IntFact::Any);
+        assert!(double.set(&mut context, 7isize.into()).is_err());
+        double.set(&mut context, 6isize.into()).unwrap();
+        assert_eq!(double.get(&context).unwrap(), 6isize.into());
+    }
+
+    #[test]
+    fn test_product_expression_zero() {
+        let inputs = TensorsProxy::new(vec![0].into());
+        let zero: ProductExpression<_> = (0, &inputs[0].rank).into_expr();
+        let mut context = Context::new(vec![TensorFact::new()], vec![]);
+
+        zero.set(&mut context, 0isize.into()).unwrap();
+        assert!(zero.set(&mut context, 2isize.into()).is_err());
+
+        let opposite: ProductExpression<_> = (-1, &inputs[0].rank).into_expr();
+        opposite.set(&mut context, 0isize.into()).unwrap();
+        assert_eq!(opposite.get(&context).unwrap(), 0isize.into());
+    }
+}
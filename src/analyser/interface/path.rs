@@ -298,7 +298,7 @@ fn get_shape_path(shape: &ShapeFact, path: &[isize]) -> Result<Wrapped> {
 fn get_value_path(value: &ValueFact, path: &[isize]) -> Result<Wrapped> {
     trace!("get_value_path path:{:?} value:{:?}", path, value);
     // Return the whole tensor.
-    if path == &[-1] || path == &[] {
+    if path == &[-1] || path.is_empty() {
         return Ok(value.clone().wrap());
     }
 
@@ -317,6 +317,7 @@ fn get_value_path(value: &ValueFact, path: &[isize]) -> Result<Wrapped> {
         None => Ok(IntFact::default().wrap()),
         Some(tensor) => match tensor {
             Tensor::I32(array) => inner!(array),
+            Tensor::I64(array) => inner!(array),
             Tensor::I8(array) => inner!(array),
             Tensor::U8(array) => inner!(array),
             _ => bail!(
@@ -259,6 +259,31 @@ impl<'s, T: Output + Fact, E: Expression<Output = T>, C: Output> fmt::Debug
     }
 }
 
+/// The `fail` rule.
+/// It makes the inference fail with the given message, e.g. when a `given`
+/// closure finds that the known values can not be right.
+///
+/// It can be added to the solver via the following method:
+/// ```text
+/// solver.fail("Some message");
+/// ```
+#[derive(Debug, new)]
+struct FailRule {
+    message: String,
+}
+
+impl<'rules> Rule<'rules> for FailRule {
+    /// Fails, whatever the context.
+    fn apply(&self, _context: &mut Context) -> Result<(bool, Vec<Box<Rule<'rules> + 'rules>>)> {
+        bail!("{}", self.message)
+    }
+
+    /// Returns the paths that the rule depends on.
+    fn get_paths(&self) -> Vec<&Path> {
+        vec![]
+    }
+}
+
 /// A declarative constraint solver for tensors.
 #[derive(Default)]
 pub struct Solver<'rules> {
@@ -299,7 +324,8 @@ impl<'rules> Solver<'rules> {
                 }
 
                 trace!("  Applying rule {:?}", rule);
-                let (step_used, mut step_added) = rule.apply(&mut context)?;
+                let (step_used, mut step_added) = rule.apply(&mut context)
+                    .map_err(|e| format!("While applying rule {:?}: {}", rule, e))?;
                 *used |= step_used;
 
                 // There is a change if the rule was used, or if it added new rules.
@@ -396,6 +422,12 @@ impl<'rules> Solver<'rules> {
         self.rules.push(Box::new(rule));
         self
     }
+
+    /// Makes the inference fail with the given message.
+    pub fn fail<S: Into<String>>(&mut self, message: S) -> &mut Solver<'rules> {
+        self.rules.push(Box::new(FailRule::new(message.into())));
+        self
+    }
 }
 
 #[cfg(test)]
@@ -516,6 +548,89 @@ mod tests {
         assert_eq!(facts, expected);
     }
 
+    #[test]
+    fn solver_incompatible_ranks() {
+        let (mut solver, inputs, _) = bootstrap();
+        solver.equals(&inputs[0].rank, 2);
+
+        let input = TensorFact {
+            shape: shapefact![1, 2, 3],
+            ..TensorFact::new()
+        };
+        let error = solver.infer((vec![input], vec![])).unwrap_err();
+        assert!(format!("{}", error).contains("Impossible to unify"));
+    }
+
+    #[test]
+    fn solver_equals_zero() {
+        let (mut solver, inputs, outputs) = bootstrap();
+        solver.equals_zero(wrap!(
+            (-1, &outputs[0].shape[0]),
+            (1, &inputs[0].shape[0]),
+            2
+        ));
+
+        let input = TensorFact {
+            shape: shapefact![3],
+            ..TensorFact::new()
+        };
+        let output = TensorFact {
+            shape: shapefact![_],
+            ..TensorFact::new()
+        };
+        let facts = solver.infer((vec![input.clone()], vec![output])).unwrap();
+        let expected = TensorFact {
+            shape: shapefact![5],
+            ..TensorFact::new()
+        };
+        assert_eq!(facts, (vec![input], vec![expected]));
+    }
+
+    #[test]
+    fn solver_equals_zero_indivisible() {
+        let (mut solver, inputs, outputs) = bootstrap();
+        solver.equals_zero(wrap!((-2, &outputs[0].shape[0]), &inputs[0].shape[0]));
+
+        let input = TensorFact {
+            shape: shapefact![3],
+            ..TensorFact::new()
+        };
+        let output = TensorFact {
+            shape: shapefact![_],
+            ..TensorFact::new()
+        };
+        assert!(solver.infer((vec![input], vec![output])).is_err());
+    }
+
+    #[test]
+    fn solver_deferred_given() {
+        let inputs = TensorsProxy::new(vec![0].into());
+        let outputs = TensorsProxy::new(vec![1].into());
+        let mut solver = Solver::default();
+        let input = &inputs[0];
+        // The rank is only known once the second rule has fired.
+        solver
+            .given(&input.rank, move |solver, rank: usize| {
+                for dim in 0..rank {
+                    solver.equals(&input.shape[dim], 2);
+                }
+            })
+            .equals(&input.rank, &outputs[0].rank);
+
+        let output = TensorFact {
+            shape: shapefact![_, _],
+            ..TensorFact::new()
+        };
+        let facts = solver
+            .infer((vec![TensorFact::new()], vec![output.clone()]))
+            .unwrap();
+        let expected = TensorFact {
+            shape: shapefact![2, 2],
+            ..TensorFact::new()
+        };
+        assert_eq!(facts, (vec![expected], vec![output]));
+    }
+
     #[test]
     #[should_panic]
     fn solver_wrong_constant() {
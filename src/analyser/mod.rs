@@ -110,6 +110,7 @@ pub struct Analyser {
 
     // The graph being analysed.
     pub nodes: Vec<Node>,
+    pub annotations: HashMap<(usize, usize), TensorFact>,
     pub edges: Vec<Edge>,
     pub prev_edges: Vec<Vec<usize>>,
     pub next_edges: Vec<Vec<usize>>,
@@ -131,18 +132,21 @@ impl Analyser {
     /// take much longer to complete.
     pub fn new(model: Model, output: usize) -> Result<Analyser> {
         let nodes = model.nodes;
+        let annotations = model.annotations;
         let mut edges = vec![];
         let mut prev_edges = vec![Vec::new(); nodes.len() + 1];
         let mut next_edges = vec![Vec::new(); nodes.len() + 1];
 
         for node in &nodes {
-            for (ix, input) in node.inputs.iter().enumerate() {
+            // Control inputs only order the computations, they carry no data.
+            let data_inputs = node.inputs.iter().filter_map(|&(id, port)| port.map(|p| (id, p)));
+            for (ix, input) in data_inputs.enumerate() {
                 let id = edges.len();
 
                 edges.push(Edge {
                     id,
                     from_node: Some(input.0),
-                    from_out: input.1.unwrap_or(0),
+                    from_out: input.1,
                     to_node: Some(node.id),
                     to_input: ix,
                     fact: TensorFact::new(),
@@ -166,6 +170,18 @@ impl Analyser {
 
         next_edges[output].push(special_edge_id);
 
+        // Seed the edges with the user-provided facts.
+        for (&(node, port), fact) in &annotations {
+            if node >= nodes.len() {
+                bail!("There is no node with index {:?}.", node);
+            }
+            for &e in &next_edges[node] {
+                if edges[e].from_out == port {
+                    edges[e].fact = unify(fact, &edges[e].fact)?;
+                }
+            }
+        }
+
         // Compute an execution plan for the graph.
         let plan = Plan::for_nodes(&nodes, &[output])?.order;
         let current_pass = 0;
@@ -177,6 +193,7 @@ impl Analyser {
         Ok(Analyser {
             output,
             nodes,
+            annotations,
             edges,
             prev_edges,
             next_edges,
@@ -211,6 +228,7 @@ impl Analyser {
         Model {
             nodes: self.nodes,
             nodes_by_name,
+            annotations: self.annotations,
         }
     }
 
@@ -259,6 +277,11 @@ impl Analyser {
 
         info!("Deleted {:?} unused nodes.", deleted);
 
+        self.annotations = self.annotations
+            .drain()
+            .filter_map(|((id, port), fact)| node_mapping[id].map(|id| ((id, port), fact)))
+            .collect();
+
         // Update the nodes and edges to use the new indices.
         for node in &mut self.nodes {
             node.id = node_mapping[node.id].unwrap();
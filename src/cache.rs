@@ -0,0 +1,153 @@
+//! A result cache for models serving repeated requests.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use {Model, Plan, Result, Tensor};
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    key: u64,
+    inputs: Vec<(usize, Tensor)>,
+    outputs: Vec<Tensor>,
+}
+
+/// Wraps a model and remembers the outputs of its most recent runs, so that
+/// a request with exactly the same inputs as a cached one is not computed
+/// again.
+///
+/// The least recently used results are evicted first. Models computing their
+/// output through an operator which is not stateless (see
+/// `Op::is_stateless`) are never cached.
+#[derive(Clone)]
+pub struct CachedModel {
+    model: Model,
+    output: usize,
+    capacity: usize,
+    cacheable: bool,
+    /// The cached results, from the least to the most recently used.
+    entries: VecDeque<CacheEntry>,
+    hits: usize,
+}
+
+impl CachedModel {
+    /// Wraps a model computing `output`, keeping at most `capacity` results.
+    pub fn new(model: Model, output: usize, capacity: usize) -> Result<CachedModel> {
+        let plan = Plan::for_model(&model, &[output])?;
+        let cacheable = plan.order.iter().all(|&n| model.nodes[n].op.is_stateless());
+        Ok(CachedModel {
+            model,
+            output,
+            capacity,
+            cacheable,
+            entries: VecDeque::new(),
+            hits: 0,
+        })
+    }
+
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Returns whether the results of the model can be cached.
+    pub fn is_cacheable(&self) -> bool {
+        self.cacheable
+    }
+
+    /// Returns the number of runs answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Runs the model, or returns the cached outputs if it has already been
+    /// run with the same inputs.
+    pub fn run(&mut self, mut inputs: Vec<(usize, Tensor)>) -> Result<Vec<Tensor>> {
+        if !self.cacheable || self.capacity == 0 {
+            return self.model.run(inputs, self.output);
+        }
+        inputs.sort_by_key(|i| i.0);
+        let mut hasher = DefaultHasher::new();
+        for &(id, ref tensor) in &inputs {
+            id.hash(&mut hasher);
+            tensor.content_hash().hash(&mut hasher);
+        }
+        let key = hasher.finish();
+
+        // The hash only narrows the search, the inputs must be equal.
+        if let Some(ix) = self.entries
+            .iter()
+            .position(|e| e.key == key && e.inputs == inputs)
+        {
+            let entry = self.entries.remove(ix).unwrap();
+            let outputs = entry.outputs.clone();
+            self.entries.push_back(entry);
+            self.hits += 1;
+            return Ok(outputs);
+        }
+
+        let outputs = self.model.run(inputs.clone(), self.output)?;
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(CacheEntry {
+            key,
+            inputs,
+            outputs: outputs.clone(),
+        });
+        Ok(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfpb;
+    use tfpb::types::DataType::DT_FLOAT;
+
+    fn plus3() -> CachedModel {
+        let model = Model::for_path("tests/models/plus3.pb").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        CachedModel::new(model, output, 2).unwrap()
+    }
+
+    #[test]
+    fn repeated_inputs_hit_the_cache() {
+        let mut cached = plus3();
+        let input = cached.model().node_id_by_name("input").unwrap();
+        let run = |cached: &mut CachedModel, x: f32| {
+            cached
+                .run(vec![(input, Tensor::f32s(&[1], &[x]).unwrap())])
+                .unwrap()
+        };
+        assert_eq!(run(&mut cached, 1.0), vec![Tensor::f32s(&[1], &[4.0]).unwrap()]);
+        assert_eq!(cached.hits(), 0);
+        assert_eq!(run(&mut cached, 1.0), vec![Tensor::f32s(&[1], &[4.0]).unwrap()]);
+        assert_eq!(cached.hits(), 1);
+
+        // 1.0 is the most recently used, so 2.0 gets evicted by 3.0.
+        run(&mut cached, 2.0);
+        run(&mut cached, 1.0);
+        run(&mut cached, 3.0);
+        assert_eq!(cached.hits(), 2);
+        run(&mut cached, 2.0);
+        assert_eq!(cached.hits(), 2);
+        run(&mut cached, 3.0);
+        assert_eq!(cached.hits(), 3);
+    }
+
+    #[test]
+    fn unknown_ops_are_not_cached() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(tfpb::node().op("Mystery").name("output").input("input"));
+        let model = Model::new(graph).unwrap();
+        let cached = CachedModel::new(model, 1, 2).unwrap();
+        assert!(!cached.is_cacheable());
+        assert!(plus3().is_cacheable());
+    }
+}
@@ -0,0 +1,232 @@
+//! A C-compatible inference API.
+//!
+//! This module is only built with the `capi` feature. To get a shared
+//! library for non-Rust hosts, build the crate as a `cdylib` with the feature
+//! enabled.
+//!
+//! All functions returning a `c_int` return 0 on success, and -1 on failure,
+//! in which case `tfd_last_error` describes what went wrong. A panic inside
+//! the library is caught and reported the same way. Datatypes are
+//! given with TensorFlow `DataType` enum values (e.g. 1 for `DT_FLOAT`), and
+//! tensor data with native endianness, in row-major order.
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use protobuf::ProtobufEnum;
+
+use {DataType, Model, Result, Tensor};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// A model loaded through the C API, along with its pending inputs and
+/// last computed output.
+pub struct TfdModel {
+    model: Model,
+    inputs: Vec<(usize, Tensor)>,
+    output: Option<Tensor>,
+}
+
+/// Runs the body of an API function, turning its errors and panics into
+/// `failure` and a message for `tfd_last_error`, as unwinding across the C
+/// boundary is undefined behaviour.
+fn wrap<T, F: FnOnce() -> Result<T>>(f: F, failure: T) -> T {
+    let message = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(t)) => return t,
+        Ok(Err(e)) => format!("{}", e),
+        Err(payload) => {
+            let cause = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown cause".to_string());
+            format!("Panic: {}", cause)
+        }
+    };
+    let message =
+        CString::new(message).unwrap_or_else(|_| CString::new("Invalid error message").unwrap());
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    failure
+}
+
+unsafe fn string<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        bail!("Unexpected null string");
+    }
+    Ok(CStr::from_ptr(s).to_str()?)
+}
+
+unsafe fn model<'a>(model: *mut TfdModel) -> Result<&'a mut TfdModel> {
+    model.as_mut().ok_or("Unexpected null model".into())
+}
+
+/// Returns the message of the last error which happened in this thread,
+/// or null. The string is owned by the library.
+#[no_mangle]
+pub extern "C" fn tfd_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match *last.borrow() {
+        Some(ref message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Loads a model from a protobuf file. Returns null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn tfd_model_load(path: *const c_char) -> *mut TfdModel {
+    wrap(
+        || {
+            let model = Model::for_path(string(path)?)?;
+            Ok(Box::into_raw(Box::new(TfdModel {
+                model,
+                inputs: vec![],
+                output: None,
+            })))
+        },
+        ptr::null_mut(),
+    )
+}
+
+/// Releases a model returned by `tfd_model_load`.
+#[no_mangle]
+pub unsafe extern "C" fn tfd_model_free(model: *mut TfdModel) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+/// Sets the value of an input node for the next run. The data is copied.
+#[no_mangle]
+pub unsafe extern "C" fn tfd_model_set_input(
+    model: *mut TfdModel,
+    name: *const c_char,
+    datatype: c_int,
+    shape: *const usize,
+    rank: usize,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    wrap(
+        || {
+            let model = self::model(model)?;
+            let id = model.model.node_id_by_name(string(name)?)?;
+            let datatype = ::tfpb::types::DataType::from_i32(datatype)
+                .ok_or(format!("Unknown datatype {}", datatype))?;
+            if (shape.is_null() && rank > 0) || (data.is_null() && len > 0) {
+                bail!("Unexpected null buffer");
+            }
+            let shape = if rank > 0 {
+                slice::from_raw_parts(shape, rank)
+            } else {
+                &[]
+            };
+            let data = if len > 0 {
+                slice::from_raw_parts(data, len)
+            } else {
+                &[]
+            };
+            let tensor = Tensor::from_raw_bytes(DataType::from_pb(&datatype)?, shape, data)?;
+            model.inputs.retain(|&(i, _)| i != id);
+            model.inputs.push((id, tensor));
+            Ok(0)
+        },
+        -1,
+    )
+}
+
+/// Runs the model up to the given output node, using the inputs set so far.
+#[no_mangle]
+pub unsafe extern "C" fn tfd_model_run(model: *mut TfdModel, output: *const c_char) -> c_int {
+    wrap(
+        || {
+            let model = self::model(model)?;
+            let output = model.model.node_id_by_name(string(output)?)?;
+            let mut outputs = model.model.run(model.inputs.clone(), output)?;
+            if outputs.is_empty() {
+                bail!("Node produced no output");
+            }
+            model.output = Some(outputs.remove(0));
+            Ok(0)
+        },
+        -1,
+    )
+}
+
+/// Gets the rank of the last output, and writes its dimensions to `shape`
+/// if it is not null. `shape` must have room for at least `max_rank` values.
+#[no_mangle]
+pub unsafe extern "C" fn tfd_model_output_shape(
+    model: *mut TfdModel,
+    shape: *mut usize,
+    max_rank: usize,
+    rank: *mut usize,
+) -> c_int {
+    wrap(
+        || {
+            let model = self::model(model)?;
+            let output = model.output.as_ref().ok_or("No output computed")?;
+            let dims = output.shape();
+            if !rank.is_null() {
+                *rank = dims.len();
+            }
+            if !shape.is_null() {
+                if dims.len() > max_rank {
+                    bail!("Output has rank {}, above {}", dims.len(), max_rank);
+                }
+                ptr::copy_nonoverlapping(dims.as_ptr(), shape, dims.len());
+            }
+            Ok(0)
+        },
+        -1,
+    )
+}
+
+/// Gets the datatype and the raw bytes of the last output. The buffer is
+/// owned by the model, and stays valid until the next run.
+#[no_mangle]
+pub unsafe extern "C" fn tfd_model_output_data(
+    model: *mut TfdModel,
+    datatype: *mut c_int,
+    data: *mut *const u8,
+    len: *mut usize,
+) -> c_int {
+    wrap(
+        || {
+            let model = self::model(model)?;
+            let output = model.output.as_ref().ok_or("No output computed")?;
+            let bytes = output.as_bytes()?;
+            if !datatype.is_null() {
+                *datatype = output.datatype().to_pb().value();
+            }
+            if !data.is_null() {
+                *data = bytes.as_ptr();
+            }
+            if !len.is_null() {
+                *len = bytes.len();
+            }
+            Ok(0)
+        },
+        -1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_catches_panics() {
+        assert_eq!(wrap(|| Ok(1), -1), 1);
+        assert_eq!(wrap(|| -> Result<c_int> { bail!("failed") }, -1), -1);
+        let error = unsafe { CStr::from_ptr(tfd_last_error()) };
+        assert_eq!(error.to_str().unwrap(), "failed");
+
+        assert_eq!(wrap(|| -> Result<c_int> { panic!("boom {}", 1) }, -1), -1);
+        let error = unsafe { CStr::from_ptr(tfd_last_error()) };
+        assert_eq!(error.to_str().unwrap(), "Panic: boom 1");
+    }
+}
@@ -4,6 +4,7 @@ error_chain!{
     foreign_links {
         Image(::image::ImageError) #[cfg(features="image_ops")];
         Io(::std::io::Error);
+        Json(::serde_json::Error) #[cfg(feature = "serialize")];
         NdarrayShape(::ndarray::ShapeError);
         Protobuf(::protobuf::ProtobufError);
         StrUtf8(::std::str::Utf8Error);
@@ -11,5 +12,13 @@ error_chain!{
     }
     errors {
         TFString {}
+        ControlFlowUnsupported(op: String, node: String) {
+            description("unsupported control flow operator")
+            display(
+                "Node {} is a {} control flow operator, which is not supported. \
+                 Freeze the graph and strip its control flow before loading it.",
+                node, op
+            )
+        }
     }
 }
@@ -39,6 +39,8 @@ extern crate derive_new;
 extern crate error_chain;
 #[cfg(feature = "image_ops")]
 extern crate image;
+#[cfg(feature = "gzip")]
+extern crate flate2;
 extern crate itertools;
 #[allow(unused_imports)]
 #[macro_use]
@@ -46,6 +48,10 @@ extern crate log;
 #[allow(unused_imports)]
 #[macro_use]
 extern crate ndarray;
+#[cfg(feature = "rayon")]
+extern crate ndarray_parallel;
+#[cfg(feature = "rayon")]
+extern crate rayon_core;
 extern crate num_traits;
 extern crate protobuf;
 #[macro_use]
@@ -60,24 +66,36 @@ extern crate simplelog;
 #[cfg(feature = "serialize")]
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "serialize")]
+extern crate serde_json;
 #[macro_use]
 extern crate downcast_rs;
 
 #[macro_use]
 pub mod analyser;
+pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod errors;
 pub mod ops;
+mod optim;
+pub mod signature;
 pub mod streaming;
 pub mod tensor;
 pub mod tfpb;
 
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::{fs, path, str};
 
 // use analyser::prelude::*;
 use analyser::helpers::tensor_to_fact;
+use analyser::TensorFact;
+pub use cache::CachedModel;
 pub use errors::*;
-use ops::{Op, OpBuffer, TensorView};
+use ops::{InferenceOp, Op, OpBuffer, TensorView};
+pub use optim::Pass;
+pub use signature::{ModelSignature, TensorSignature};
 pub use tensor::{DataType, Tensor};
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
@@ -87,7 +105,7 @@ pub struct Node {
     pub name: String,
     pub op_name: String,
     pub inputs: Vec<(usize, Option<usize>)>,
-    pub op: Box<Op>,
+    pub op: Arc<Box<Op>>,
 }
 
 impl Node {
@@ -113,7 +131,7 @@ impl Node {
     }
 
     pub fn op(&self) -> &Op {
-        &*self.op
+        &**self.op
     }
 }
 
@@ -125,6 +143,9 @@ pub fn for_path<P: AsRef<path::Path>>(p: P) -> Result<Model> {
 #[derive(Debug)]
 pub struct Plan {
     pub order: Vec<usize>,
+    targets: Vec<usize>,
+    pinned: HashSet<usize>,
+    free_intermediates: bool,
 }
 
 impl Plan {
@@ -149,8 +170,12 @@ impl Plan {
                 for i in node.inputs.iter() {
                     if !done.contains(i.0) {
                         computable = false;
-                        done_something = true;
-                        needed.insert(i.0.clone());
+                        // Inputs already needed (e.g. shared with another
+                        // target) are not progress: counting them would loop
+                        // forever on a cycle.
+                        if needed.insert(i.0) {
+                            done_something = true;
+                        }
                     }
                 }
                 if computable {
@@ -169,19 +194,152 @@ impl Plan {
                 Err(format!("Could not plan for node {}", node.name))?
             }
         }
-        Ok(Plan { order })
+        Ok(Plan {
+            order,
+            targets: targets.to_vec(),
+            pinned: HashSet::new(),
+            free_intermediates: false,
+        })
     }
 
-    pub fn run(&self, state: &mut ModelState) -> Result<()> {
+    /// Releases the outputs of intermediate nodes as soon as their last
+    /// consumer in the plan has been computed.
+    pub fn set_free_intermediates(&mut self, free: bool) {
+        self.free_intermediates = free;
+    }
+
+    /// Exempts some nodes from being released when running with
+    /// `free_intermediates`, so that their outputs can be inspected after
+    /// the run.
+    pub fn pin(&mut self, nodes: &[usize]) {
+        self.pinned.extend(nodes);
+    }
+
+    /// Checks that the plan can run on inputs matching the given facts,
+    /// without computing anything.
+    ///
+    /// The inference rules of every node are applied once, in the plan order,
+    /// which checks the number and the datatypes of their inputs. Returns the
+    /// first inconsistency found, along with the name of the faulty node.
+    pub fn validate(&self, model: &Model, inputs: &[(usize, TensorFact)]) -> Result<()> {
+        let mut facts: HashMap<usize, Vec<TensorFact>> = HashMap::new();
         for &n in &self.order {
+            let node = &model.nodes[n];
+            // Control inputs only order the computations, like in
+            // `ModelState::compute_one`.
+            let input_facts = node.inputs
+                .iter()
+                .filter_map(|&(i, port)| {
+                    port.map(|port| {
+                        facts
+                            .get(&i)
+                            .and_then(|f| f.get(port))
+                            .cloned()
+                            .unwrap_or_else(TensorFact::new)
+                    })
+                })
+                .collect();
+
+            // Like the analyser, only consider the ports actually consumed.
+            let ports = model
+                .nodes
+                .iter()
+                .flat_map(|m| m.inputs.iter())
+                .filter(|i| i.0 == n)
+                .map(|i| i.1.unwrap_or(0) + 1)
+                .max()
+                .unwrap_or(1);
+            let mut output_facts = vec![TensorFact::new(); ports];
+            if let Some(&(_, ref fact)) = inputs.iter().find(|i| i.0 == n) {
+                output_facts[0] = fact.clone();
+            }
+
+            let (_, output_facts) = node.op
+                .infer(input_facts, output_facts)
+                .map_err(|e| format!("While validating {} ({}): {}", node.name, node.op_name, e))?;
+            facts.insert(n, output_facts);
+        }
+        Ok(())
+    }
+
+    /// Builds a pool of `threads` threads for `run_parallel`. With `0`, rayon
+    /// picks the number of threads as for its global pool.
+    ///
+    /// Starting the threads is costly, so the pool should be built once and
+    /// reused across runs.
+    #[cfg(feature = "rayon")]
+    pub fn thread_pool(threads: usize) -> Result<rayon_core::ThreadPool> {
+        Ok(rayon_core::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| format!("Could not build a pool of {} threads: {}", threads, e))?)
+    }
+
+    /// Runs the plan on the given pool, instead of the global rayon pool, so
+    /// that the operators splitting their work across threads use at most
+    /// as many threads as the pool has.
+    #[cfg(feature = "rayon")]
+    pub fn run_parallel(&self, state: &mut ModelState, pool: &rayon_core::ThreadPool) -> Result<()> {
+        pool.install(|| self.run(state))
+    }
+
+    pub fn run(&self, state: &mut ModelState) -> Result<()> {
+        self.run_observing(state, |_, _| ())
+    }
+
+    /// Runs the plan like `run`, calling `observe` with the position in the
+    /// plan right before each node is computed and right after it, before
+    /// the intermediates it consumed are released.
+    fn run_observing<F>(&self, state: &mut ModelState, mut observe: F) -> Result<()>
+    where
+        F: FnMut(usize, &ModelState),
+    {
+        // Position in the plan of the last consumer of each node.
+        let mut last_use = HashMap::new();
+        if self.free_intermediates {
+            for (step, &n) in self.order.iter().enumerate() {
+                for i in &state.model.nodes[n].inputs {
+                    last_use.insert(i.0, step);
+                }
+            }
+        }
+
+        for (step, &n) in self.order.iter().enumerate() {
+            // The intermediates consumed for the last time are moved into
+            // the inputs of the node, which may then reuse their buffers.
+            let released: Vec<usize> = state.model.nodes[n]
+                .inputs
+                .iter()
+                .map(|i| i.0)
+                .filter(|i| {
+                    last_use.get(i) == Some(&step)
+                        && !self.pinned.contains(i)
+                        && !self.targets.contains(i)
+                })
+                .collect();
+
+            observe(step, state);
             if state.outputs[n].is_none() {
-                state.compute_one(n)?;
+                state.compute_one_releasing(n, &released)?;
+            }
+            observe(step, state);
+
+            for i in released {
+                state.outputs[i] = None;
             }
         }
         Ok(())
     }
 }
 
+/// The outputs held in memory after computing a node, see
+/// `Model::liveness_report`.
+struct LiveStep {
+    node: usize,
+    live: Vec<usize>,
+    bytes: usize,
+}
+
 /// Model is Tfdeploy workhouse. It wraps a protobuf tensorflow model,
 /// and runs the inference interpreter.
 ///
@@ -189,15 +347,39 @@ impl Plan {
 pub struct Model {
     pub nodes: Vec<Node>,
     pub nodes_by_name: HashMap<String, usize>,
+    /// User-provided facts about the outputs of some nodes, by node id and
+    /// output port, which seed the analyser.
+    pub annotations: HashMap<(usize, usize), TensorFact>,
 }
 
 impl Model {
     pub fn new(graph: tfpb::graph::GraphDef) -> Result<Model> {
+        Self::new_lenient(graph, Some(&[]))
+    }
+
+    /// Builds a model from a graph which contains operations that can not be
+    /// built, e.g. because they sit on branches which will be pruned away.
+    ///
+    /// The nodes whose operation is in `allowed` (or every node, if `allowed`
+    /// is `None`) get an `UnimplementedOp` instead of failing the loading.
+    /// They only fail if they are evaluated. This only applies to operations
+    /// which are not in the registry: a registered operation which fails to
+    /// build, e.g. because of a bad attribute, still fails the loading.
+    pub fn new_lenient(graph: tfpb::graph::GraphDef, allowed: Option<&[&str]>) -> Result<Model> {
         let mut nodes = vec![];
         let mut nodes_by_name: HashMap<String, usize> = HashMap::new();
         let op_builder = ops::OpBuilder::new();
         for pbnode in graph.get_node().iter() {
             let name = pbnode.get_name().to_string();
+            // The inputs are resolved by name, so they would be ambiguous.
+            if let Some(&other) = nodes_by_name.get(&name) {
+                bail!(
+                    "Duplicate node name {} (nodes #{} and #{})",
+                    name,
+                    other,
+                    nodes.len()
+                );
+            }
 
             // From the node_def.proto documentation:
             // Each input is "node:src_output" with "node" being a string name and
@@ -239,9 +421,19 @@ impl Model {
                 name: name.to_string(),
                 op_name: pbnode.get_op().to_string(),
                 inputs: inputs,
-                op: op_builder
-                    .build(&pbnode)
-                    .map_err(|e| format!("While building node {}, {}", name, e.description()))?,
+                op: Arc::new(match op_builder.build(&pbnode) {
+                    Err(_)
+                        if !op_builder.knows(pbnode.get_op())
+                            && allowed.map_or(true, |ops| ops.contains(&pbnode.get_op())) =>
+                    {
+                        Box::new(ops::UnimplementedOp::new(&pbnode))
+                    }
+                    op => op.map_err(|e| match e.kind() {
+                        // Already mentions the node, and tells the user what to do.
+                        &ErrorKind::ControlFlowUnsupported(..) => e,
+                        _ => format!("While building node {}, {}", name, e.description()).into(),
+                    })?,
+                }),
             };
             nodes_by_name.insert(name, nodes.len());
             nodes.push(node)
@@ -249,6 +441,7 @@ impl Model {
         Ok(Model {
             nodes,
             nodes_by_name,
+            annotations: HashMap::new(),
         })
     }
 
@@ -259,6 +452,80 @@ impl Model {
             .ok_or(format!("Node named {} not found", name).into())
     }
 
+    /// Renames a node, e.g. to avoid a collision before splicing graphs.
+    ///
+    /// Nodes refer to their inputs by id, so only the node and the name index
+    /// need to change.
+    pub fn rename_node(&mut self, old: &str, new: &str) -> Result<()> {
+        let id = self.node_id_by_name(old)?;
+        if old == new {
+            return Ok(());
+        }
+        if self.nodes_by_name.contains_key(new) {
+            bail!("Can not rename {} to {}: the name is already taken", old, new);
+        }
+        self.nodes_by_name.remove(old);
+        self.nodes_by_name.insert(new.to_string(), id);
+        self.nodes[id].name = new.to_string();
+        Ok(())
+    }
+
+    /// Appends the nodes of `other` to the model, e.g. to chain a
+    /// preprocessing graph and a network.
+    ///
+    /// Each connection is a pair of an output of this model (as `node` or
+    /// `node:port`) and a placeholder of `other`, whose consumers are rewired
+    /// to that output. The nodes of `other` get their ids offset by the
+    /// number of nodes in this model, and a numeric suffix when their name is
+    /// already taken.
+    pub fn merge(&self, other: &Model, connections: &[(&str, &str)]) -> Result<Model> {
+        let offset = self.nodes.len();
+        let mut rewired: HashMap<usize, (usize, usize)> = HashMap::new();
+        for &(output, placeholder) in connections {
+            let splits: Vec<_> = output.splitn(2, ':').collect();
+            let node = self.node_id_by_name(splits[0])?;
+            let port = if splits.len() > 1 {
+                splits[1].parse::<usize>()?
+            } else {
+                0
+            };
+            let target = other.node_id_by_name(placeholder)?;
+            if other.nodes[target].op_name != "Placeholder" {
+                bail!("Can not connect {} to {}: not a placeholder", output, placeholder);
+            }
+            rewired.insert(target, (node, port));
+        }
+
+        let mut merged = self.clone();
+        for node in &other.nodes {
+            let mut name = node.name.clone();
+            let mut suffix = 1;
+            while merged.nodes_by_name.contains_key(&name) {
+                name = format!("{}_{}", node.name, suffix);
+                suffix += 1;
+            }
+            let inputs = node.inputs
+                .iter()
+                .map(|&(id, port)| match (rewired.get(&id), port) {
+                    (Some(&(node, port)), Some(_)) => (node, Some(port)),
+                    _ => (id + offset, port),
+                })
+                .collect();
+            merged.nodes_by_name.insert(name.clone(), merged.nodes.len());
+            merged.nodes.push(Node {
+                id: node.id + offset,
+                name,
+                op_name: node.op_name.clone(),
+                inputs,
+                op: node.op.clone(),
+            });
+        }
+        for (&(id, port), fact) in &other.annotations {
+            merged.annotations.insert((id + offset, port), fact.clone());
+        }
+        Ok(merged)
+    }
+
     pub fn state(&self) -> ModelState {
         ModelState {
             model: self,
@@ -276,13 +543,41 @@ impl Model {
         Model::new(Self::graphdef_for_reader(r)?)
     }
 
+    /// Load a Tfdeploy model from a reader, tolerating the operations which
+    /// can not be built (see `new_lenient`).
+    pub fn for_reader_lenient<R: ::std::io::Read>(r: R, allowed: Option<&[&str]>) -> Result<Model> {
+        Model::new_lenient(Self::graphdef_for_reader(r)?, allowed)
+    }
+
     /// Load a Tensorflow protobuf graph def from a reader.
-    pub fn graphdef_for_reader<R: ::std::io::Read>(mut r: R) -> Result<::tfpb::graph::GraphDef> {
+    ///
+    /// Gzip-compressed graphs are recognized by their magic bytes, which can
+    /// not start a valid protobuf message, and decompressed when the `gzip`
+    /// feature is enabled.
+    pub fn graphdef_for_reader<R: ::std::io::Read>(r: R) -> Result<::tfpb::graph::GraphDef> {
+        use std::io::BufRead;
+        let mut r = ::std::io::BufReader::new(r);
+        if r.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+            return Self::gunzip_graphdef(r);
+        }
         Ok(::protobuf::parse_from_reader::<::tfpb::graph::GraphDef>(
             &mut r,
         )?)
     }
 
+    #[cfg(feature = "gzip")]
+    fn gunzip_graphdef<R: ::std::io::Read>(r: R) -> Result<::tfpb::graph::GraphDef> {
+        let mut r = ::flate2::read::GzDecoder::new(r);
+        Ok(::protobuf::parse_from_reader::<::tfpb::graph::GraphDef>(
+            &mut r,
+        )?)
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    fn gunzip_graphdef<R: ::std::io::Read>(_r: R) -> Result<::tfpb::graph::GraphDef> {
+        bail!("The graph is gzip-compressed, loading it requires the gzip feature")
+    }
+
     /// Load a Tensorflow protobuf graph def from a path
     pub fn graphdef_for_path<P: AsRef<path::Path>>(p: P) -> Result<::tfpb::graph::GraphDef> {
         Self::graphdef_for_reader(fs::File::open(p)?)
@@ -318,6 +613,332 @@ impl Model {
         &*self.nodes
     }
 
+    /// Returns a copy of the model for another inference worker.
+    ///
+    /// The operations are held in `Arc`s, so the copy shares them, and their
+    /// weights and caches, with this model instead of duplicating them. Each
+    /// copy still gets its own `ModelState` when run.
+    pub fn share(&self) -> Model {
+        self.clone()
+    }
+
+    /// Runs one inference over the whole model, discarding the results.
+    ///
+    /// Operators may defer expensive preparation work to their first
+    /// evaluation, so calling this with representative inputs before serving
+    /// keeps that latency away from the first real request.
+    pub fn warmup(&self, inputs: Vec<(&str, Tensor)>) -> Result<()> {
+        let mut consumed = HashSet::new();
+        for node in &self.nodes {
+            consumed.extend(node.inputs.iter().map(|i| i.0));
+        }
+        let targets: Vec<usize> = (0..self.nodes.len())
+            .filter(|n| !consumed.contains(n))
+            .collect();
+        let mut state = self.state();
+        state.set_values(inputs)?;
+        Plan::for_model(self, &targets)?.run(&mut state)
+    }
+
+    /// Replaces the value of a `Const` node, e.g. to edit a weight without
+    /// rebuilding the model. The new value must have the same datatype.
+    pub fn replace_const(&mut self, id: usize, value: Tensor) -> Result<()> {
+        let node = self.nodes
+            .get_mut(id)
+            .ok_or(format!("There is no node with index {:?}.", id))?;
+        let current = match node.op.const_value() {
+            Some(ref current) if node.op_name == "Const" => current.datatype(),
+            _ => bail!("Node {} is not a Const node ({}).", node.name, node.op_name),
+        };
+        if value.datatype() != current {
+            bail!(
+                "Can not replace the {:?} value of {} with a {:?} tensor.",
+                current,
+                node.name,
+                value.datatype()
+            );
+        }
+        node.op = Arc::new(Box::new(ops::konst::Const::for_shared(Arc::new(value))));
+        Ok(())
+    }
+
+    /// Makes the operators which support it promote their narrower inputs to
+    /// their datatype instead of rejecting them, e.g. for graphs where an
+    /// `i32` reaches an `f32` addition because a cast was stripped.
+    ///
+    /// This follows the numpy promotion rules: an input which can't be
+    /// converted without loss still gets rejected.
+    pub fn coerce_inputs(&mut self) {
+        for node in &mut self.nodes {
+            if let Some(op) = node.op.coercing() {
+                node.op = Arc::new(op);
+            }
+        }
+    }
+
+    /// Sets the output range of a `QuantizedAdd` node, whose sums then
+    /// saturate at its bounds instead of spanning the sum of the input ranges.
+    pub fn set_output_range(&mut self, id: usize, min: f32, max: f32) -> Result<()> {
+        let node = self.nodes
+            .get_mut(id)
+            .ok_or(format!("There is no node with index {:?}.", id))?;
+        if node.op_name != "QuantizedAdd" {
+            bail!("Node {} is not a QuantizedAdd node ({}).", node.name, node.op_name);
+        }
+        if min.is_nan() || max.is_nan() || min >= max {
+            bail!("Invalid output range [{}, {}] for node {}.", min, max, node.name);
+        }
+        let op = ops::quant::add::QuantizedAdd::new(Some((min, max)));
+        node.op = Arc::new(Box::new(op));
+        Ok(())
+    }
+
+    /// Records a known fact about an output of a node, e.g. the shape of the
+    /// output of an operation which the analyser doesn't know about.
+    ///
+    /// Every analyser built from the model starts from the annotations, and
+    /// propagates them to the consumers of the node.
+    pub fn annotate(&mut self, node: usize, output: usize, fact: TensorFact) -> Result<()> {
+        if let Some(arity) = self.get_node_by_id(node)?.op.output_arity() {
+            if output >= arity {
+                bail!(
+                    "Node {} only has {} output(s), can not annotate output {}.",
+                    self.nodes[node].name,
+                    arity,
+                    output
+                );
+            }
+        }
+        let fact = match self.annotations.get(&(node, output)) {
+            Some(previous) => analyser::prelude::unify(previous, &fact)?,
+            None => fact,
+        };
+        self.annotations.insert((node, output), fact);
+        Ok(())
+    }
+
+    /// Checks that every input of every node refers to an existing output of
+    /// its producer.
+    ///
+    /// `Model::new` reads an input without a `:k` suffix as output 0, and
+    /// doesn't know how many outputs the producers have, so this should be
+    /// called on models coming from untrusted graphs.
+    pub fn validate_wiring(&self) -> Result<()> {
+        for node in &self.nodes {
+            for &(producer, port) in &node.inputs {
+                let producer = self.get_node_by_id(producer)?;
+                if let (Some(port), Some(arity)) = (port, producer.op.output_arity()) {
+                    if port >= arity {
+                        bail!(
+                            "Node {} uses output {} of {} ({}), which only has {} output(s).",
+                            node.name,
+                            port,
+                            producer.name,
+                            producer.op_name,
+                            arity
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a human-readable table of the nodes of the model, with their
+    /// operator, the shape of their first output, and an estimation of the
+    /// floating point operations they need.
+    ///
+    /// The shapes and estimations come from the analyser, starting from the
+    /// given facts about the inputs, so they are only as precise as these
+    /// facts allow.
+    pub fn summary(&self, inputs: &[(usize, TensorFact)]) -> Result<String> {
+        let output = analyser::detect_output(self)?.ok_or("Unable to auto-detect output node.")?;
+        let mut analyser = analyser::Analyser::new(self.clone(), output)?;
+        for &(input, ref fact) in inputs {
+            analyser.hint(input, fact)?;
+        }
+        analyser.run()?;
+
+        let mut rows = vec![];
+        let mut total = Some(0);
+        for node in &analyser.nodes {
+            let facts = |edges: &[usize]| -> Vec<TensorFact> {
+                edges.iter().map(|&e| analyser.edges[e].fact.clone()).collect()
+            };
+            let inputs = facts(&analyser.prev_edges[node.id]);
+            let outputs = facts(&analyser.next_edges[node.id]);
+            let shape = analyser.next_edges[node.id]
+                .iter()
+                .map(|&e| &analyser.edges[e])
+                .find(|e| e.from_out == 0)
+                .map(|e| format!("{:?}", e.fact.shape))
+                .unwrap_or("?".to_string());
+            let flops = node.op.flops(&inputs, &outputs);
+            total = match (total, flops) {
+                (Some(total), Some(flops)) => Some(total + flops),
+                _ => None,
+            };
+            rows.push((
+                node.name.clone(),
+                node.op_name.clone(),
+                shape,
+                flops.map(|f| f.to_string()).unwrap_or("?".to_string()),
+            ));
+        }
+
+        let name_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0).max(4);
+        let op_width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0).max(2);
+        let shape_width = rows.iter().map(|r| r.2.len()).max().unwrap_or(0).max(12);
+        let mut summary = format!(
+            "{:nw$}  {:ow$}  {:sw$}  {}\n",
+            "Node",
+            "Op",
+            "Output shape",
+            "FLOPs",
+            nw = name_width,
+            ow = op_width,
+            sw = shape_width
+        );
+        for (name, op, shape, flops) in rows {
+            summary.push_str(&format!(
+                "{:nw$}  {:ow$}  {:sw$}  {}\n",
+                name,
+                op,
+                shape,
+                flops,
+                nw = name_width,
+                ow = op_width,
+                sw = shape_width
+            ));
+        }
+        match total {
+            Some(total) => summary.push_str(&format!("Total FLOPs: {}\n", total)),
+            None => summary.push_str("Total FLOPs: ? (some nodes could not be estimated)\n"),
+        }
+        Ok(summary)
+    }
+
+    /// Estimates the memory needed to run the model, in bytes.
+    ///
+    /// The shapes of the outputs of every node come from the analyser,
+    /// starting from the given facts about the inputs, and must all be known.
+    /// The estimate is the total size of the constants plus the largest size
+    /// of the outputs held at once while following the plan, assuming each
+    /// output is freed after its last consumer ran.
+    pub fn estimate_peak_memory(&self, inputs: &[(usize, TensorFact)]) -> Result<usize> {
+        let (weights, steps) = self.liveness(inputs)?;
+        Ok(weights + steps.iter().map(|s| s.bytes).max().unwrap_or(0))
+    }
+
+    /// Returns a human-readable table of the outputs held in memory at each
+    /// step of the plan, as assumed by `estimate_peak_memory`, with the step
+    /// where the peak is reached.
+    pub fn liveness_report(&self, inputs: &[(usize, TensorFact)]) -> Result<String> {
+        let (weights, steps) = self.liveness(inputs)?;
+        let peak = (0..steps.len()).max_by_key(|&i| (steps[i].bytes, -(i as isize)));
+
+        let names = |nodes: &[usize]| -> String {
+            let names: Vec<&str> = nodes.iter().map(|&n| &*self.nodes[n].name).collect();
+            names.join(", ")
+        };
+        let name_width = steps
+            .iter()
+            .map(|s| self.nodes[s.node].name.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        let mut report = format!(
+            "{:>4}  {:nw$}  {:>10}  {}\n",
+            "Step",
+            "Node",
+            "Live bytes",
+            "Live outputs",
+            nw = name_width
+        );
+        for (ix, step) in steps.iter().enumerate() {
+            let line = format!(
+                "{:>4}  {:nw$}  {:>10}  {}{}",
+                ix,
+                self.nodes[step.node].name,
+                step.bytes,
+                names(&step.live),
+                if Some(ix) == peak { "  <- peak" } else { "" },
+                nw = name_width
+            );
+            report.push_str(line.trim_right());
+            report.push('\n');
+        }
+        report.push_str(&format!("Constants: {} bytes\n", weights));
+        let peak = peak.map(|p| steps[p].bytes).unwrap_or(0);
+        report.push_str(&format!("Peak: {} bytes\n", weights + peak));
+        Ok(report)
+    }
+
+    /// Follows the plan computing the output of the model, and returns the
+    /// size of the constants together with the outputs held in memory at
+    /// each step.
+    fn liveness(&self, inputs: &[(usize, TensorFact)]) -> Result<(usize, Vec<LiveStep>)> {
+        use analyser::prelude::Fact;
+        let output = analyser::detect_output(self)?.ok_or("Unable to auto-detect output node.")?;
+        let mut analyser = analyser::Analyser::new(self.clone(), output)?;
+        for &(input, ref fact) in inputs {
+            analyser.hint(input, fact)?;
+        }
+        analyser.run()?;
+
+        let plan = Plan::for_model(self, &[output])?;
+        let mut weights = 0;
+        let mut sizes = HashMap::new();
+        for &n in &plan.order {
+            if let Some(value) = self.nodes[n].op.const_value() {
+                let size_of = value.datatype().size_of().unwrap_or(0);
+                weights += value.shape().iter().product::<usize>() * size_of;
+                continue;
+            }
+            let mut ports = HashMap::new();
+            for &e in &analyser.next_edges[n] {
+                let edge = &analyser.edges[e];
+                let fact = &edge.fact;
+                let size = match (fact.datatype.concretize(), fact.shape.concretize()) {
+                    (Some(dt), Some(shape)) => {
+                        dt.size_of().map(|s| s * shape.iter().product::<usize>())
+                    }
+                    _ => None,
+                };
+                let size = size.ok_or_else(|| {
+                    format!(
+                        "Unable to estimate the size of output {} of node {}: {:?}",
+                        edge.from_out, self.nodes[n].name, edge.fact
+                    )
+                })?;
+                ports.insert(edge.from_out, size);
+            }
+            sizes.insert(n, ports.values().sum::<usize>());
+        }
+
+        // Position in the plan of the last consumer of each node.
+        let mut last_use = HashMap::new();
+        for (step, &n) in plan.order.iter().enumerate() {
+            for i in &self.nodes[n].inputs {
+                last_use.insert(i.0, step);
+            }
+        }
+        let mut live = vec![];
+        let mut steps = vec![];
+        for (step, &n) in plan.order.iter().enumerate() {
+            if sizes.contains_key(&n) {
+                live.push(n);
+            }
+            steps.push(LiveStep {
+                node: n,
+                live: live.clone(),
+                bytes: live.iter().map(|l| sizes[l]).sum(),
+            });
+            live.retain(|&l| l == output || last_use.get(&l) != Some(&step));
+        }
+        Ok((weights, steps))
+    }
+
     pub fn run_with_names(&self, inputs: Vec<(&str, Tensor)>, output: &str) -> Result<Vec<Tensor>> {
         let inputs = inputs
             .into_iter()
@@ -360,17 +981,67 @@ impl<'a> ModelState<'a> {
     }
 
     pub fn compute_one(&mut self, node: usize) -> Result<()> {
+        self.compute_one_releasing(node, &[])
+    }
+
+    /// Computes a node, moving the outputs of the `released` nodes into its
+    /// inputs instead of copying them, so that the operation can reuse their
+    /// buffers. The outputs of the released nodes are gone afterwards.
+    pub fn compute_one_releasing(&mut self, node: usize, released: &[usize]) -> Result<()> {
         let node: &Node = &self.model.nodes[node];
+        let mut moved: HashMap<usize, Vec<Option<TensorView>>> = HashMap::new();
         let mut inputs: Vec<TensorView> = vec![];
-        for i in &node.inputs {
+        for (ix, i) in node.inputs.iter().enumerate() {
             let prec_node = &self.model.nodes[i.0];
-            let prec = self.outputs[i.0].as_ref().ok_or(format!(
-                "Computing {}, precursor {} not done:",
-                node.name, prec_node.name
-            ))?;
-            inputs.push(prec[i.1.ok_or("no output found")?].clone().into())
+            let not_done = || format!("Computing {}, precursor {} not done:", node.name, prec_node.name);
+            if released.contains(&i.0) && !moved.contains_key(&i.0) {
+                let prec = self.outputs[i.0].take().ok_or_else(&not_done)?;
+                moved.insert(i.0, prec.into_iter().map(Some).collect());
+            }
+            // Control inputs only order the computations.
+            let port = match i.1 {
+                Some(port) => port,
+                None if moved.contains_key(&i.0) => continue,
+                None => {
+                    self.outputs[i.0].as_ref().ok_or_else(&not_done)?;
+                    continue;
+                }
+            };
+            let input = match moved.get_mut(&i.0) {
+                // An output consumed again later by this node is copied.
+                Some(prec) if node.inputs[ix + 1..].contains(i) => prec[port].clone(),
+                Some(prec) => prec[port].take(),
+                None => Some(self.outputs[i.0].as_ref().ok_or_else(&not_done)?[port].clone()),
+            };
+            inputs.push(input.ok_or_else(&not_done)?);
+        }
+        // Only collect the shapes when they are going to be logged.
+        let input_shapes = if log_enabled!(log::Level::Trace) {
+            Some(
+                inputs
+                    .iter()
+                    .map(|i| i.as_tensor().shape().to_vec())
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            None
+        };
+        // Owned inputs are not used anywhere else, so the op may overwrite them.
+        let in_place = if inputs.iter().all(|i| match i {
+            &TensorView::Owned(_) => true,
+            _ => false,
+        }) {
+            node.op.eval_in_place(&mut inputs)
+        } else {
+            None
+        };
+        let outputs = match in_place {
+            Some(outputs) => outputs?,
+            None => node.op.eval(inputs)?,
+        };
+        if let Some(input_shapes) = input_shapes {
+            trace!("{}", computed_shapes(node, &input_shapes, &outputs));
         }
-        let outputs = node.op.eval(inputs)?;
         self.outputs[node.id] = Some(outputs);
         Ok(())
     }
@@ -401,7 +1072,883 @@ impl<'a> ModelState<'a> {
         Ok(self.take(output)?)
     }
 
+    /// Runs the network like `run`, also writing the outputs of each node
+    /// to `dir/<node name>.json` as soon as they are computed.
+    ///
+    /// Each file holds the list of the outputs of the node, serialized like
+    /// `Tensor`. Node names with slashes end up in subdirectories.
+    #[cfg(feature = "serialize")]
+    pub fn run_dumping<P: AsRef<path::Path>>(
+        &mut self,
+        inputs: Vec<(usize, Tensor)>,
+        output: usize,
+        dir: P,
+    ) -> Result<Vec<Tensor>> {
+        self.reset()?;
+        for input in inputs {
+            self.set_value(input.0, input.1)?;
+        }
+        for n in Plan::for_model(self.model, &[output])?.order {
+            if self.outputs[n].is_none() {
+                self.compute_one(n)?;
+            }
+            let path = dir.as_ref().join(format!("{}.json", self.model.nodes[n].name));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let tensors: Vec<&Tensor> = self.outputs[n]
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|t| t.as_tensor())
+                .collect();
+            serde_json::to_writer(fs::File::create(path)?, &tensors)?;
+        }
+        Ok(self.take(output)?)
+    }
+
     pub fn model(&self) -> &Model {
         self.model
     }
 }
+
+/// Describes the shapes of the inputs and outputs of a computed node, for
+/// the trace logs.
+fn computed_shapes(node: &Node, input_shapes: &[Vec<usize>], outputs: &[TensorView]) -> String {
+    format!(
+        "Computed {} ({}): inputs {:?}, outputs {:?}",
+        node.name,
+        node.op_name,
+        input_shapes,
+        outputs
+            .iter()
+            .map(|o| o.as_tensor().shape())
+            .collect::<Vec<_>>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protobuf::Message;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tfpb::types::DataType::DT_FLOAT;
+
+    fn chain() -> Model {
+        let konst = tfpb::node()
+            .op("Const")
+            .name("three")
+            .attr("dtype", DT_FLOAT)
+            .attr(
+                "value",
+                Tensor::f32s(&[1], &[3.0]).unwrap().to_pb().unwrap(),
+            );
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(konst)
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("mid")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("three"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("mid")
+                    .input("three"),
+            );
+        Model::new(graph).unwrap()
+    }
+
+    /// Counts how many times it had to prepare its output.
+    #[derive(Debug, Clone)]
+    struct LazyOp {
+        inits: Arc<AtomicUsize>,
+        cache: Arc<Mutex<Option<Tensor>>>,
+    }
+
+    impl Op for LazyOp {
+        fn get_attributes(&self) -> HashMap<&'static str, ops::Attr> {
+            HashMap::new()
+        }
+
+        fn eval(&self, _inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.is_none() {
+                self.inits.fetch_add(1, Ordering::SeqCst);
+                *cache = Some(Tensor::f32s(&[1], &[3.0])?);
+            }
+            Ok(vec![cache.clone().unwrap().into()])
+        }
+    }
+
+    impl ops::InferenceRulesOp for LazyOp {
+        fn rules<'r, 'p: 'r, 's: 'r>(
+            &'s self,
+            solver: &mut analyser::interface::Solver<'r>,
+            inputs: &'p analyser::interface::TensorsProxy,
+            outputs: &'p analyser::interface::TensorsProxy,
+        ) {
+            solver.equals(&inputs.len, 0).equals(&outputs.len, 1);
+        }
+    }
+
+    #[test]
+    fn warmup_triggers_lazy_initialization() {
+        let mut model = chain();
+        let inits = Arc::new(AtomicUsize::new(0));
+        let three = model.node_id_by_name("three").unwrap();
+        model.nodes[three].op = Arc::new(Box::new(LazyOp {
+            inits: inits.clone(),
+            cache: Arc::new(Mutex::new(None)),
+        }));
+
+        model
+            .warmup(vec![("input", Tensor::f32s(&[1], &[0.0]).unwrap())])
+            .unwrap();
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+
+        let result = model
+            .run_with_names(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())], "output")
+            .unwrap();
+        assert_eq!(result, vec![Tensor::f32s(&[1], &[7.0]).unwrap()]);
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn control_flow_is_unsupported() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Switch")
+                    .name("switch")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("input"),
+            );
+        match Model::new(graph) {
+            Err(Error(ErrorKind::ControlFlowUnsupported(op, node), _)) => {
+                assert_eq!(op, "Switch");
+                assert_eq!(node, "switch");
+            }
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn lenient_loading() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Switch")
+                    .name("dead")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("input"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("input"),
+            );
+        assert!(Model::new_lenient(graph.clone(), Some(&["Merge"])).is_err());
+
+        let mut bytes = vec![];
+        graph.write_to_vec(&mut bytes).unwrap();
+        for allowed in &[Some(&["Switch"][..]), None] {
+            let model = Model::for_reader_lenient(&*bytes, *allowed).unwrap();
+            let input = Tensor::f32s(&[1], &[1.0]).unwrap();
+            assert_eq!(
+                model.run_with_names(vec![("input", input.clone())], "output").unwrap(),
+                vec![Tensor::f32s(&[1], &[2.0]).unwrap()]
+            );
+            assert!(model.run_with_names(vec![("input", input)], "dead").is_err());
+        }
+
+        // A registered operation which can not be built is still an error.
+        let graph = graph.node(tfpb::node().op("Add").name("untyped").input("input").input("input"));
+        assert!(Model::new_lenient(graph.clone(), Some(&["Add"])).is_err());
+        assert!(Model::new_lenient(graph, None).is_err());
+    }
+
+    #[test]
+    fn control_and_data_inputs() {
+        let konst = tfpb::node()
+            .op("Const")
+            .name("three")
+            .attr("dtype", DT_FLOAT)
+            .attr(
+                "value",
+                Tensor::f32s(&[1], &[3.0]).unwrap().to_pb().unwrap(),
+            );
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(konst)
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("three")
+                    .input("^three"),
+            );
+        let model = Model::new(graph).unwrap();
+        let three = model.node_id_by_name("three").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        assert_eq!(model.nodes[output].inputs[1..], [(three, Some(0)), (three, None)]);
+
+        let input = model.node_id_by_name("input").unwrap();
+        let mut fact = TensorFact::new();
+        fact.datatype = typefact!(DataType::F32);
+        let plan = Plan::for_model(&model, &[output]).unwrap();
+        plan.validate(&model, &[(input, fact.clone())]).unwrap();
+        // The analyser doesn't count the control input as a data input.
+        fact.shape = shapefact![1];
+        let summary = model.summary(&[(input, fact)]).unwrap();
+        assert!(summary.contains("output"));
+        assert_eq!(
+            model
+                .run(vec![(input, Tensor::f32s(&[1], &[1.0]).unwrap())], output)
+                .unwrap(),
+            vec![Tensor::f32s(&[1], &[4.0]).unwrap()]
+        );
+    }
+
+    #[test]
+    fn plan_frees_intermediates() {
+        let model = chain();
+        let mut state = model.state();
+        state
+            .set_values(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())])
+            .unwrap();
+        let mut plan =
+            Plan::for_model(&model, &[model.node_id_by_name("output").unwrap()]).unwrap();
+        plan.set_free_intermediates(true);
+        plan.run(&mut state).unwrap();
+        assert!(state.outputs[model.node_id_by_name("mid").unwrap()].is_none());
+        assert_eq!(
+            state.take_by_name("output").unwrap(),
+            vec![Tensor::f32s(&[1], &[7.0]).unwrap()]
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn run_parallel_thread_counts() {
+        use ops::PARALLEL_MAP_THRESHOLD;
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Tanh")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input"),
+            );
+        let model = Model::new(graph).unwrap();
+        let len = 4 * PARALLEL_MAP_THRESHOLD + 3;
+        let input: Vec<f32> = (0..len).map(|i| (i as f32 - 1000.0) / 777.0).collect();
+        let input = Tensor::f32s(&[len], &input).unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let plan = Plan::for_model(&model, &[output]).unwrap();
+        let run = |pool: &rayon_core::ThreadPool| {
+            let mut state = model.state();
+            state.set_values(vec![("input", input.clone())]).unwrap();
+            plan.run_parallel(&mut state, pool).unwrap();
+            state.take(output).unwrap()
+        };
+        let serial = run(&Plan::thread_pool(1).unwrap());
+        let pool = Plan::thread_pool(4).unwrap();
+        assert_eq!(pool.current_num_threads(), 4);
+        assert_eq!(serial, run(&pool));
+        assert_eq!(serial, run(&pool));
+        assert_eq!(serial, model.run(vec![(0, input.clone())], output).unwrap());
+    }
+
+    #[test]
+    fn plan_overlapping_targets() {
+        let abs = |name: &str, input: &str| {
+            tfpb::node()
+                .op("Abs")
+                .name(name)
+                .attr("T", DT_FLOAT)
+                .input(input)
+        };
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(abs("a", "input"))
+            .node(abs("b", "a"))
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("c")
+                    .attr("T", DT_FLOAT)
+                    .input("b")
+                    .input("a"),
+            )
+            .node(abs("d", "b"));
+        let mut model = Model::new(graph).unwrap();
+        let ids = |names: &[&str]| -> Vec<usize> {
+            names.iter().map(|n| model.node_id_by_name(n).unwrap()).collect()
+        };
+        for targets in &[ids(&["c", "d"]), ids(&["d", "b", "c"]), ids(&["c", "c"])] {
+            let order = Plan::for_model(&model, targets).unwrap().order;
+            let mut sorted = order.clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(sorted.len(), order.len(), "{:?} has duplicates", order);
+            for (pos, &n) in order.iter().enumerate() {
+                for i in &model.nodes[n].inputs {
+                    assert!(order[..pos].contains(&i.0), "{:?} is not ordered", order);
+                }
+            }
+            assert!(targets.iter().all(|t| order.contains(t)));
+        }
+
+        // A cycle can not be planned, but must not hang.
+        let a = model.node_id_by_name("a").unwrap();
+        let b = model.node_id_by_name("b").unwrap();
+        model.nodes[a].inputs = vec![(b, Some(0))];
+        assert!(Plan::for_model(&model, &[b]).is_err());
+    }
+
+    #[test]
+    fn share_ops() {
+        let model = chain();
+        let shared = model.share();
+        for node in &model.nodes {
+            assert!(Arc::ptr_eq(&node.op, &shared.nodes[node.id].op));
+        }
+        let three = model.node_id_by_name("three").unwrap();
+        let value = |model: &Model| model.nodes[three].op.eval(vec![]).unwrap().remove(0);
+        assert_eq!(
+            value(&model).as_f32s().unwrap().as_ptr(),
+            value(&shared).as_f32s().unwrap().as_ptr()
+        );
+        assert_eq!(
+            shared
+                .run_with_names(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())], "output")
+                .unwrap(),
+            vec![Tensor::f32s(&[1], &[7.0]).unwrap()]
+        );
+    }
+
+    #[test]
+    fn gzipped_graph() {
+        let loaded = Model::for_path("tests/models/plus3.pb.gz");
+        if cfg!(feature = "gzip") {
+            let model = loaded.unwrap();
+            let plain = Model::for_path("tests/models/plus3.pb").unwrap();
+            let input = || vec![("input", Tensor::f32s(&[2], &[1.0, 2.0]).unwrap())];
+            assert_eq!(
+                model.run_with_names(input(), "output").unwrap(),
+                plain.run_with_names(input(), "output").unwrap()
+            );
+        } else {
+            assert!(loaded.is_err());
+        }
+    }
+
+    #[test]
+    fn rename_node() {
+        let mut model = chain();
+        let mid = model.node_id_by_name("mid").unwrap();
+        model.rename_node("mid", "middle").unwrap();
+        assert_eq!(model.node_id_by_name("middle").unwrap(), mid);
+        assert_eq!(model.nodes[mid].name, "middle");
+        assert!(model.node_id_by_name("mid").is_err());
+        assert_eq!(
+            model
+                .run_with_names(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())], "output")
+                .unwrap(),
+            vec![Tensor::f32s(&[1], &[7.0]).unwrap()]
+        );
+
+        assert!(model.rename_node("middle", "three").is_err());
+        assert!(model.rename_node("mid", "other").is_err());
+        assert_eq!(model.node_id_by_name("middle").unwrap(), mid);
+    }
+
+    #[test]
+    fn merge() {
+        let plus3 = Model::for_path("tests/models/plus3.pb").unwrap();
+        let plus6 = plus3.merge(&plus3, &[("output", "input")]).unwrap();
+        assert_eq!(plus6.nodes.len(), 2 * plus3.nodes.len());
+        assert!(plus6.node_id_by_name("input_1").is_ok());
+        assert_eq!(
+            plus6
+                .run_with_names(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())], "output_1")
+                .unwrap(),
+            vec![Tensor::f32s(&[1], &[7.0]).unwrap()]
+        );
+
+        assert!(plus3.merge(&plus3, &[("output", "output")]).is_err());
+        assert!(plus3.merge(&plus3, &[("missing", "input")]).is_err());
+    }
+
+    #[test]
+    fn coerce_inputs() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("ints")
+                    .attr("dtype", tfpb::types::DataType::DT_INT32),
+            )
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("floats")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("sum")
+                    .attr("T", DT_FLOAT)
+                    .input("ints")
+                    .input("floats"),
+            );
+        let mut model = Model::new(graph).unwrap();
+        let inputs = || {
+            vec![
+                ("ints", Tensor::i32s(&[2], &[1, 2]).unwrap()),
+                ("floats", Tensor::f32s(&[2], &[0.5, 0.25]).unwrap()),
+            ]
+        };
+        assert!(model.run_with_names(inputs(), "sum").is_err());
+        model.coerce_inputs();
+        assert_eq!(
+            model.run_with_names(inputs(), "sum").unwrap(),
+            vec![Tensor::f32s(&[2], &[1.5, 2.25]).unwrap()]
+        );
+    }
+
+    #[test]
+    fn set_output_range() {
+        use ndarray::{arr0, arr1};
+        use tfpb::types::DataType::DT_UINT8;
+        let names = ["x", "y", "min_x", "max_x", "min_y", "max_y"];
+        let mut graph = tfpb::graph();
+        for (ix, name) in names.iter().enumerate() {
+            let dtype = if ix < 2 { DT_UINT8 } else { DT_FLOAT };
+            graph = graph.node(tfpb::node().op("Placeholder").name(*name).attr("dtype", dtype));
+        }
+        let mut add = tfpb::node().op("QuantizedAdd").name("sum");
+        for name in &names {
+            add = add.input(*name);
+        }
+        let mut model = Model::new(graph.node(add)).unwrap();
+        let inputs = || {
+            let mut inputs = vec![
+                ("x", Tensor::from(arr1(&[200u8, 0]))),
+                ("y", Tensor::from(arr1(&[200u8, 0]))),
+            ];
+            for (name, v) in names[2..].iter().zip(&[0.0f32, 1.0, 0.0, 1.0]) {
+                inputs.push((*name, Tensor::F32(arr0(*v).into_dyn())));
+            }
+            inputs
+        };
+        let sum = model.node_id_by_name("sum").unwrap();
+        assert!(model.set_output_range(sum, 1.0, 0.0).is_err());
+        assert!(model.set_output_range(0, 0.0, 1.0).is_err());
+        model.set_output_range(sum, 0.0, 1.0).unwrap();
+        let outputs = model.run_with_names(inputs(), "sum").unwrap();
+        assert_eq!(outputs[0], Tensor::from(arr1(&[255u8, 0])));
+        assert_eq!(outputs[2], Tensor::F32(arr0(1.0f32).into_dyn()));
+    }
+
+    #[test]
+    fn replace_const() {
+        let mut model = chain();
+        let three = model.node_id_by_name("three").unwrap();
+        model
+            .replace_const(three, Tensor::f32s(&[1], &[5.0]).unwrap())
+            .unwrap();
+        let input = Tensor::f32s(&[1], &[1.0]).unwrap();
+        assert_eq!(
+            model.run_with_names(vec![("input", input)], "output").unwrap(),
+            vec![Tensor::f32s(&[1], &[11.0]).unwrap()]
+        );
+
+        assert!(
+            model
+                .replace_const(three, Tensor::i32s(&[1], &[5]).unwrap())
+                .is_err()
+        );
+        let mid = model.node_id_by_name("mid").unwrap();
+        assert!(
+            model
+                .replace_const(mid, Tensor::f32s(&[1], &[5.0]).unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn plan_validate() {
+        let model = chain();
+        let input = model.node_id_by_name("input").unwrap();
+        let plan = Plan::for_model(&model, &[model.node_id_by_name("output").unwrap()]).unwrap();
+        let mut fact = TensorFact::new();
+        fact.datatype = typefact!(DataType::F32);
+        plan.validate(&model, &[(input, fact)]).unwrap();
+
+        let mut fact = TensorFact::new();
+        fact.datatype = typefact!(DataType::I32);
+        let error = plan.validate(&model, &[(input, fact)]).unwrap_err();
+        assert!(format!("{}", error).contains("input (Placeholder)"));
+    }
+
+    thread_local! {
+        // The log lines written by the current thread, so that tests running
+        // in parallel don't see each other's.
+        static CAPTURED: ::std::cell::RefCell<Vec<u8>> = ::std::cell::RefCell::new(vec![]);
+    }
+
+    struct Capture;
+
+    impl ::std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            CAPTURED.with(|c| c.borrow_mut().extend_from_slice(buf));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    static CAPTURE_LOGGER: ::std::sync::Once = ::std::sync::ONCE_INIT;
+
+    #[test]
+    fn compute_one_traces_shapes() {
+        CAPTURE_LOGGER.call_once(|| {
+            simplelog::WriteLogger::init(
+                log::LevelFilter::Trace,
+                simplelog::Config::default(),
+                Capture,
+            ).unwrap()
+        });
+        let model = chain();
+        let mut state = model.state();
+        state
+            .set_values(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())])
+            .unwrap();
+        state.compute_one(model.node_id_by_name("three").unwrap()).unwrap();
+        CAPTURED.with(|c| c.borrow_mut().clear());
+        state.compute_one(model.node_id_by_name("mid").unwrap()).unwrap();
+        let captured = CAPTURED.with(|c| String::from_utf8(c.borrow().clone()).unwrap());
+        assert!(
+            captured.contains("Computed mid (Add): inputs [[1], [1]], outputs [[1]]"),
+            "Unexpected trace: {:?}",
+            captured
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let model = Model::for_path("tests/models/plus3.pb").unwrap();
+        let mut fact = TensorFact::new();
+        fact.datatype = typefact!(DataType::F32);
+        fact.shape = shapefact![1];
+        let summary = model
+            .summary(&[(model.node_id_by_name("input").unwrap(), fact)])
+            .unwrap();
+        let lines: Vec<Vec<&str>> = summary
+            .lines()
+            .map(|l| l.split_whitespace().collect())
+            .collect();
+        assert_eq!(lines[1], vec!["input", "Placeholder", "[1]", "0"]);
+        assert_eq!(lines[2], vec!["three", "Const", "[1]", "0"]);
+        assert_eq!(lines[3], vec!["output", "Add", "[1]", "1"]);
+        assert_eq!(lines[4], vec!["Total", "FLOPs:", "1"]);
+    }
+
+    #[test]
+    fn estimate_peak_memory() {
+        let model = chain();
+        let input = model.node_id_by_name("input").unwrap();
+        let mut fact = TensorFact::new();
+        fact.datatype = typefact!(DataType::F32);
+        fact.shape = shapefact![1000];
+        let estimate = model.estimate_peak_memory(&[(input, fact)]).unwrap();
+
+        // Measure the outputs actually held by a plan freeing its
+        // intermediates: the inputs of a node stay in memory until it has
+        // been computed, even when they are moved into it.
+        let mut state = model.state();
+        state
+            .set_value(input, Tensor::f32s(&[1000], &[1.0; 1000]).unwrap())
+            .unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let mut plan = Plan::for_model(&model, &[output]).unwrap();
+        plan.set_free_intermediates(true);
+        let mut held: Vec<HashMap<usize, usize>> = vec![HashMap::new(); plan.order.len()];
+        plan.run_observing(&mut state, |step, state| {
+            for (n, values) in state.outputs.iter().enumerate() {
+                if let Some(ref values) = *values {
+                    let bytes = values.iter().map(|v| v.as_f32s().unwrap().len() * 4).sum();
+                    held[step].insert(n, bytes);
+                }
+            }
+        }).unwrap();
+        let measured = held
+            .iter()
+            .map(|step| step.values().sum::<usize>())
+            .max()
+            .unwrap();
+        assert_eq!(estimate, measured);
+        assert!(state.outputs[input].is_none());
+    }
+
+    #[test]
+    fn liveness_report() {
+        let model = chain();
+        let input = model.node_id_by_name("input").unwrap();
+        let mut fact = TensorFact::new();
+        fact.datatype = typefact!(DataType::F32);
+        fact.shape = shapefact![1000];
+        let inputs = [(input, fact)];
+        let report = model.liveness_report(&inputs).unwrap();
+        let lines: Vec<Vec<&str>> = report
+            .lines()
+            .map(|l| l.split_whitespace().collect())
+            .collect();
+        assert_eq!(lines[0], vec!["Step", "Node", "Live", "bytes", "Live", "outputs"]);
+        assert_eq!(lines[1], vec!["0", "three", "0"]);
+        assert_eq!(lines[2], vec!["1", "input", "4000", "input"]);
+        assert_eq!(lines[3], vec!["2", "mid", "8000", "input,", "mid", "<-", "peak"]);
+        assert_eq!(lines[4], vec!["3", "output", "8000", "mid,", "output"]);
+        let peak = model.estimate_peak_memory(&inputs).unwrap();
+        assert_eq!(lines[6], vec!["Peak:", &*peak.to_string(), "bytes"]);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn run_dumping() {
+        let model = Model::for_path("tests/models/plus3.pb").unwrap();
+        let dir = ::std::env::temp_dir().join(format!("tfdeploy-dump-{}", ::std::process::id()));
+        let input = Tensor::f32s(&[1], &[1.0]).unwrap();
+        let found = model
+            .state()
+            .run_dumping(
+                vec![(model.node_id_by_name("input").unwrap(), input.clone())],
+                model.node_id_by_name("output").unwrap(),
+                &dir,
+            )
+            .unwrap();
+        assert_eq!(found, vec![Tensor::f32s(&[1], &[4.0]).unwrap()]);
+
+        let dumped = |name: &str| fs::read_to_string(dir.join(format!("{}.json", name))).unwrap();
+        let json = |t: Tensor| serde_json::to_string(&[t]).unwrap();
+        assert_eq!(dumped("input"), json(input));
+        assert_eq!(dumped("three"), json(Tensor::f32s(&[1], &[3.0]).unwrap()));
+        assert_eq!(dumped("output"), json(Tensor::f32s(&[1], &[4.0]).unwrap()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_node_names() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            );
+        let error = Model::new(graph).err().unwrap();
+        assert!(format!("{}", error).contains("Duplicate node name input"));
+    }
+
+    #[test]
+    fn annotate_unknown_op() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(tfpb::node().op("Mystery").name("mystery").input("input"))
+            .node(
+                tfpb::node()
+                    .op("Abs")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("mystery"),
+            );
+        let mut model = Model::new(graph).unwrap();
+        let mystery = model.node_id_by_name("mystery").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let output_shape = |model: &Model| {
+            let mut analyser = analyser::Analyser::new(model.clone(), output).unwrap();
+            analyser.run().unwrap();
+            let edge = analyser.next_edges[output][0];
+            analyser.edges[edge].fact.shape.clone()
+        };
+        assert_eq!(output_shape(&model), shapefact![..]);
+
+        let mut fact = TensorFact::new();
+        fact.shape = shapefact![2, 3];
+        model.annotate(mystery, 0, fact).unwrap();
+        assert_eq!(output_shape(&model), shapefact![2, 3]);
+
+        // Nothing is known about the outputs of an unimplemented operation.
+        model.annotate(mystery, 1, TensorFact::new()).unwrap();
+        let input = model.node_id_by_name("input").unwrap();
+        assert!(model.annotate(input, 1, TensorFact::new()).is_err());
+        let mut fact = TensorFact::new();
+        fact.shape = shapefact![2, 4];
+        assert!(model.annotate(mystery, 0, fact).is_err());
+    }
+
+    #[test]
+    fn validate_wiring() {
+        chain().validate_wiring().unwrap();
+
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("input:1"),
+            );
+        let error = Model::new(graph).unwrap().validate_wiring().unwrap_err();
+        let message = format!("{}", error);
+        assert!(message.contains("Node output"));
+        assert!(message.contains("output 1 of input"));
+
+        // The outputs of an unimplemented operation are not known.
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(tfpb::node().op("Mystery").name("mystery").input("input"))
+            .node(
+                tfpb::node()
+                    .op("Abs")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("mystery:1"),
+            );
+        Model::new(graph).unwrap().validate_wiring().unwrap();
+    }
+
+    #[test]
+    fn compute_one_moves_released_inputs() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Abs")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input"),
+            );
+        let model = Model::new(graph).unwrap();
+        let input = model.node_id_by_name("input").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let mut state = model.state();
+
+        let value = Tensor::f32s(&[2], &[-1.0, 2.0]).unwrap();
+        state.set_value(input, value.clone()).unwrap();
+        state.compute_one(output).unwrap();
+        assert!(state.outputs[input].is_some());
+        let copied = state.outputs[output].as_ref().unwrap()[0].as_f32s().unwrap().as_ptr();
+        let kept = state.outputs[input].as_ref().unwrap()[0].as_f32s().unwrap().as_ptr();
+        assert_ne!(copied, kept);
+
+        // A released input is moved, and its buffer is reused.
+        state.compute_one_releasing(output, &[input]).unwrap();
+        assert!(state.outputs[input].is_none());
+        let reused = state.outputs[output].as_ref().unwrap()[0].as_f32s().unwrap().as_ptr();
+        assert_eq!(reused, kept);
+        assert_eq!(
+            state.take(output).unwrap(),
+            vec![Tensor::f32s(&[2], &[1.0, 2.0]).unwrap()]
+        );
+    }
+
+    #[test]
+    fn plan_keeps_pinned_intermediates() {
+        let model = chain();
+        let mid = model.node_id_by_name("mid").unwrap();
+        let mut state = model.state();
+        state
+            .set_values(vec![("input", Tensor::f32s(&[1], &[1.0]).unwrap())])
+            .unwrap();
+        let mut plan =
+            Plan::for_model(&model, &[model.node_id_by_name("output").unwrap()]).unwrap();
+        plan.set_free_intermediates(true);
+        plan.pin(&[mid]);
+        plan.run(&mut state).unwrap();
+        assert_eq!(
+            state.take(mid).unwrap(),
+            vec![Tensor::f32s(&[1], &[4.0]).unwrap()]
+        );
+    }
+}
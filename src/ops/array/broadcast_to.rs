@@ -0,0 +1,145 @@
+use analyser::interface::*;
+use ops::prelude::*;
+
+/// TensorFlow `BroadcastTo`, which broadcasts a tensor to the shape given by
+/// its second input.
+///
+/// The input dimensions are aligned with the trailing target dimensions, and
+/// each of them must either be 1 or match its target dimension.
+#[derive(Debug, Clone, new)]
+pub struct BroadcastTo<T: Datum>(PhantomData<T>);
+
+pub fn broadcast_to(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    Ok(boxed_new!(BroadcastTo(dtype)()))
+}
+
+impl<T: Datum> Op for BroadcastTo<T> {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (input, shape) = args_2!(inputs);
+        let input = T::tensor_to_view(&input)?;
+        let shape = i32::tensor_to_view(&shape)?;
+        if shape.iter().any(|&d| d < 0) {
+            bail!("BroadcastTo expects a positive shape, got {:?}", shape);
+        }
+        let shape: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+        let output = input
+            .broadcast(&*shape)
+            .ok_or_else(|| {
+                format!(
+                    "BroadcastTo can not broadcast a tensor of shape {:?} to {:?}",
+                    input.shape(),
+                    shape
+                )
+            })?
+            .to_owned();
+        Ok(vec![T::array_into_tensor(output).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T" => Attr::DataType(T::datatype()),
+        }
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for BroadcastTo<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 2)
+            .equals(&outputs.len, 1)
+            .equals(&inputs[0].datatype, T::datatype())
+            .equals(&inputs[1].datatype, DataType::I32)
+            .equals(&outputs[0].datatype, T::datatype())
+            .equals(&inputs[1].rank, 1)
+            .given(&inputs[1].value, move |solver, shape: Tensor| {
+                let shape = <i32 as Datum>::tensor_into_array(shape).unwrap(); // checked
+                if shape.iter().any(|&d| d < 0) {
+                    solver.fail(format!("BroadcastTo expects a positive shape, got {:?}", shape));
+                    return;
+                }
+                let shape: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+                solver.equals(&outputs[0].shape, ShapeFact::from(shape.clone()));
+                solver.given(&inputs[0].rank, move |solver, rank: usize| {
+                    if rank > shape.len() {
+                        solver.fail(format!(
+                            "BroadcastTo can not broadcast a tensor of rank {} to {:?}",
+                            rank, shape
+                        ));
+                        return;
+                    }
+                    // Every input dimension which is not 1 must match the
+                    // target dimension it is aligned with.
+                    for ix in 0..rank {
+                        let target = shape[shape.len() - rank + ix];
+                        solver.given(&inputs[0].shape[ix], move |solver, dim: usize| {
+                            if dim != 1 {
+                                solver.equals(&inputs[0].shape[ix], target as isize);
+                            }
+                        });
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    #[test]
+    fn broadcast_rows() {
+        let input = Tensor::f32s(&[1, 3], &[1.0, 2.0, 3.0]).unwrap();
+        let shape = Tensor::i32s(&[2], &[4, 3]).unwrap();
+        let found = BroadcastTo::<f32>::new()
+            .eval(vec![input.into(), shape.into()])
+            .unwrap();
+        let expected = Tensor::f32s(&[4, 3], &[1.0, 2.0, 3.0].repeat(4)).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+    }
+
+    #[test]
+    fn incompatible_shapes() {
+        let input = Tensor::f32s(&[2, 3], &[0.0; 6]).unwrap();
+        let shape = Tensor::i32s(&[2], &[4, 3]).unwrap();
+        assert!(
+            BroadcastTo::<f32>::new()
+                .eval(vec![input.into(), shape.into()])
+                .is_err()
+        );
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![2, 3];
+        let shape = Tensor::i32s(&[2], &[4, 3]).unwrap();
+        assert!(
+            BroadcastTo::<f32>::new()
+                .infer(vec![input, shape.into()], vec![TensorFact::new()])
+                .is_err()
+        );
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![1, 1, 3];
+        let shape = Tensor::i32s(&[2], &[4, 3]).unwrap();
+        assert!(
+            BroadcastTo::<f32>::new()
+                .infer(vec![input, shape.into()], vec![TensorFact::new()])
+                .is_err()
+        );
+
+        let shape = Tensor::i32s(&[2], &[-1, 3]).unwrap();
+        assert!(
+            BroadcastTo::<f32>::new()
+                .infer(vec![TensorFact::new(), shape.into()], vec![TensorFact::new()])
+                .is_err()
+        );
+    }
+}
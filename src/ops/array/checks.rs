@@ -0,0 +1,271 @@
+use analyser::interface::*;
+use ops::prelude::*;
+
+/// TensorFlow `Assert`, which takes a predicate followed by the data to
+/// print when it does not hold.
+///
+/// The predicate is not checked: the op forwards its first data input (or
+/// the predicate itself if there is none), so that the nodes depending on it
+/// can be computed.
+#[derive(Debug, Clone, new)]
+pub struct Assert {
+    inputs: usize,
+}
+
+impl Assert {
+    pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        let inputs = pb.get_input().iter().filter(|i| !i.starts_with("^")).count();
+        if inputs == 0 {
+            bail!("Assert expects at least a predicate input");
+        }
+        Ok(Box::new(Assert::new(inputs)))
+    }
+
+    /// The index of the forwarded input.
+    fn forwarded(&self) -> usize {
+        if self.inputs > 1 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl Op for Assert {
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{}
+    }
+
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        if inputs.len() != self.inputs {
+            bail!("Assert expects {} inputs, got {}", self.inputs, inputs.len());
+        }
+        Ok(vec![inputs.swap_remove(self.forwarded())])
+    }
+}
+
+impl InferenceRulesOp for Assert {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let forwarded = &inputs[self.forwarded()];
+        solver
+            .equals(&inputs.len, self.inputs as isize)
+            .equals(&outputs.len, 1)
+            .equals(&inputs[0].datatype, DataType::Bool)
+            .equals(&forwarded.datatype, &outputs[0].datatype)
+            .equals(&forwarded.shape, &outputs[0].shape)
+            .equals(&forwarded.value, &outputs[0].value);
+    }
+}
+
+/// TensorFlow `CheckNumerics`, an identity which fails on NaN or infinite
+/// values.
+///
+/// The values are only scanned when the `check_numerics` feature is enabled,
+/// otherwise the input is forwarded untouched.
+#[derive(Debug, Clone, new)]
+pub struct CheckNumerics {
+    message: String,
+    enabled: bool,
+}
+
+impl CheckNumerics {
+    pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        let message = pb.get_attr_opt_str("message")?.unwrap_or_default();
+        Ok(Box::new(CheckNumerics::new(
+            message,
+            cfg!(feature = "check_numerics"),
+        )))
+    }
+
+    /// Fails if the tensor holds a NaN or an infinite value.
+    fn check(&self, tensor: &Tensor) -> Result<()> {
+        let (nan, inf) = match tensor {
+            &Tensor::F32(ref a) => (
+                a.iter().any(|x| x.is_nan()),
+                a.iter().any(|x| x.is_infinite()),
+            ),
+            &Tensor::F64(ref a) => (
+                a.iter().any(|x| x.is_nan()),
+                a.iter().any(|x| x.is_infinite()),
+            ),
+            _ => bail!("CheckNumerics expects a floating point tensor"),
+        };
+        match (nan, inf) {
+            (true, true) => bail!("{} : Tensor had Inf and NaN values", self.message),
+            (true, false) => bail!("{} : Tensor had NaN values", self.message),
+            (false, true) => bail!("{} : Tensor had Inf values", self.message),
+            (false, false) => Ok(()),
+        }
+    }
+}
+
+impl Op for CheckNumerics {
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{}
+    }
+
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let input = args_1!(inputs);
+        if self.enabled {
+            self.check(input.as_tensor())?;
+        }
+        Ok(vec![input])
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
+    /// Evaluates one step of the operation on the given input tensors.
+    fn step(
+        &self,
+        mut inputs: Vec<(Option<usize>, Option<TensorView>)>,
+        _: &mut Box<OpBuffer>,
+    ) -> Result<Option<Vec<TensorView>>> {
+        let input = args_1!(inputs);
+        match input.1 {
+            None => Ok(None),
+            Some(tv) => Ok(Some(self.eval(vec![tv])?)),
+        }
+    }
+}
+
+impl InferenceRulesOp for CheckNumerics {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 1)
+            .equals(&outputs.len, 1)
+            .equals(&inputs[0].datatype, &outputs[0].datatype)
+            .equals(&inputs[0].shape, &outputs[0].shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfpb::types::DataType::DT_FLOAT;
+    use Model;
+
+    #[test]
+    fn run_past_assert() {
+        let graph = ::tfpb::graph()
+            .node(
+                ::tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Const")
+                    .name("predicate")
+                    .attr("dtype", ::tfpb::types::DataType::DT_BOOL)
+                    .attr("value", Tensor::from(::ndarray::arr0(false)).to_pb().unwrap()),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Assert")
+                    .name("assert")
+                    .input("predicate")
+                    .input("input"),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("CheckNumerics")
+                    .name("checked")
+                    .attr("T", DT_FLOAT)
+                    .attr("message", "checked")
+                    .input("assert"),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Abs")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("checked")
+                    .input("^assert"),
+            );
+        let model = Model::new(graph).unwrap();
+        let input = Tensor::f32s(&[2], &[-1.0, 2.0]).unwrap();
+        let found = model
+            .run_with_names(vec![("input", input)], "output")
+            .unwrap();
+        assert_eq!(found[0], Tensor::f32s(&[2], &[1.0, 2.0]).unwrap());
+    }
+
+    #[test]
+    fn assert_with_string_message() {
+        // As exported by TensorFlow: the data starts with a string message,
+        // and the consumers only depend on the Assert through control inputs.
+        let message = Tensor::String(::ndarray::arr0(b"input is negative".to_vec()).into_dyn());
+        let graph = ::tfpb::graph()
+            .node(
+                ::tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Const")
+                    .name("predicate")
+                    .attr("dtype", ::tfpb::types::DataType::DT_BOOL)
+                    .attr("value", Tensor::from(::ndarray::arr0(true)).to_pb().unwrap()),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Const")
+                    .name("message")
+                    .attr("dtype", ::tfpb::types::DataType::DT_STRING)
+                    .attr("value", message.to_pb().unwrap()),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Assert")
+                    .name("assert")
+                    .input("predicate")
+                    .input("message")
+                    .input("input"),
+            )
+            .node(
+                ::tfpb::node()
+                    .op("Abs")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("^assert"),
+            );
+        let model = Model::new(graph).unwrap();
+        let input = Tensor::f32s(&[2], &[-1.0, 2.0]).unwrap();
+        let found = model
+            .run_with_names(vec![("input", input)], "output")
+            .unwrap();
+        assert_eq!(found[0], Tensor::f32s(&[2], &[1.0, 2.0]).unwrap());
+    }
+
+    #[test]
+    fn check_numerics() {
+        let check = CheckNumerics::new("nan".to_string(), true);
+        let nan = Tensor::f32s(&[2], &[1.0, ::std::f32::NAN]).unwrap();
+        assert!(check.eval(vec![nan.clone().into()]).is_err());
+        let finite = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap();
+        assert!(check.eval(vec![finite.into()]).is_ok());
+        let unchecked = CheckNumerics::new("nan".to_string(), false);
+        assert!(unchecked.eval(vec![nan.into()]).is_ok());
+    }
+}
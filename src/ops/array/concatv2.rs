@@ -9,6 +9,16 @@ pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     Ok(boxed_new!(ConcatV2(t)(n, tidx)))
 }
 
+/// Normalizes a possibly negative concatenation axis for inputs of the given
+/// rank.
+fn normalize_axis(axis: i32, rank: usize) -> Result<usize> {
+    let normalized = if axis < 0 { axis + rank as i32 } else { axis };
+    if normalized < 0 || normalized as usize >= rank {
+        bail!("Invalid ConcatV2 axis {} for inputs of rank {}", axis, rank);
+    }
+    Ok(normalized as usize)
+}
+
 #[derive(Debug, Clone, new)]
 pub struct ConcatV2<T: Datum> {
     n: usize,
@@ -35,9 +45,12 @@ impl<T: Datum> Op for ConcatV2<T> {
             .into_iter()
             .next()
             .unwrap();
-        let mats: Result<Vec<ArrayViewD<T>>> =
-            inputs.iter().map(|mat| T::tensor_to_view(&mat)).collect();
-        let result = ::ndarray::stack(Axis(axis as usize), &*mats?)?;
+        let mats: Vec<ArrayViewD<T>> = inputs
+            .iter()
+            .map(|mat| T::tensor_to_view(&mat))
+            .collect::<Result<_>>()?;
+        let axis = normalize_axis(axis, mats.get(0).map(|m| m.ndim()).unwrap_or(0))?;
+        let result = ::ndarray::stack(Axis(axis), &*mats)?;
         let result = T::array_into_tensor(result);
 
         Ok(vec![result.into()])
@@ -48,6 +61,11 @@ impl<T: Datum> Op for ConcatV2<T> {
         Box::new(QueuesBuffer::new(self.n))
     }
 
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
     /// Evaluates one step of the operation on the given input tensors.
     fn step(
         &self,
@@ -77,8 +95,15 @@ impl<T: Datum> Op for ConcatV2<T> {
             .next()
             .unwrap()
             .clone();
+        let rank = inputs[0..self.n]
+            .iter()
+            .filter_map(|i| i.1.as_ref())
+            .map(|chunk| chunk.as_tensor().shape().len())
+            .next()
+            .ok_or("ConcatV2 expects at least one chunk.")?;
+        let axis = normalize_axis(axis, rank)?;
 
-        if inputs[0..self.n].iter().all(|i| i.0 == Some(axis as usize)) {
+        if inputs[0..self.n].iter().all(|i| i.0 == Some(axis)) {
             // All the input tensors are streamed along `axis`.
             let chunk = inputs[0..self.n]
                 .iter_mut()
@@ -131,27 +156,63 @@ impl<T: Datum> InferenceRulesOp for ConcatV2<T> {
             .equals(&inputs[n].rank, 0)
             .equals(&outputs[0].rank, &inputs[0].rank)
             .given(&inputs[n].value, move |solver, axis: Tensor| {
-                let axis = *axis.as_i32s().unwrap().iter().next().unwrap() as usize; // both checked
+                let axis = *axis.as_i32s().unwrap().iter().next().unwrap(); // both checked
                 trace!("axis for Concatv2: {}", axis);
-                (0..axis).for_each(|d| {
-                    solver.equals_all((0..n).map(|i| bexp(&inputs[i].shape[d])).collect());
-                });
-                (0..axis).for_each(|d| {
-                    solver.equals(&inputs[0].shape[d], &outputs[0].shape[d]);
-                });
                 solver.given(&inputs[0].rank, move |solver, rank: usize| {
                     trace!("Given rank {}", rank);
-                    ((axis + 1)..rank).for_each(|d| {
+                    let axis = match normalize_axis(axis, rank) {
+                        Ok(axis) => axis,
+                        Err(_) => return,
+                    };
+                    (0..rank).filter(|&d| d != axis).for_each(|d| {
                         solver.equals(&inputs[0].shape[d], &outputs[0].shape[d]);
-                    });
-                    ((axis + 1)..rank).for_each(|d| {
                         solver.equals_all((0..n).map(|i| bexp(&inputs[i].shape[d])).collect());
                     });
-                });
 
-                let mut concat_dim = vec![bexp((-1, &outputs[0].shape[axis]))];
-                concat_dim.extend((0..n).map(|i| bexp((1, &inputs[i].shape[axis]))));
-                solver.equals_zero(concat_dim);
+                    let mut concat_dim = vec![bexp((-1, &outputs[0].shape[axis]))];
+                    concat_dim.extend((0..n).map(|i| bexp((1, &inputs[i].shape[axis]))));
+                    solver.equals_zero(concat_dim);
+                });
             });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    fn inputs(axis: i32) -> Vec<Tensor> {
+        vec![
+            Tensor::f32s(&[2, 1], &[1.0, 2.0]).unwrap(),
+            Tensor::f32s(&[2, 2], &[3.0, 4.0, 5.0, 6.0]).unwrap(),
+            Tensor::f32s(&[2, 1], &[7.0, 8.0]).unwrap(),
+            Tensor::i32s(&[], &[axis]).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn concat_axis_1() {
+        let op = ConcatV2::<f32>::new(3, DataType::I32);
+        let expected = Tensor::f32s(&[2, 4], &[1.0, 3.0, 4.0, 7.0, 2.0, 5.0, 6.0, 8.0]).unwrap();
+        for &axis in &[1, -1] {
+            let found = op.eval(inputs(axis).into_iter().map(|t| t.into()).collect())
+                .unwrap();
+            assert_eq!(found[0].as_tensor(), &expected);
+        }
+        assert!(op.eval(inputs(2).into_iter().map(|t| t.into()).collect()).is_err());
+        assert!(op.eval(inputs(0).into_iter().map(|t| t.into()).collect()).is_err());
+    }
+
+    #[test]
+    fn concat_infer_negative_axis() {
+        let op = ConcatV2::<f32>::new(3, DataType::I32);
+        let mut facts: Vec<TensorFact> = inputs(-1).into_iter().map(|t| t.into()).collect();
+        facts[1] = TensorFact::new();
+        facts[1].shape = shapefact![_, 2];
+        let (inputs, outputs) = op.infer(facts, vec![TensorFact::new()]).unwrap();
+        assert_eq!(inputs[1].shape, shapefact![2, 2]);
+        assert_eq!(outputs[0].shape, shapefact![2, 4]);
+    }
+}
@@ -11,7 +11,7 @@ pub struct Fill<T: Datum> {
 }
 
 pub fn fill(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
-    let dtype = pb.get_attr_datatype("T")?;
+    let dtype = pb.get_attr_first_datatype(&["T", "dtype"])?;
     Ok(boxed_new!(Fill(dtype)()))
 }
 
@@ -61,3 +61,22 @@ impl<T: Datum> InferenceRulesOp for Fill<T> {
             });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfpb::types::DataType::DT_INT32;
+
+    #[test]
+    fn fill_datatype_attributes() {
+        for &attr in &["T", "dtype"] {
+            let op = fill(&::tfpb::node().op("Fill").attr(attr, DT_INT32)).unwrap();
+            let found = op.eval(vec![
+                Tensor::i32s(&[2], &[2, 1]).unwrap().into(),
+                Tensor::i32s(&[], &[7]).unwrap().into(),
+            ]).unwrap();
+            assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[2, 1], &[7, 7]).unwrap());
+        }
+        assert!(fill(&::tfpb::node().op("Fill")).is_err());
+    }
+}
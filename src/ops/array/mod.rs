@@ -2,26 +2,38 @@ use analyser::interface::*;
 use ndarray::prelude::*;
 use ops::prelude::*;
 
+mod broadcast_to;
+mod checks;
 mod concatv2;
 mod fill;
+mod nd_index;
 mod pack;
 mod pad;
 mod reshape;
+mod slice;
 mod squeeze;
 mod strided_slice;
+mod transpose;
 
 pub fn register_all_ops(reg: &mut OpRegister) {
+    reg.insert("Assert", checks::Assert::build);
+    reg.insert("BroadcastTo", broadcast_to::broadcast_to);
+    reg.insert("CheckNumerics", checks::CheckNumerics::build);
     reg.insert("ConcatV2", concatv2::build);
     reg.insert("ExpandDims", ExpandDims::build);
     reg.insert("Identity", Identity::build);
     reg.insert("Fill", fill::fill);
+    reg.insert("GatherNd", nd_index::gather_nd);
     reg.insert("Pack", pack::pack);
     reg.insert("Pad", pad::pad);
     reg.insert("Placeholder", Placeholder::build);
     reg.insert("Reshape", reshape::reshape);
+    reg.insert("ScatterNd", nd_index::scatter_nd);
     reg.insert("Shape", Shape::build);
+    reg.insert("Slice", slice::slice);
     reg.insert("Squeeze", squeeze::squeeze);
     reg.insert("StridedSlice", strided_slice::build);
+    reg.insert("Transpose", transpose::transpose);
 }
 
 #[derive(Debug, Clone)]
@@ -57,6 +69,11 @@ impl Op for ExpandDims {
         Ok(vec![Tensor::from(data.into_shape(shape)?).into()])
     }
 
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
     /// Evaluates one step of the operation on the given input tensors.
     fn step(
         &self,
@@ -135,6 +152,11 @@ impl Op for Identity {
         Ok(inputs)
     }
 
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
     /// Evaluates one step of the operation on the given input tensors.
     fn step(
         &self,
@@ -167,12 +189,14 @@ impl InferenceRulesOp for Identity {
 #[derive(Debug, Clone)]
 pub struct Placeholder {
     dtype: DataType,
+    shape: Option<Vec<isize>>,
 }
 
 impl Placeholder {
     pub fn build(node: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
         Ok(Box::new(Placeholder {
             dtype: node.get_attr_datatype("dtype")?,
+            shape: node.get_attr_opt_shape("shape")?,
         }))
     }
 }
@@ -185,9 +209,18 @@ impl Op for Placeholder {
 
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
-        hashmap!{
+        let mut attributes = hashmap!{
             "dtype" => Attr::DataType(self.dtype)
+        };
+        if let Some(ref shape) = self.shape {
+            attributes.insert("shape", Attr::Shape(shape.clone()));
         }
+        attributes
+    }
+
+    /// Estimates the number of floating point operations.
+    fn flops(&self, _inputs: &[TensorFact], _outputs: &[TensorFact]) -> Option<u64> {
+        Some(0)
     }
 
     fn infer_and_propagate(
@@ -215,26 +248,44 @@ impl InferenceRulesOp for Placeholder {
     }
 }
 
+/// Returns the shape of its input, as an i32 or i64 tensor depending on the
+/// `out_type` attribute.
 #[derive(Debug, Clone)]
-pub struct Shape;
+pub struct Shape {
+    out_type: DataType,
+}
 
 impl Shape {
-    pub fn build(_pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
-        Ok(Box::new(Shape))
+    pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        let out_type = pb.get_attr_opt_datatype("out_type")?.unwrap_or(DataType::I32);
+        if out_type != DataType::I32 && out_type != DataType::I64 {
+            bail!("Shape can only output i32 or i64, not {:?}", out_type);
+        }
+        Ok(Box::new(Shape { out_type }))
+    }
+
+    /// Builds the output tensor for the given dimensions.
+    fn shape_tensor(&self, dims: &[usize]) -> Tensor {
+        if self.out_type == DataType::I64 {
+            Tensor::from(dims.iter().map(|&d| d as i64).collect::<Array1<i64>>())
+        } else {
+            Tensor::from(dims.iter().map(|&d| d as i32).collect::<Array1<i32>>())
+        }
     }
 }
 
 impl Op for Shape {
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
-        hashmap!{}
+        hashmap!{
+            "out_type" => Attr::DataType(self.out_type)
+        }
     }
 
     /// Evaluates the operation given the input tensors.
     fn eval(&self, inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
         let data = inputs[0].as_f32s().ok_or("Expect input #0 to be f32")?;
-        let shape: Vec<i32> = data.shape().into_iter().map(|s| *s as i32).collect();
-        Ok(vec![Tensor::from(Array1::from_vec(shape)).into()])
+        Ok(vec![self.shape_tensor(data.shape()).into()])
     }
 }
 
@@ -248,34 +299,31 @@ impl InferenceRulesOp for Shape {
         solver
             .equals(&inputs.len, 1)
             .equals(&outputs.len, 1)
-            .equals(&outputs[0].datatype, DataType::I32)
+            .equals(&outputs[0].datatype, self.out_type)
             .equals(&outputs[0].rank, 1)
             .equals(&outputs[0].shape[0], &inputs[0].rank)
             .given(&inputs[0].shape, move |solver, shape: ShapeFact| {
                 if !shape.open && shape.dims.iter().all(|d| *d != DimFact::Any) {
-                    let shape = shape
+                    let shape: Vec<usize> = shape
                         .dims
                         .iter()
                         .map(|d| {
                             if let DimFact::Only(d) = d {
-                                *d as i32
+                                *d
                             } else {
                                 1
                             }
                         })
                         .collect();
-                    let array1: Array1<i32> = Array1::from_vec(shape);
-                    let tensor: Tensor = Tensor::from(array1);
+                    let tensor = self.shape_tensor(&shape);
                     solver.equals(&outputs[0].value, valuefact!(tensor));
                 }
             })
             .given(&outputs[0].value, move |solver, shape: Tensor| {
-                let shape: Vec<usize> = shape
-                    .take_i32s()
-                    .unwrap()
-                    .iter()
-                    .map(|i: &i32| *i as usize)
-                    .collect();
+                let shape: Vec<usize> = match shape {
+                    Tensor::I64(shape) => shape.iter().map(|&i| i as usize).collect(),
+                    shape => shape.take_i32s().unwrap().iter().map(|&i| i as usize).collect(),
+                };
                 for (ix, d) in shape.iter().enumerate() {
                     // hackish: if dim is 1, it may be the streaming
                     // dimension, so we don't infer
@@ -292,6 +340,33 @@ mod tests {
     use super::*;
     use tfpb::node;
 
+    #[test]
+    fn placeholder_shape() {
+        use tfpb::tensor_shape::{TensorShapeProto, TensorShapeProto_Dim};
+        let mut shape = TensorShapeProto::new();
+        shape.set_dim(
+            [-1, 3]
+                .iter()
+                .map(|&i| {
+                    let mut d = TensorShapeProto_Dim::new();
+                    d.set_size(i);
+                    d
+                })
+                .collect(),
+        );
+        let pb = node()
+            .op("Placeholder")
+            .attr("dtype", DataType::F32)
+            .attr("shape", shape);
+        match Placeholder::build(&pb).unwrap().get_attributes().get("shape") {
+            Some(&Attr::Shape(ref shape)) => assert_eq!(shape, &vec![-1, 3]),
+            other => panic!("Unexpected shape {:?}", other),
+        }
+
+        let pb = node().op("Placeholder").attr("dtype", DataType::F32);
+        assert!(Placeholder::build(&pb).unwrap().get_attributes().get("shape").is_none());
+    }
+
     #[test]
     fn shape_inference_1() {
         let input = TensorFact {
@@ -359,4 +434,13 @@ mod tests {
 
         assert_backward!(Shape::build(&node()).unwrap(), input, output);
     }
+
+    #[test]
+    fn shape_i64() {
+        use tfpb::types::DataType::DT_INT64;
+        let shape = Shape::build(&node().attr("out_type", DT_INT64)).unwrap();
+        let input = Tensor::f32s(&[2, 1, 3], &[0.0; 6]).unwrap();
+        let output = shape.eval(vec![input.into()]).unwrap();
+        assert_eq!(output[0].as_tensor(), &Tensor::i64s(&[3], &[2, 1, 3]).unwrap());
+    }
 }
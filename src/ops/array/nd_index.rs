@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use analyser::interface::*;
+use ndarray::prelude::*;
+use ops::prelude::*;
+use tensor::Datum;
+use Result;
+
+/// Reads an index tensor, which must be of the `tindices` datatype, either
+/// i32 or i64.
+fn indices(indices: &Tensor, tindices: DataType) -> Result<ArrayD<i64>> {
+    if indices.datatype() != tindices {
+        bail!("Expected {:?} indices, got {:?}", tindices, indices.datatype());
+    }
+    match indices {
+        &Tensor::I32(ref indices) => Ok(indices.mapv(|i| i as i64)),
+        &Tensor::I64(ref indices) => Ok(indices.clone()),
+        _ => bail!("Indices must be i32 or i64, got {:?}", indices.datatype()),
+    }
+}
+
+/// Checks that an index datatype is supported.
+fn check_tindices(tindices: DataType) -> Result<DataType> {
+    match tindices {
+        DataType::I32 | DataType::I64 => Ok(tindices),
+        _ => bail!("Indices must be i32 or i64, got {:?}", tindices),
+    }
+}
+
+/// Splits an index tensor in a list of coordinate vectors, and returns the
+/// flat offsets they point to in a standard layout tensor of `shape`,
+/// together with the number of elements in each pointed slice.
+///
+/// The coordinates are checked against `shape` unless `checked` is false, in
+/// which case only negative coordinates and overflowing offsets are rejected.
+fn offsets(
+    indices: &ArrayViewD<i64>,
+    shape: &[usize],
+    checked: bool,
+) -> Result<(Vec<usize>, usize)> {
+    if indices.ndim() == 0 {
+        bail!("Indices must be at least of rank 1");
+    }
+    let depth = indices.shape()[indices.ndim() - 1];
+    if depth > shape.len() {
+        bail!(
+            "Indices depth ({}) exceeds indexed tensor rank ({})",
+            depth,
+            shape.len()
+        );
+    }
+    let slice_len: usize = shape[depth..].iter().product();
+    let strides: Vec<usize> = (0..depth)
+        .map(|d| shape[d + 1..depth].iter().product::<usize>() * slice_len)
+        .collect();
+    let count: usize = indices.shape()[..indices.ndim() - 1].iter().product();
+    let indices: Vec<i64> = indices.iter().cloned().collect();
+    let offsets = if depth == 0 {
+        vec![0; count]
+    } else {
+        indices
+            .chunks(depth)
+            .map(|coords| {
+                let mut offset = 0usize;
+                for (d, &c) in coords.iter().enumerate() {
+                    if c < 0 || (checked && c as usize >= shape[d]) {
+                        bail!("Index {} out of bounds for dimension {} of {:?}", c, d, shape);
+                    }
+                    offset = (c as usize)
+                        .checked_mul(strides[d])
+                        .and_then(|o| o.checked_add(offset))
+                        .ok_or_else(|| format!("Index {:?} overflows {:?}", coords, shape))?;
+                }
+                Ok(offset)
+            })
+            .collect::<Result<Vec<usize>>>()?
+    };
+    Ok((offsets, slice_len))
+}
+
+/// Gathers slices of `params` at the coordinates given by `indices`.
+///
+/// Indices are checked against each dimension unless the node has a false
+/// `validate_indices` attribute. Unchecked mode only makes sure that each
+/// slice lies within `params`, so an out of bounds coordinate may pick a
+/// slice from the next row instead of failing.
+///
+/// The indices can be either i32 or i64, as given by `Tindices`.
+#[derive(Debug, Clone, new)]
+pub struct GatherNd<T: Datum> {
+    checked: bool,
+    tindices: DataType,
+    _phantom: PhantomData<T>,
+}
+
+pub fn gather_nd(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("Tparams")?;
+    let checked = pb.get_attr_opt_bool("validate_indices")?.unwrap_or(true);
+    let tindices = check_tindices(pb.get_attr_opt_datatype("Tindices")?.unwrap_or(DataType::I32))?;
+    Ok(boxed_new!(GatherNd(dtype)(checked, tindices)))
+}
+
+impl<T> Op for GatherNd<T>
+where
+    T: Datum,
+{
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (params, indices) = args_2!(inputs);
+        let params = T::tensor_to_view(&params)?;
+        let indices = self::indices(&indices, self.tindices)?;
+        let (offsets, slice_len) = offsets(&indices.view(), params.shape(), self.checked)?;
+
+        let rank = indices.ndim();
+        let depth = indices.shape()[rank - 1];
+        let mut shape = indices.shape()[..rank - 1].to_vec();
+        shape.extend_from_slice(&params.shape()[depth..]);
+
+        let params: Vec<T> = params.iter().cloned().collect();
+        let mut data = Vec::with_capacity(offsets.len() * slice_len);
+        for offset in offsets {
+            let slice = offset
+                .checked_add(slice_len)
+                .and_then(|end| params.get(offset..end))
+                .ok_or("GatherNd index out of bounds")?;
+            data.extend_from_slice(slice);
+        }
+        let output = ArrayD::from_shape_vec(shape, data)?;
+        Ok(vec![T::array_into_tensor(output).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "Tparams"          => Attr::DataType(T::datatype()),
+            "Tindices"         => Attr::DataType(self.tindices),
+            "validate_indices" => Attr::Bool(self.checked),
+        }
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for GatherNd<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let params = &inputs[0];
+        let indices = &inputs[1];
+        let output = &outputs[0];
+        solver
+            .equals(&inputs.len, 2)
+            .equals(&outputs.len, 1)
+            .equals(&params.datatype, T::datatype())
+            .equals(&output.datatype, T::datatype())
+            .equals(&indices.datatype, self.tindices)
+            .given(&indices.rank, move |solver, rank: usize| {
+                if rank == 0 {
+                    solver.fail("GatherNd indices must be at least of rank 1");
+                    return;
+                }
+                let depth = &indices.shape[rank - 1];
+                solver.equals_zero(wrap!(
+                    (-1, &output.rank),
+                    (1, &indices.rank),
+                    (-1isize, 1),
+                    (1, &params.rank),
+                    (-1, depth)
+                ));
+                (0..rank - 1).for_each(|d| {
+                    solver.equals(&output.shape[d], &indices.shape[d]);
+                });
+                solver.given(depth, move |solver, depth: usize| {
+                    solver.given(&params.rank, move |solver, params_rank: usize| {
+                        (depth..params_rank).for_each(|d| {
+                            solver.equals(&output.shape[rank - 1 + d - depth], &params.shape[d]);
+                        });
+                    });
+                });
+            });
+    }
+}
+
+/// Scatters updates in a zero tensor, at the coordinates given by `indices`.
+///
+/// The indices and shape can be either i32 or i64, as given by `Tindices`.
+#[derive(Debug, Clone, new)]
+pub struct ScatterNd<T: Datum> {
+    tindices: DataType,
+    _phantom: PhantomData<T>,
+}
+
+pub fn scatter_nd(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    let tindices = check_tindices(pb.get_attr_opt_datatype("Tindices")?.unwrap_or(DataType::I32))?;
+    Ok(boxed_new!(ScatterNd(dtype)(tindices)))
+}
+
+impl<T> Op for ScatterNd<T>
+where
+    T: Datum,
+{
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (indices, updates, shape) = args_3!(inputs);
+        let indices = self::indices(&indices, self.tindices)?;
+        let updates = T::tensor_to_view(&updates)?;
+        let shape = self::indices(&shape, self.tindices)?;
+        if shape.iter().any(|&d| d < 0) {
+            bail!("ScatterNd shape has a negative dimension: {:?}", shape);
+        }
+        let shape: Vec<usize> = shape.iter().map(|&d| d as usize).collect();
+        let (offsets, slice_len) = offsets(&indices.view(), &shape, true)?;
+        if updates.len() != offsets.len() * slice_len {
+            bail!(
+                "Expected {} updates of {} elements, got {:?}",
+                offsets.len(),
+                slice_len,
+                updates.shape()
+            );
+        }
+
+        let mut data = vec![T::zero(); shape.iter().product()];
+        let updates: Vec<T> = updates.iter().cloned().collect();
+        for (offset, update) in offsets.into_iter().zip(updates.chunks(slice_len.max(1))) {
+            // Duplicate coordinates accumulate, like in TensorFlow.
+            for (x, &u) in data[offset..offset + slice_len].iter_mut().zip(update) {
+                *x += u;
+            }
+        }
+        let output = ArrayD::from_shape_vec(shape, data)?;
+        Ok(vec![T::array_into_tensor(output).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T"        => Attr::DataType(T::datatype()),
+            "Tindices" => Attr::DataType(self.tindices),
+        }
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for ScatterNd<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let indices = &inputs[0];
+        let updates = &inputs[1];
+        let shape = &inputs[2];
+        let output = &outputs[0];
+        solver
+            .equals(&inputs.len, 3)
+            .equals(&outputs.len, 1)
+            .equals(&indices.datatype, self.tindices)
+            .equals(&updates.datatype, T::datatype())
+            .equals(&output.datatype, T::datatype())
+            .equals(&shape.datatype, self.tindices)
+            .equals(&shape.rank, 1)
+            .equals(&output.rank, &shape.shape[0])
+            .given(&shape.value, move |solver, shape: Tensor| {
+                let shape = match self::indices(&shape, self.tindices) {
+                    Ok(shape) => shape,
+                    Err(e) => {
+                        solver.fail(e.to_string());
+                        return;
+                    }
+                };
+                if shape.iter().any(|&d| d < 0) {
+                    solver.fail(format!("ScatterNd shape has a negative dimension: {:?}", shape));
+                    return;
+                }
+                let shape: ShapeFact = shape.iter().map(|&d| d as usize).collect();
+                solver.equals(&output.shape, shape);
+            })
+            .given(&indices.rank, move |solver, rank: usize| {
+                if rank == 0 {
+                    solver.fail("ScatterNd indices must be at least of rank 1");
+                    return;
+                }
+                let depth = &indices.shape[rank - 1];
+                solver.equals_zero(wrap!(
+                    (-1, &updates.rank),
+                    (1, &indices.rank),
+                    (-1isize, 1),
+                    (1, &output.rank),
+                    (-1, depth)
+                ));
+                (0..rank - 1).for_each(|d| {
+                    solver.equals(&updates.shape[d], &indices.shape[d]);
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ndarray::arr2;
+    use ops::InferenceOp;
+    use Tensor;
+
+    #[test]
+    fn gather_nd_rank_2() {
+        let params = Tensor::from(arr2(&[[1, 2, 3], [4, 5, 6], [7, 8, 9]]));
+        let indices = Tensor::from(arr2(&[[2, 0], [0, 1], [1, 2]]));
+        let result = GatherNd::<i32>::new(true, DataType::I32)
+            .eval(vec![params.into(), indices.into()])
+            .unwrap();
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr1(&[7, 2, 6])));
+    }
+
+    #[test]
+    fn gather_nd_rows() {
+        let params = Tensor::from(arr2(&[[1, 2], [3, 4]]));
+        let indices = Tensor::from(arr2(&[[1], [0]]));
+        let result = GatherNd::<i32>::new(true, DataType::I32)
+            .eval(vec![params.into(), indices.into()])
+            .unwrap();
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr2(&[[3, 4], [1, 2]])));
+    }
+
+    #[test]
+    fn scatter_nd_rank_2() {
+        let indices = Tensor::from(arr2(&[[0, 1], [2, 2], [0, 1]]));
+        let updates = Tensor::from(arr1(&[5, 7, 1]));
+        let shape = Tensor::from(arr1(&[3, 3]));
+        let result = ScatterNd::<i32>::new(DataType::I32)
+            .eval(vec![indices.into(), updates.into(), shape.into()])
+            .unwrap();
+        assert_eq!(
+            result[0].as_tensor(),
+            &Tensor::from(arr2(&[[0, 6, 0], [0, 0, 0], [0, 0, 7]]))
+        );
+    }
+
+    #[test]
+    fn gather_nd_out_of_bounds() {
+        let params = Tensor::from(arr2(&[[1, 2], [3, 4]]));
+        let indices = Tensor::from(arr2(&[[2, 0]]));
+        assert!(
+            GatherNd::<i32>::new(true, DataType::I32)
+                .eval(vec![params.into(), indices.into()])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn gather_nd_unchecked() {
+        let params = Tensor::from(arr2(&[[1, 2], [3, 4]]));
+        let indices = Tensor::from(arr2(&[[1, 0]]));
+        let result = GatherNd::<i32>::new(false, DataType::I32)
+            .eval(vec![params.into(), indices.into()])
+            .unwrap();
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr1(&[3])));
+
+        // Unchecked mode still rejects the slices out of params.
+        let gather = GatherNd::<i32>::new(false, DataType::I32);
+        for indices in &[arr2(&[[2, 0]]), arr2(&[[-1, 0]]), arr2(&[[0, 7]])] {
+            let params = Tensor::from(arr2(&[[1, 2], [3, 4]]));
+            let indices = Tensor::from(indices.clone());
+            assert!(gather.eval(vec![params.into(), indices.into()]).is_err());
+        }
+    }
+
+    #[test]
+    fn gather_nd_i64_indices() {
+        let params = Tensor::from(arr2(&[[1, 2], [3, 4]]));
+        let gather = GatherNd::<i32>::new(true, DataType::I64);
+        let indices = Tensor::from(arr2(&[[1i64, 1], [0, 1]]));
+        let result = gather
+            .eval(vec![params.clone().into(), indices.into()])
+            .unwrap();
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr1(&[4, 2])));
+
+        // Truncated to 32 bits, this index would be 1.
+        let indices = Tensor::from(arr2(&[[(1i64 << 32) + 1, 0]]));
+        assert!(gather.eval(vec![params.into(), indices.into()]).is_err());
+    }
+
+    #[test]
+    fn gather_nd_whole_params() {
+        let params = Tensor::from(arr1(&[1, 2]));
+        let indices = Tensor::I32(ArrayD::zeros(vec![3, 0]));
+        let result = GatherNd::<i32>::new(true, DataType::I32)
+            .eval(vec![params.into(), indices.into()])
+            .unwrap();
+        assert_eq!(
+            result[0].as_tensor(),
+            &Tensor::from(arr2(&[[1, 2], [1, 2], [1, 2]]))
+        );
+    }
+
+    #[test]
+    fn scalar_indices_fail_inference() {
+        let mut indices = TensorFact::new();
+        indices.shape = shapefact![];
+        assert!(
+            GatherNd::<i32>::new(true, DataType::I32)
+                .infer(vec![TensorFact::new(), indices.clone()], vec![TensorFact::new()])
+                .is_err()
+        );
+        assert!(
+            ScatterNd::<i32>::new(DataType::I32)
+                .infer(
+                    vec![indices, TensorFact::new(), TensorFact::new()],
+                    vec![TensorFact::new()]
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn scatter_nd_negative_shape() {
+        let scatter = ScatterNd::<i32>::new(DataType::I32);
+        let indices = Tensor::from(arr2(&[[0]]));
+        let updates = Tensor::from(arr2(&[[1, 2]]));
+        let shape = Tensor::from(arr1(&[1, -2]));
+        assert!(
+            scatter
+                .eval(vec![indices.into(), updates.into(), shape.clone().into()])
+                .is_err()
+        );
+        assert!(
+            scatter
+                .infer(
+                    vec![TensorFact::new(), TensorFact::new(), shape.into()],
+                    vec![TensorFact::new()]
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn mismatched_indices_datatype() {
+        let params = Tensor::from(arr2(&[[1, 2], [3, 4]]));
+        let indices = Tensor::from(arr2(&[[1i64, 1]]));
+        assert!(
+            GatherNd::<i32>::new(true, DataType::I32)
+                .eval(vec![params.into(), indices.clone().into()])
+                .is_err()
+        );
+        let updates = Tensor::from(arr1(&[5]));
+        let shape = Tensor::from(arr1(&[2, 2]));
+        assert!(
+            ScatterNd::<i32>::new(DataType::I64)
+                .eval(vec![indices.into(), updates.into(), shape.into()])
+                .is_err()
+        );
+    }
+}
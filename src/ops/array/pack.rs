@@ -15,7 +15,15 @@ pub struct Pack<T: Datum> {
 
 pub fn pack(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     let dtype = pb.get_attr_datatype("T")?;
-    let n = pb.get_input().len();
+    // Control inputs only order the computations, they are not packed.
+    let inputs = pb.get_input().iter().filter(|i| !i.starts_with("^")).count();
+    let n = pb.get_attr_opt_int("N")?.unwrap_or(inputs);
+    if n != inputs {
+        bail!("Pack expects {} inputs, found {}", n, inputs);
+    }
+    if n == 0 {
+        bail!("Pack needs at least one input");
+    }
     let axis = pb.get_attr_int("axis")?;
 
     Ok(boxed_new!(Pack(dtype)(n, axis)))
@@ -27,13 +35,15 @@ where
 {
     /// Evaluates the operation given the input tensors.
     fn eval(&self, inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
-        use ndarray::Axis;
-        let views = inputs
+        let inputs = args_n!(inputs, self.n, self.n);
+        let tensors = inputs
             .iter()
-            .map(|m| Ok(T::tensor_to_view(&*m)?.insert_axis(Axis(self.axis))))
+            .map(|m| {
+                T::tensor_to_view(m)?;
+                Ok(m.as_tensor())
+            })
             .collect::<Result<Vec<_>>>()?;
-        let array = ::ndarray::stack(Axis(self.axis), &*views)?;
-        Ok(vec![T::array_into_tensor(array).into()])
+        Ok(vec![Tensor::stack(&tensors, self.axis)?.into()])
     }
 
     /// Returns the attributes of the operation and their values.
@@ -87,6 +97,45 @@ mod tests {
     use ndarray::arr2;
     use Tensor;
 
+    #[test]
+    fn args_n() {
+        fn count(inputs: Vec<TensorView>) -> Result<usize> {
+            Ok(args_n!(inputs, 2, 3).len())
+        }
+        let input = || TensorView::from(Tensor::f32s(&[1], &[1.0]).unwrap());
+        assert!(count(vec![input()]).is_err());
+        assert_eq!(count(vec![input(), input()]).unwrap(), 2);
+        assert_eq!(count(vec![input(), input(), input()]).unwrap(), 3);
+        assert!(count(vec![input(), input(), input(), input()]).is_err());
+
+        let pack = Pack::<f32>::new(2, 0);
+        assert!(pack.eval(vec![]).is_err());
+        assert!(pack.eval(vec![input(), input()]).is_ok());
+        assert!(pack.eval(vec![input(), input(), input()]).is_err());
+        assert!(pack.eval(vec![input()]).is_err());
+    }
+
+    #[test]
+    fn pack_inputs_count() {
+        use tfpb::types::DataType::DT_FLOAT;
+        let node = || {
+            ::tfpb::node()
+                .op("Pack")
+                .attr("T", DT_FLOAT)
+                .attr("axis", 0)
+                .input("a")
+                .input("b")
+                .input("^c")
+        };
+        let input = || TensorView::from(Tensor::f32s(&[1], &[1.0]).unwrap());
+        let op = pack(&node()).unwrap();
+        assert!(op.eval(vec![input(), input()]).is_ok());
+        assert!(op.eval(vec![input()]).is_err());
+
+        assert!(pack(&node().attr("N", 2)).is_ok());
+        assert!(pack(&node().attr("N", 3)).is_err());
+    }
+
     #[test]
     fn pack_0() {
         let inputs = vec![
@@ -114,16 +163,29 @@ mod tests {
 
     #[test]
     fn pack_1() {
-        let pack = Pack::<i32>::new(3, 0);
+        let pack = Pack::<i32>::new(1, 0);
         let input = Tensor::i32s(&[0], &[]).unwrap();
         let exp: Tensor = Tensor::i32s(&[1, 0], &[]).unwrap();
         let found = pack.eval(vec![input.into()]).unwrap();
 
         assert!(
-            exp.close_enough(&found[0]),
+            exp.close_enough(&found[0]).unwrap(),
             "expected: {:?} found: {:?}",
             exp,
             found[0]
         )
     }
+
+    #[test]
+    fn pack_zero_rows() {
+        let input = Tensor::i32s(&[0, 3], &[]).unwrap();
+        let found = Pack::<i32>::new(2, 0)
+            .eval(vec![input.clone().into(), input.clone().into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[2, 0, 3], &[]).unwrap());
+        let found = Pack::<i32>::new(2, 1)
+            .eval(vec![input.clone().into(), input.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[0, 2, 3], &[]).unwrap());
+    }
 }
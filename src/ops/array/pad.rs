@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
 
 use ndarray::{Array, ArrayD, ArrayView2, ArrayViewD};
@@ -13,12 +13,45 @@ pub struct Pad<T: Datum> {
     _phantom: PhantomData<T>,
 }
 
+/// The streaming buffer of Pad.
+///
+/// Padding along the streaming dimension delays the output stream by the
+/// length of the front padding: the buffer emits one zero chunk per input
+/// chunk until the front padding is done, queueing the real chunks, and
+/// releases the queue and the back padding on flush.
+#[derive(Debug, Clone)]
+pub struct PadBuffer<T: Datum> {
+    front: Option<usize>,
+    back: usize,
+    queue: VecDeque<ArrayD<T>>,
+    zeros: Option<ArrayD<T>>,
+}
+
+impl<T: Datum> OpBuffer for PadBuffer<T> {}
+
 pub fn pad(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     let dtype = pb.get_attr_datatype("T")?;
     Ok(boxed_new!(Pad(dtype)()))
 }
 
 impl<T: Datum> Pad<T> {
+    /// Checks that the paddings tensor is [rank, 2] and non-negative.
+    fn paddings(paddings: &Tensor, rank: usize) -> Result<ArrayView2<i32>> {
+        let paddings = i32::tensor_to_view(paddings)?;
+        if paddings.shape() != &[rank, 2] {
+            bail!(
+                "Pad expects paddings of shape [{}, 2] for a rank {} input, found {:?}",
+                rank,
+                rank,
+                paddings.shape()
+            );
+        }
+        if paddings.iter().any(|&p| p < 0) {
+            bail!("Pad expects non-negative paddings, found {:?}", paddings);
+        }
+        Ok(paddings.into_dimensionality()?)
+    }
+
     fn compute(
         input: &ArrayViewD<T>,
         paddings: ArrayView2<i32>,
@@ -39,12 +72,16 @@ impl<T: Datum> Pad<T> {
         let mut index_in_input = vec![0; input.ndim()];
         let result = Array::from_shape_fn(shape, |index| {
             for i in 0..input.ndim() {
-                if index[i] < paddings[(i, 0)] as usize
-                    || index[i] - paddings[(i, 0)] as usize >= input.shape()[i] as usize
-                {
+                // Padding along the streaming dimension is handled by `step`.
+                let before = if Some(i) == stream_dim {
+                    0
+                } else {
+                    paddings[(i, 0)] as usize
+                };
+                if index[i] < before || index[i] - before >= input.shape()[i] as usize {
                     return T::zero();
                 } else {
-                    index_in_input[i] = index[i] - paddings[(i, 0)] as usize;
+                    index_in_input[i] = index[i] - before;
                 };
             }
             input[&*index_in_input]
@@ -61,7 +98,7 @@ where
     fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
         let (input, paddings) = args_2!(inputs);
         let input = T::tensor_to_view(&input)?;
-        let paddings = i32::tensor_to_view(&paddings)?.into_dimensionality()?;
+        let paddings = Self::paddings(&paddings, input.ndim())?;
         Ok(vec![
             T::array_into_tensor(Self::compute(&input, paddings, None)?).into(),
         ])
@@ -74,21 +111,75 @@ where
         }
     }
 
+    /// Returns a new streaming buffer for the operation.
+    fn new_buffer(&self) -> Box<OpBuffer> {
+        Box::new(PadBuffer::<T> {
+            front: None,
+            back: 0,
+            queue: VecDeque::new(),
+            zeros: None,
+        })
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
+    /// Evaluates one step of the operation on the given input tensors.
     fn step(
         &self,
         mut inputs: Vec<(Option<usize>, Option<TensorView>)>,
-        _buffer: &mut Box<OpBuffer>,
+        buffer: &mut Box<OpBuffer>,
     ) -> Result<Option<Vec<TensorView>>> {
         if let ((Some(stream_dim), Some(chunk)), (None, Some(paddings))) = args_2!(inputs) {
+            let buffer = buffer
+                .downcast_mut::<PadBuffer<T>>()
+                .ok_or("The buffer can't be downcasted to PadBuffer<T>.")?;
             let chunk = T::tensor_to_view(&chunk)?;
-            let paddings = i32::tensor_to_view(&paddings)?.into_dimensionality()?;
-            Ok(Some(vec![
-                T::array_into_tensor(Self::compute(&chunk, paddings, Some(stream_dim))?).into(),
-            ]))
+            let paddings = Self::paddings(&paddings, chunk.ndim())?;
+            if buffer.front.is_none() {
+                buffer.front = Some(paddings[(stream_dim, 0)] as usize);
+                buffer.back = paddings[(stream_dim, 1)] as usize;
+            }
+            let padded = Self::compute(&chunk, paddings, Some(stream_dim))?;
+            if buffer.zeros.is_none() {
+                buffer.zeros = Some(ArrayD::zeros(padded.shape()));
+            }
+            buffer.queue.push_back(padded);
+
+            let output = match buffer.front {
+                Some(ref mut front) if *front > 0 => {
+                    *front -= 1;
+                    buffer.zeros.clone().unwrap()
+                }
+                _ => buffer.queue.pop_front().unwrap(),
+            };
+            Ok(Some(vec![T::array_into_tensor(output).into()]))
         } else {
             Ok(None)
         }
     }
+
+    fn flush(&self, buffer: &mut Box<OpBuffer>) -> Result<Vec<Vec<TensorView>>> {
+        let buffer = buffer
+            .downcast_mut::<PadBuffer<T>>()
+            .ok_or("The buffer can't be downcasted to PadBuffer<T>.")?;
+        let zeros = match buffer.zeros.take() {
+            Some(zeros) => zeros,
+            // Nothing was streamed, so there is nothing to pad.
+            None => return Ok(vec![]),
+        };
+        let mut chunks: Vec<ArrayD<T>> = buffer.queue.drain(..).collect();
+        // Short streams may not even have covered the front padding.
+        let front = buffer.front.take().unwrap_or(0);
+        chunks.splice(0..0, (0..front).map(|_| zeros.clone()));
+        chunks.extend((0..buffer.back).map(|_| zeros.clone()));
+        Ok(chunks
+            .into_iter()
+            .map(|c| vec![T::array_into_tensor(c).into()])
+            .collect())
+    }
 }
 
 impl<T: Datum> InferenceRulesOp for Pad<T> {
@@ -126,7 +217,7 @@ impl<T: Datum> InferenceRulesOp for Pad<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ndarray::arr2;
+    use ndarray::{arr2, Axis};
     use Tensor;
 
     #[test]
@@ -148,4 +239,56 @@ mod tests {
             vec![expected.into()]
         );
     }
+
+    #[test]
+    fn pad_wrong_paddings_shape() {
+        let input = Tensor::from(arr2(&[[1, 2, 3], [4, 5, 6]]));
+        let paddings = Tensor::i32s(&[3], &[1, 1, 1]).unwrap();
+        let err = Pad::<i32>::new()
+            .eval(vec![input.into(), paddings.into()])
+            .unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Pad expects paddings of shape [2, 2] for a rank 2 input, found [3]"
+        );
+    }
+
+    #[test]
+    fn pad_streaming() {
+        let input = Tensor::from(arr2(&[[1, 2], [3, 4], [5, 6]]));
+        let paddings = Tensor::from(arr2(&[[2, 1], [1, 0]]));
+        let pad = Pad::<i32>::new();
+        let expected = pad
+            .eval(vec![input.clone().into(), paddings.clone().into()])
+            .unwrap();
+
+        let mut buffer = pad.new_buffer();
+        let mut chunks = vec![];
+        for row in input.as_i32s().unwrap().outer_iter() {
+            let chunk = Tensor::from(row.to_owned().insert_axis(Axis(0)));
+            let inputs = vec![
+                (Some(0), Some(chunk.into())),
+                (None, Some(paddings.clone().into())),
+            ];
+            chunks.extend(pad.step(inputs, &mut buffer).unwrap());
+        }
+        chunks.extend(pad.flush(&mut buffer).unwrap());
+
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|c| c[0].as_i32s().unwrap().view())
+            .collect();
+        let streamed = ::ndarray::stack(Axis(0), &chunks).unwrap();
+        assert_eq!(&Tensor::from(streamed), expected[0].as_tensor());
+    }
+
+    #[test]
+    fn pad_zero_rows() {
+        let input = Tensor::i32s(&[0, 2], &[]).unwrap();
+        let paddings = Tensor::from(arr2(&[[1, 1], [0, 1]]));
+        let found = Pad::<i32>::new()
+            .eval(vec![input.into(), paddings.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[2, 3], &[0; 6]).unwrap());
+    }
 }
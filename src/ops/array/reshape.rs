@@ -11,19 +11,24 @@ pub fn reshape(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
 
 impl<T: Datum> Reshape<T> {
     /// Computes a vector of dimensions from the `dims` input.
-    /// This is needed because `dims` might contain some -1 indices, in which
+    /// This is needed because `dims` might contain a -1 index, in which
     /// case we need to infer the value for that index.
-    fn true_dims(mut dims: Vec<i32>, input_length: usize) -> Vec<usize> {
-        if dims.contains(&-1) {
-            let prod: i32 = dims.iter().map(|a| *a).filter(|a| *a != -1i32).product();
-            for a in dims.iter_mut() {
-                if *a == -1 {
-                    *a = input_length as i32 / prod;
-                }
-            }
+    fn true_dims(dims: Vec<i32>, input_length: usize) -> Result<Vec<usize>> {
+        if dims.iter().filter(|&&a| a == -1).count() > 1 {
+            bail!("Reshape dims can contain at most one -1, found {:?}", dims);
         }
-
-        dims.into_iter().map(|a| a as usize).collect()
+        if dims.iter().any(|&a| a < -1) {
+            bail!("Invalid reshape dims {:?}", dims);
+        }
+        let prod: usize = dims.iter().filter(|&&a| a != -1).map(|&a| a as usize).product();
+        let missing = if prod == 0 { 0 } else { input_length / prod };
+        let dims: Vec<usize> = dims.into_iter()
+            .map(|a| if a == -1 { missing } else { a as usize })
+            .collect();
+        if dims.iter().product::<usize>() != input_length {
+            bail!("Can not reshape {} elements to {:?}", input_length, dims);
+        }
+        Ok(dims)
     }
 }
 
@@ -34,7 +39,7 @@ impl<T: Datum> Op for Reshape<T> {
 
         let input = T::tensor_into_array(input.into_tensor())?;
         let dims = <i32 as Datum>::tensor_into_array(dims.into_tensor())?;
-        let dims = Self::true_dims(dims.iter().cloned().collect(), input.len());
+        let dims = Self::true_dims(dims.iter().cloned().collect(), input.len())?;
 
         let output = input.into_shape(&*dims)?.into_dyn();
         Ok(vec![T::array_into_tensor(output).into()])
@@ -60,12 +65,138 @@ impl<T: Datum> InferenceRulesOp for Reshape<T> {
             .equals(&inputs[1].datatype, DataType::I32)
             .equals(&outputs[0].datatype, T::datatype())
             .equals(&inputs[1].rank, 1)
-            .given(&inputs[0].rank, move |solver, input_rank| {
-                solver.given(&inputs[1].value, move |solver, dims: Tensor| {
-                    let dims = <i32 as Datum>::tensor_into_array(dims).unwrap(); // checked
-                    let shape = Self::true_dims(dims.into_iter().cloned().collect(), input_rank);
-                    solver.equals(&outputs[0].shape, ShapeFact::from(shape));
-                });
+            .given(&inputs[1].value, move |solver, dims: Tensor| {
+                let dims = <i32 as Datum>::tensor_into_array(dims).unwrap(); // checked
+                let dims: Vec<i32> = dims.into_iter().cloned().collect();
+                if dims.iter().any(|&d| d < -1) {
+                    solver.fail(format!("Invalid reshape dims {:?}", dims));
+                } else if !dims.contains(&-1) {
+                    // Without a -1, the output shape does not depend on the
+                    // input one, which may be unknown or streamed.
+                    let shape: ShapeFact = dims.iter().map(|&d| d as usize).collect();
+                    solver.equals(&outputs[0].shape, shape);
+                }
+            })
+            .given(&inputs[0].shape, move |solver, input_shape: Vec<usize>| {
+                let count: usize = input_shape.iter().product();
+                solver
+                    .given(&inputs[1].value, move |solver, dims: Tensor| {
+                        let dims = <i32 as Datum>::tensor_into_array(dims).unwrap(); // checked
+                        let dims = dims.into_iter().cloned().collect();
+                        match Self::true_dims(dims, count) {
+                            Ok(shape) => {
+                                solver.equals(&outputs[0].shape, ShapeFact::from(shape));
+                            }
+                            Err(e) => {
+                                solver.fail(e.to_string());
+                            }
+                        }
+                    })
+                    .given(&outputs[0].shape, move |solver, shape: ShapeFact| {
+                        // The output has as many elements as the input, so a
+                        // single unknown output dimension follows from the
+                        // others.
+                        if shape.open || shape.dims.iter().any(|d| d.is_streamed()) {
+                            return;
+                        }
+                        let unknown: Vec<usize> = (0..shape.dims.len())
+                            .filter(|&ix| shape.dims[ix].concretize().is_none())
+                            .collect();
+                        let known: usize = shape.dims.iter().filter_map(|d| d.concretize()).product();
+                        if unknown.len() == 1 && known != 0 && count % known == 0 {
+                            solver.equals(&outputs[0].shape[unknown[0]], (count / known) as isize);
+                        }
+                    });
             });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    #[test]
+    fn reshape_infer_with_minus_one() {
+        let input = TensorFact {
+            datatype: typefact!(DataType::F32),
+            shape: shapefact![2, 3, 4],
+            value: valuefact!(_),
+        };
+        let dims = Tensor::i32s(&[2], &[-1, 4]).unwrap();
+        let (_, output) = Reshape::<f32>::new()
+            .infer(vec![input, dims.into()], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(output[0].shape, shapefact![6, 4]);
+
+        assert!(Reshape::<f32>::true_dims(vec![-1, 5], 24).is_err());
+        assert!(Reshape::<f32>::true_dims(vec![-1, -1], 24).is_err());
+    }
+
+    #[test]
+    fn reshape_infer_element_count() {
+        let input = TensorFact {
+            datatype: typefact!(DataType::F32),
+            shape: shapefact![2, 3, 4],
+            value: valuefact!(_),
+        };
+        let dims = TensorFact {
+            datatype: typefact!(DataType::I32),
+            shape: shapefact![3],
+            value: valuefact!(_),
+        };
+        let output = TensorFact {
+            datatype: typefact!(_),
+            shape: shapefact![2, _, 3],
+            value: valuefact!(_),
+        };
+        let (_, output) = Reshape::<f32>::new()
+            .infer(vec![input, dims], vec![output])
+            .unwrap();
+        assert_eq!(output[0].shape, shapefact![2, 4, 3]);
+    }
+
+    #[test]
+    fn reshape_infer_without_input_shape() {
+        let dims = Tensor::i32s(&[2], &[3, 2]).unwrap();
+        let (_, output) = Reshape::<f32>::new()
+            .infer(vec![TensorFact::new(), dims.into()], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(output[0].shape, shapefact![3, 2]);
+
+        let input = TensorFact {
+            datatype: typefact!(DataType::F32),
+            shape: shapefact![S, 2],
+            value: valuefact!(_),
+        };
+        let dims = Tensor::i32s(&[3], &[1, 2, 2]).unwrap();
+        let (_, output) = Reshape::<f32>::new()
+            .infer(vec![input, dims.into()], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(output[0].shape, shapefact![1, 2, 2]);
+    }
+
+    #[test]
+    fn reshape_infer_invalid_dims() {
+        let input = TensorFact {
+            datatype: typefact!(DataType::F32),
+            shape: shapefact![2, 3],
+            value: valuefact!(_),
+        };
+        for dims in &[[4, 2], [-1, 4], [-1, -1]] {
+            let dims = Tensor::i32s(&[2], dims).unwrap();
+            assert!(
+                Reshape::<f32>::new()
+                    .infer(vec![input.clone(), dims.into()], vec![TensorFact::new()])
+                    .is_err()
+            );
+        }
+        let dims = Tensor::i32s(&[2], &[-2, 3]).unwrap();
+        assert!(
+            Reshape::<f32>::new()
+                .infer(vec![TensorFact::new(), dims.into()], vec![TensorFact::new()])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use analyser::interface::*;
+use ndarray::prelude::*;
+use ndarray::Slice as Range;
+use ops::prelude::*;
+
+/// TensorFlow `Slice`, which extracts a block of `size` elements starting
+/// at `begin` along each axis.
+///
+/// A size of -1 takes all the remaining elements of the axis. The begin and
+/// size inputs are either both I32 or both I64, as given by the `Index`
+/// attribute.
+#[derive(Debug, Clone, new)]
+pub struct Slice<T: Datum> {
+    index: DataType,
+    _phantom: PhantomData<T>,
+}
+
+pub fn slice(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    let index = pb.get_attr_datatype("Index")?;
+    if index != DataType::I32 && index != DataType::I64 {
+        bail!("Slice expects I32 or I64 indices, got {:?}", index);
+    }
+    Ok(boxed_new!(Slice(dtype)(index)))
+}
+
+/// Reads begin or size indices, which are either I32 or I64.
+fn indices(tensor: &Tensor) -> Result<Vec<i64>> {
+    match *tensor {
+        Tensor::I32(ref a) => Ok(a.iter().map(|&x| x as i64).collect()),
+        Tensor::I64(ref a) => Ok(a.iter().cloned().collect()),
+        _ => bail!("Slice expects I32 or I64 indices, got {:?}", tensor.datatype()),
+    }
+}
+
+/// Computes the `(begin, end)` bounds of the slice along each axis.
+fn bounds(shape: &[usize], begin: &[i64], size: &[i64]) -> Result<Vec<(usize, usize)>> {
+    if begin.len() != shape.len() || size.len() != shape.len() {
+        bail!(
+            "Slice expects a begin and a size for each axis of {:?}, got {:?} and {:?}",
+            shape,
+            begin,
+            size
+        );
+    }
+    shape
+        .iter()
+        .zip(begin.iter().zip(size.iter()))
+        .map(|(&dim, (&b, &s))| {
+            let end = if s == -1 {
+                dim as i64
+            } else {
+                b.checked_add(s).ok_or_else(|| {
+                    format!("Slice of {:?} overflows: begin {}, size {}", shape, b, s)
+                })?
+            };
+            if b < 0 || s < -1 || end > dim as i64 {
+                bail!("Invalid slice of {:?}: begin {:?}, size {:?}", shape, begin, size);
+            }
+            Ok((b as usize, end as usize))
+        })
+        .collect()
+}
+
+impl<T: Datum> Op for Slice<T> {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (input, begin, size) = args_3!(inputs);
+        let input = T::tensor_to_view(&input)?;
+        if begin.datatype() != self.index || size.datatype() != self.index {
+            bail!(
+                "Slice expects {:?} begin and size, got {:?} and {:?}",
+                self.index,
+                begin.datatype(),
+                size.datatype()
+            );
+        }
+        let begin = indices(&begin)?;
+        let size = indices(&size)?;
+        let mut output = input.view();
+        for (axis, (b, e)) in bounds(input.shape(), &begin, &size)?.into_iter().enumerate() {
+            output.slice_axis_inplace(Axis(axis), Range::from(b..e));
+        }
+        Ok(vec![T::array_into_tensor(output.to_owned()).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T"     => Attr::DataType(T::datatype()),
+            "Index" => Attr::DataType(self.index),
+        }
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for Slice<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 3)
+            .equals(&outputs.len, 1)
+            .equals(&inputs[0].datatype, T::datatype())
+            .equals(&inputs[1].datatype, self.index)
+            .equals(&inputs[2].datatype, self.index)
+            .equals(&outputs[0].datatype, T::datatype())
+            .equals(&inputs[1].rank, 1)
+            .equals(&inputs[2].rank, 1)
+            .equals(&inputs[1].shape[0], &inputs[0].rank)
+            .equals(&inputs[2].shape[0], &inputs[0].rank)
+            .equals(&outputs[0].rank, &inputs[0].rank)
+            .given(&inputs[2].value, move |solver, size: Tensor| {
+                let size = match indices(&size) {
+                    Ok(size) => size,
+                    Err(e) => {
+                        solver.fail(e.to_string());
+                        return;
+                    }
+                };
+                for (axis, &s) in size.iter().enumerate() {
+                    if s >= 0 {
+                        solver.equals(&outputs[0].shape[axis], s as isize);
+                        continue;
+                    }
+                    // The slice runs to the end of the axis.
+                    solver.given(&inputs[1].value, move |solver, begin: Tensor| {
+                        let begin = match indices(&begin) {
+                            Ok(begin) => begin,
+                            Err(e) => {
+                                solver.fail(e.to_string());
+                                return;
+                            }
+                        };
+                        if let Some(&b) = begin.get(axis) {
+                            solver.given(&inputs[0].shape[axis], move |solver, dim: usize| {
+                                match (dim as i64).checked_sub(b) {
+                                    Some(rest) if b >= 0 && rest >= 0 => {
+                                        solver.equals(&outputs[0].shape[axis], rest as isize);
+                                    }
+                                    _ => {
+                                        solver.fail(format!(
+                                            "Invalid slice begin {} for an axis of {}",
+                                            b, dim
+                                        ));
+                                    }
+                                }
+                            });
+                        }
+                    });
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    #[test]
+    fn slice_to_end() {
+        let input = Tensor::f32s(&[3, 4], &(0..12).map(|x| x as f32).collect::<Vec<_>>()).unwrap();
+        let begin = Tensor::i32s(&[2], &[1, 1]).unwrap();
+        let size = Tensor::i32s(&[2], &[2, -1]).unwrap();
+        let found = Slice::<f32>::new(DataType::I32)
+            .eval(vec![input.into(), begin.clone().into(), size.clone().into()])
+            .unwrap();
+        let expected = Tensor::f32s(&[2, 3], &[5.0, 6.0, 7.0, 9.0, 10.0, 11.0]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![3, 4];
+        let (_, outputs) = Slice::<f32>::new(DataType::I32)
+            .infer(vec![input, begin.into(), size.into()], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(outputs[0].shape, shapefact![2, 3]);
+    }
+
+    #[test]
+    fn invalid_slices() {
+        for &(begin, size) in &[([2, 0], [2, 1]), ([-1, 0], [1, 1]), ([0, 0], [1, -2])] {
+            let input = Tensor::f32s(&[3, 4], &[0.0; 12]).unwrap();
+            let begin = Tensor::i32s(&[2], &begin).unwrap();
+            let size = Tensor::i32s(&[2], &size).unwrap();
+            assert!(
+                Slice::<f32>::new(DataType::I32)
+                    .eval(vec![input.into(), begin.into(), size.into()])
+                    .is_err()
+            );
+        }
+    }
+    #[test]
+    fn slice_with_i64_indices() {
+        let input = Tensor::f32s(&[3, 4], &(0..12).map(|x| x as f32).collect::<Vec<_>>()).unwrap();
+        let begin = Tensor::I64(arr1(&[1i64, 1]).into_dyn());
+        let size = Tensor::I64(arr1(&[2i64, -1]).into_dyn());
+        let found = Slice::<f32>::new(DataType::I64)
+            .eval(vec![input.clone().into(), begin.into(), size.into()])
+            .unwrap();
+        let expected = Tensor::f32s(&[2, 3], &[5.0, 6.0, 7.0, 9.0, 10.0, 11.0]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        // I32 indices don't match the Index attribute.
+        let begin = Tensor::i32s(&[2], &[1, 1]).unwrap();
+        let size = Tensor::i32s(&[2], &[2, -1]).unwrap();
+        assert!(
+            Slice::<f32>::new(DataType::I64)
+                .eval(vec![input.into(), begin.into(), size.into()])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn overflowing_slice() {
+        let input = Tensor::f32s(&[3], &[0.0; 3]).unwrap();
+        let begin = Tensor::I64(arr1(&[1i64]).into_dyn());
+        let size = Tensor::I64(arr1(&[i64::max_value()]).into_dyn());
+        let error = Slice::<f32>::new(DataType::I64)
+            .eval(vec![input.into(), begin.into(), size.into()])
+            .unwrap_err();
+        assert!(error.to_string().contains("overflows"), "{}", error);
+    }
+}
@@ -62,6 +62,11 @@ impl<T: Datum> Op for Squeeze<T> {
         attrs
     }
 
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
     /// Evaluates one step of the operation on the given input tensors.
     fn step(
         &self,
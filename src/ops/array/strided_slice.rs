@@ -11,6 +11,11 @@ pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     let begin_mask = pb.get_attr_opt_int("begin_mask")?.unwrap_or(0);
     let end_mask = pb.get_attr_opt_int("end_mask")?.unwrap_or(0);
     let shrink_axis_mask = pb.get_attr_opt_int("shrink_axis_mask")?.unwrap_or(0);
+    for &mask in &["ellipsis_mask", "new_axis_mask"] {
+        if pb.get_attr_opt_int::<i64>(mask)?.unwrap_or(0) != 0 {
+            bail!("StridedSlice does not support {}", mask);
+        }
+    }
     let datatype = pb.get_attr_datatype("T")?;
     Ok(boxed_new!(StridedSlice(datatype)(
         begin_mask,
@@ -27,6 +32,15 @@ pub struct StridedSlice<T: Datum> {
     _phantom: PhantomData<T>,
 }
 
+/// The streaming buffer of StridedSlice, which counts the frames seen
+/// along the streaming dimension.
+#[derive(Debug, Clone, Default)]
+pub struct StridedSliceBuffer {
+    frame: usize,
+}
+
+impl OpBuffer for StridedSliceBuffer {}
+
 #[derive(Debug)]
 struct Dim {
     begin: i32,
@@ -136,6 +150,9 @@ impl<T: Datum> Op for StridedSlice<T> {
         let begin = begin.as_i32s().ok_or("Begin expected as I32")?;
         let end = end.as_i32s().ok_or("End expected as I32")?;
         let strides = strides.as_i32s().ok_or("Strides expected as I32")?;
+        if strides.iter().any(|&s| s == 0) {
+            bail!("StridedSlice does not support a stride of 0, got {:?}", strides);
+        }
         let (bounds, mid_shape, end_shape) = self.prepare(
             input.shape(),
             &begin.view().into_dimensionality()?,
@@ -163,6 +180,106 @@ impl<T: Datum> Op for StridedSlice<T> {
             "shrink_axis_mask" => Attr::I64(self.shrink_axis_mask),
         }
     }
+
+    /// Returns a new streaming buffer for the operation.
+    fn new_buffer(&self) -> Box<OpBuffer> {
+        Box::new(StridedSliceBuffer::default())
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
+    /// Evaluates one step of the operation on the given input tensors.
+    ///
+    /// Along the streaming dimension, the frames are counted as they come,
+    /// and only the selected ones are sliced along the other dimensions and
+    /// emitted. As the length of the stream is unknown, this only works with
+    /// non-negative bounds and a positive stride on the streaming dimension.
+    fn step(
+        &self,
+        mut inputs: Vec<(Option<usize>, Option<TensorView>)>,
+        buffer: &mut Box<OpBuffer>,
+    ) -> Result<Option<Vec<TensorView>>> {
+        let (input, begin, end, strides) = args_4!(inputs);
+        let (begin, end, strides) = match (begin, end, strides) {
+            ((None, Some(begin)), (None, Some(end)), (None, Some(strides))) => {
+                (begin, end, strides)
+            }
+            _ => bail!("StridedSlice bounds should not be streamed."),
+        };
+        let (dim, chunk) = match input {
+            (_, None) => return Ok(None),
+            (None, Some(input)) => return Ok(Some(self.eval(vec![input, begin, end, strides])?)),
+            (Some(dim), Some(chunk)) => (dim, chunk),
+        };
+
+        let buffer = buffer
+            .downcast_mut::<StridedSliceBuffer>()
+            .ok_or("The buffer can't be downcasted to StridedSliceBuffer.")?;
+        let frame = buffer.frame as i32;
+        buffer.frame += 1;
+
+        let mut begin = begin.as_i32s().ok_or("Begin expected as I32")?.to_owned();
+        let mut end = end.as_i32s().ok_or("End expected as I32")?.to_owned();
+        let mut strides = strides.as_i32s().ok_or("Strides expected as I32")?.to_owned();
+        if dim >= begin.len() {
+            // The streaming dimension is not sliced.
+            return Ok(Some(self.eval(vec![
+                chunk,
+                Tensor::from(begin).into(),
+                Tensor::from(end).into(),
+                Tensor::from(strides).into(),
+            ])?));
+        }
+        if self.must_shrink(dim) {
+            bail!("StridedSlice can not shrink the streaming dimension ({}).", dim);
+        }
+        let stride = strides[dim];
+        let first = if (self.begin_mask >> dim) & 1 == 1 {
+            0
+        } else {
+            begin[dim]
+        };
+        let last = if (self.end_mask >> dim) & 1 == 1 {
+            None
+        } else {
+            Some(end[dim])
+        };
+        if stride <= 0 || first < 0 || last.map(|e| e < 0).unwrap_or(false) {
+            bail!(
+                "StridedSlice can only slice the streaming dimension ({}) with \
+                 non-negative bounds and a positive stride, found {}:{:?}:{}.",
+                dim,
+                first,
+                last,
+                stride
+            );
+        }
+        let selected = frame >= first
+            && last.map(|e| frame < e).unwrap_or(true)
+            && (frame - first) % stride == 0;
+        if !selected {
+            return Ok(None);
+        }
+
+        // Keep the whole chunk along the streaming dimension.
+        begin[dim] = 0;
+        end[dim] = 1;
+        strides[dim] = 1;
+        let op = StridedSlice::<T>::new(
+            self.begin_mask & !(1 << dim),
+            self.end_mask & !(1 << dim),
+            self.shrink_axis_mask,
+        );
+        Ok(Some(op.eval(vec![
+            chunk,
+            Tensor::from(begin).into(),
+            Tensor::from(end).into(),
+            Tensor::from(strides).into(),
+        ])?))
+    }
 }
 
 impl<T: Datum> InferenceRulesOp for StridedSlice<T> {
@@ -375,6 +492,54 @@ mod tests {
         )
     }
 
+    #[test]
+    fn step_every_other_frame() {
+        let input = Tensor::from(arr2(&[[0, 1], [2, 3], [4, 5], [6, 7], [8, 9], [10, 11]]));
+        let streamed = |op: &StridedSlice<i32>,
+                        begin: &[i32],
+                        end: &[i32],
+                        strides: &[i32]|
+         -> Result<Tensor> {
+            let mut buffer = op.new_buffer();
+            let mut chunks = vec![];
+            for row in input.as_i32s().unwrap().outer_iter() {
+                let chunk = Tensor::from(row.to_owned().insert_axis(Axis(0)));
+                let inputs = vec![
+                    (Some(0), Some(chunk.into())),
+                    (None, Some(Tensor::i32s(&[2], begin).unwrap().into())),
+                    (None, Some(Tensor::i32s(&[2], end).unwrap().into())),
+                    (None, Some(Tensor::i32s(&[2], strides).unwrap().into())),
+                ];
+                if let Some(mut chunk) = op.step(inputs, &mut buffer)? {
+                    chunks.push(chunk.remove(0).into_tensor().take_i32s().unwrap());
+                }
+            }
+            let views: Vec<_> = chunks.iter().map(|c| c.view()).collect();
+            Ok(Tensor::from(stack(Axis(0), &views).unwrap()))
+        };
+
+        let mut op = StridedSlice::default();
+        op.end_mask = 1;
+        let found = streamed(&op, &[1, 1], &[0, 2], &[2, 1]).unwrap();
+        assert_eq!(found, Tensor::from(arr2(&[[3], [7], [11]])));
+        let expected = eval(
+            op.clone(),
+            input.clone(),
+            arr1(&[1, 1]),
+            arr1(&[0, 2]),
+            arr1(&[2, 1]),
+        );
+        assert_eq!(found, expected);
+
+        let op = StridedSlice::default();
+        let found = streamed(&op, &[0, 0], &[3, 2], &[2, 1]).unwrap();
+        assert_eq!(found, Tensor::from(arr2(&[[0, 1], [4, 5]])));
+
+        // Negative bounds would need to know where the stream ends.
+        assert!(streamed(&op, &[-2, 0], &[6, 2], &[1, 1]).is_err());
+        assert!(streamed(&op, &[4, 0], &[0, 2], &[-1, 1]).is_err());
+    }
+
     #[test]
     fn eval_begin_mask_1() {
         let mut op = StridedSlice::default();
@@ -385,6 +550,24 @@ mod tests {
         )
     }
 
+    #[test]
+    fn unsupported_strides_and_masks() {
+        let op = StridedSlice::<i32>::default();
+        let result = op.eval(vec![
+            Tensor::from(arr1(&[1, 2, 3])).into(),
+            Tensor::from(arr1(&[0])).into(),
+            Tensor::from(arr1(&[3])).into(),
+            Tensor::from(arr1(&[0])).into(),
+        ]);
+        assert!(result.is_err());
+
+        let pb = ::tfpb::node()
+            .op("StridedSlice")
+            .attr("T", ::tfpb::types::DataType::DT_INT32)
+            .attr("new_axis_mask", 1);
+        assert!(build(&pb).is_err());
+    }
+
     #[test]
     fn eval_shrink_1() {
         let mut op = StridedSlice::default();
@@ -0,0 +1,179 @@
+use analyser::interface::*;
+use ops::prelude::*;
+
+/// TensorFlow `Transpose`, which permutes the axes of a tensor.
+///
+/// The axis `i` of the output is the axis `perm[i]` of the input. Without a
+/// permutation input, the axes are reversed.
+#[derive(Debug, Clone, new)]
+pub struct Transpose<T: Datum>(PhantomData<T>);
+
+pub fn transpose(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    Ok(boxed_new!(Transpose(dtype)()))
+}
+
+/// Checks that `perm` is a permutation of `0..rank`, or returns the reverse
+/// permutation if there is none.
+fn permutation(perm: Option<&[i32]>, rank: usize) -> Result<Vec<usize>> {
+    let perm = match perm {
+        Some(perm) => perm,
+        None => return Ok((0..rank).rev().collect()),
+    };
+    let mut seen = vec![false; rank];
+    for &axis in perm {
+        if axis < 0 || axis as usize >= rank || seen[axis as usize] {
+            bail!("Invalid permutation {:?} for a tensor of rank {}", perm, rank);
+        }
+        seen[axis as usize] = true;
+    }
+    if perm.len() != rank {
+        bail!("Invalid permutation {:?} for a tensor of rank {}", perm, rank);
+    }
+    Ok(perm.iter().map(|&axis| axis as usize).collect())
+}
+
+impl<T: Datum> Op for Transpose<T> {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let mut inputs = args_n!(inputs, 1, 2);
+        let perm = if inputs.len() == 2 {
+            let perm = inputs.pop().unwrap();
+            Some(i32::tensor_to_view(&perm)?.iter().cloned().collect::<Vec<i32>>())
+        } else {
+            None
+        };
+        let data = T::tensor_into_array(inputs.pop().unwrap().into_tensor())?;
+        let perm = permutation(perm.as_ref().map(|p| &**p), data.ndim())?;
+        // The consumers expect standard layout, e.g. to reshape the result.
+        let permuted = ::ops::standard_layout(&data.permuted_axes(perm));
+        Ok(vec![T::array_into_tensor(permuted).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T" => Attr::DataType(T::datatype()),
+        }
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for Transpose<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&outputs.len, 1)
+            .equals(&inputs[0].datatype, T::datatype())
+            .equals(&outputs[0].datatype, T::datatype())
+            .equals(&outputs[0].rank, &inputs[0].rank)
+            .given(&inputs.len, move |solver, len: usize| {
+                if len == 2 {
+                    solver
+                        .equals(&inputs[1].datatype, DataType::I32)
+                        .equals(&inputs[1].rank, 1)
+                        .equals(&inputs[1].shape[0], &inputs[0].rank)
+                        .given(&inputs[1].value, move |solver, perm: Tensor| {
+                            let perm = i32::tensor_into_array(perm).unwrap(); // checked
+                            let perm: Vec<i32> = perm.iter().cloned().collect();
+                            if let Ok(perm) = permutation(Some(&perm), perm.len()) {
+                                for (ix, &axis) in perm.iter().enumerate() {
+                                    solver.equals(&outputs[0].shape[ix], &inputs[0].shape[axis]);
+                                }
+                            }
+                        });
+                } else if len == 1 {
+                    solver.given(&inputs[0].rank, move |solver, rank: usize| {
+                        for ix in 0..rank {
+                            solver.equals(&outputs[0].shape[ix], &inputs[0].shape[rank - 1 - ix]);
+                        }
+                    });
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    #[test]
+    fn nhwc_to_nchw() {
+        let data = Tensor::f32s(&[1, 2, 3, 2], &(0..12).map(|x| x as f32).collect::<Vec<_>>())
+            .unwrap();
+        let perm = Tensor::i32s(&[4], &[0, 3, 1, 2]).unwrap();
+        let found = Transpose::<f32>::new()
+            .eval(vec![data.clone().into(), perm.clone().into()])
+            .unwrap();
+        let expected = Tensor::f32s(
+            &[1, 2, 2, 3],
+            &[0., 2., 4., 6., 8., 10., 1., 3., 5., 7., 9., 11.],
+        ).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![1, 2, 3, 2];
+        let (_, outputs) = Transpose::<f32>::new()
+            .infer(vec![input, perm.into()], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(outputs[0].shape, shapefact![1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn default_permutation() {
+        let data = Tensor::f32s(&[2, 3], &[1., 2., 3., 4., 5., 6.]).unwrap();
+        let found = Transpose::<f32>::new().eval(vec![data.into()]).unwrap();
+        let expected = Tensor::f32s(&[3, 2], &[1., 4., 2., 5., 3., 6.]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![2, 3, 4];
+        let (_, outputs) = Transpose::<f32>::new()
+            .infer(vec![input], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(outputs[0].shape, shapefact![4, 3, 2]);
+    }
+
+    #[test]
+    fn transpose_then_reshape() {
+        use ops::array::reshape::Reshape;
+        let data = Tensor::f32s(&[2, 3], &[1., 2., 3., 4., 5., 6.]).unwrap();
+        let transposed = Transpose::<f32>::new().eval(vec![data.into()]).unwrap();
+        let dims = Tensor::i32s(&[1], &[6]).unwrap();
+        let found = Reshape::<f32>::new()
+            .eval(vec![transposed[0].clone(), dims.into()])
+            .unwrap();
+        let expected = Tensor::f32s(&[6], &[1., 4., 2., 5., 3., 6.]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let data = Tensor::f32s(&[1, 2, 3, 2], &[0.0; 12]).unwrap();
+        let perm = Tensor::i32s(&[4], &[0, 3, 1, 2]).unwrap();
+        let transposed = Transpose::<f32>::new()
+            .eval(vec![data.into(), perm.into()])
+            .unwrap();
+        let dims = Tensor::i32s(&[2], &[2, 6]).unwrap();
+        assert!(
+            Reshape::<f32>::new()
+                .eval(vec![transposed[0].clone(), dims.into()])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn invalid_permutations() {
+        for perm in &[&[0, 0][..], &[0, 2], &[1], &[-1, 0], &[0, 1, 2]] {
+            let data = Tensor::f32s(&[2, 3], &[0.0; 6]).unwrap();
+            let perm = Tensor::i32s(&[perm.len()], perm).unwrap();
+            assert!(
+                Transpose::<f32>::new()
+                    .eval(vec![data.into(), perm.into()])
+                    .is_err()
+            );
+        }
+    }
+}
@@ -8,42 +8,100 @@ pub fn register_all_ops(reg: &mut OpRegister) {
     reg.insert("Cast", Cast::build);
 }
 
+/// Converts a tensor to the `DstT` datatype, with the semantics of `as` for
+/// the elements.
 #[derive(Debug, Clone)]
-pub struct Cast;
+pub struct Cast {
+    src: Option<DataType>,
+    dst: DataType,
+}
 
 impl Cast {
-    pub fn build(_pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
-        Ok(Box::new(Cast {}))
+    pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        Ok(Box::new(Cast {
+            src: pb.get_attr_opt_datatype("SrcT")?,
+            dst: pb.get_attr_first_datatype(&["DstT", "dtype"])?,
+        }))
     }
 }
 
 impl ::ops::Op for Cast {
     /// Evaluates the operation given the input tensors.
-    fn eval(&self, mut _inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
-        panic!(
-            "nope, fixme. parse two args in build to get src and dst types, then generalize (macro ?)"
-        );
-        /*
-        let input = inputs.remove(0).take_f32s().ok_or(
-            "Expect input #0 to be f32",
-        )?;
-        Ok(vec![Tensor::F32(input.mapv(|i| i as _))])
-        */
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let input = args_1!(inputs);
+        if let Some(src) = self.src {
+            if input.datatype() != src {
+                bail!("Cast expects a {:?} input, got {:?}", src, input.datatype());
+            }
+        }
+        Ok(vec![input.cast_to(self.dst)?.into()])
     }
 
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
-        unimplemented!("Cast op")
+        let mut attributes = hashmap!{
+            "DstT" => Attr::DataType(self.dst),
+        };
+        if let Some(src) = self.src {
+            attributes.insert("SrcT", Attr::DataType(src));
+        }
+        attributes
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
+    /// Evaluates one step of the operation on the given input tensors.
+    fn step(
+        &self,
+        mut inputs: Vec<(Option<usize>, Option<TensorView>)>,
+        _: &mut Box<OpBuffer>,
+    ) -> Result<Option<Vec<TensorView>>> {
+        let input = args_1!(inputs);
+        match input.1 {
+            None => Ok(None),
+            Some(tv) => Ok(Some(self.eval(vec![tv])?)),
+        }
     }
 }
 
 impl InferenceRulesOp for Cast {
     fn rules<'r, 'p: 'r, 's: 'r>(
         &'s self,
-        _: &mut Solver<'r>,
-        _: &'p TensorsProxy,
-        _: &'p TensorsProxy,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
     ) {
-        unimplemented!("Cast op")
+        solver
+            .equals(&inputs.len, 1)
+            .equals(&outputs.len, 1)
+            .equals(&outputs[0].datatype, self.dst)
+            .equals(&inputs[0].shape, &outputs[0].shape);
+        if let Some(src) = self.src {
+            solver.equals(&inputs[0].datatype, src);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfpb::types::DataType::{DT_FLOAT, DT_INT32};
+
+    #[test]
+    fn cast_eval() {
+        let pb = ::tfpb::node()
+            .op("Cast")
+            .attr("SrcT", DT_FLOAT)
+            .attr("DstT", DT_INT32);
+        let op = Cast::build(&pb).unwrap();
+        let input = Tensor::f32s(&[3], &[1.5, -2.5, 3.0]).unwrap();
+        let found = op.eval(vec![input.into()]).unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[3], &[1, -2, 3]).unwrap());
+        let input = Tensor::i32s(&[1], &[1]).unwrap();
+        assert!(op.eval(vec![input.into()]).is_err());
+        assert!(Cast::build(&::tfpb::node().op("Cast").attr("SrcT", DT_FLOAT)).is_err());
     }
 }
@@ -16,8 +16,20 @@ pub struct Const {
 }
 
 impl Const {
+    /// Creates a Const op sharing an already allocated tensor.
+    ///
+    /// The tensor is never copied: every evaluation hands out a
+    /// `TensorView::Shared` pointing at the same allocation, so large weight
+    /// blobs can be owned once by the caller and reused by the runtime.
+    pub fn for_shared(value: Arc<Tensor>) -> Const {
+        Const {
+            dtype: value.datatype(),
+            value,
+        }
+    }
+
     pub fn build(node: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
-        let dtype = node.get_attr_datatype("dtype")?;
+        let dtype = node.get_attr_first_datatype(&["dtype", "T"])?;
         let mat = node.get_attr_tensor("value")?;
 
         if mat.datatype() != dtype {
@@ -49,6 +61,11 @@ impl Op for Const {
         }
     }
 
+    /// Estimates the number of floating point operations.
+    fn flops(&self, _inputs: &[TensorFact], _outputs: &[TensorFact]) -> Option<u64> {
+        Some(0)
+    }
+
     fn const_value(&self) -> Option<Tensor> {
         Some((*self.value).clone())
     }
@@ -65,3 +82,56 @@ impl ::ops::InferenceRulesOp for Const {
         solver.equals(&inputs.len, 0).equals(&outputs.len, 1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn const_eval_aliases_shared_tensor() {
+        let weights = Arc::new(Tensor::f32s(&[2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap());
+        let konst = Const::for_shared(weights.clone());
+        let output = konst.eval(vec![]).unwrap().remove(0);
+
+        match output {
+            TensorView::Shared(ref shared) => assert!(Arc::ptr_eq(shared, &weights)),
+            TensorView::Owned(_) => panic!("Const should not copy its value."),
+        }
+        assert_eq!(
+            output.as_f32s().unwrap().as_ptr(),
+            weights.as_f32s().unwrap().as_ptr()
+        );
+    }
+
+    #[test]
+    fn const_from_tensor_content() {
+        // Larger tensors are stored as raw bytes in `tensor_content`.
+        let model = ::Model::for_path("tests/models/conv2d.pb").unwrap();
+        let value = model.get_node("Const").unwrap().op().const_value().unwrap();
+        assert_eq!(value.shape(), &[2, 2, 1, 1]);
+        assert_eq!(value.datatype(), DataType::F32);
+    }
+
+    #[test]
+    fn const_from_typed_values() {
+        // Small tensors are stored in the typed fields, e.g. `float_val`.
+        let model = ::Model::for_path("tests/models/plus3.pb").unwrap();
+        let value = model.get_node("three").unwrap().op().const_value().unwrap();
+        assert_eq!(value, Tensor::f32s(&[1], &[3.0]).unwrap());
+
+        let model = ::Model::for_path("tests/models/concat-0.pb").unwrap();
+        let value = model.get_node("concat/axis").unwrap().op().const_value().unwrap();
+        assert_eq!(value, Tensor::i32s(&[], &[0]).unwrap());
+    }
+
+    #[test]
+    fn const_without_tensor() {
+        use tfpb::types::DataType::DT_FLOAT;
+        let node = ::tfpb::node().op("Const").name("c").attr("dtype", DT_FLOAT);
+        let err = Const::build(&node).unwrap_err();
+        assert!(format!("{}", err).contains("expected tensor attribute 'value'"));
+        let node = node.attr("value", DT_FLOAT);
+        let err = Const::build(&node).unwrap_err();
+        assert!(format!("{}", err).contains("expected tensor attribute 'value'"));
+    }
+}
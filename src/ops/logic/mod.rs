@@ -0,0 +1,7 @@
+use super::OpRegister;
+
+mod where_op;
+
+pub fn register_all_ops(reg: &mut OpRegister) {
+    reg.insert("Where", where_op::Where::build);
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use analyser::interface::*;
+use ndarray::prelude::*;
+use num_traits::Zero;
+use ops::prelude::*;
+use Result;
+
+/// Returns the coordinates of the true (or nonzero) elements of its input,
+/// in row-major order, as a `[num_true, rank]` i64 tensor.
+#[derive(Debug, Clone)]
+pub struct Where;
+
+impl Where {
+    pub fn build(_pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        Ok(Box::new(Where))
+    }
+
+    fn coordinates<T, F>(input: &ArrayD<T>, is_true: F) -> Result<Tensor>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let rank = input.ndim();
+        let mut data = vec![];
+        for (coords, x) in input.indexed_iter() {
+            if is_true(x) {
+                data.extend(coords.slice().iter().map(|&c| c as i64));
+            }
+        }
+        let count = if rank == 0 {
+            input.iter().filter(|x| is_true(x)).count()
+        } else {
+            data.len() / rank
+        };
+        Ok(Array::from_shape_vec((count, rank), data)?.into())
+    }
+}
+
+impl Op for Where {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let input = args_1!(inputs);
+        let output = match input.as_tensor() {
+            &Tensor::Bool(ref a) => Self::coordinates(a, |&x| x)?,
+            &Tensor::F32(ref a) => Self::coordinates(a, |x| !x.is_zero())?,
+            &Tensor::F64(ref a) => Self::coordinates(a, |x| !x.is_zero())?,
+            &Tensor::I32(ref a) => Self::coordinates(a, |x| !x.is_zero())?,
+            &Tensor::I64(ref a) => Self::coordinates(a, |x| !x.is_zero())?,
+            &Tensor::I8(ref a) => Self::coordinates(a, |x| !x.is_zero())?,
+            &Tensor::U8(ref a) => Self::coordinates(a, |x| !x.is_zero())?,
+            t => bail!("Where does not support {:?} tensors", t.datatype()),
+        };
+        Ok(vec![output.into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{}
+    }
+}
+
+impl InferenceRulesOp for Where {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 1)
+            .equals(&outputs.len, 1)
+            .equals(&outputs[0].datatype, DataType::I64)
+            .equals(&outputs[0].rank, 2)
+            .equals(&outputs[0].shape[1], &inputs[0].rank);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+
+    #[test]
+    fn where_bool_mask() {
+        let mask = Tensor::from(arr2(&[[true, false, true], [false, false, true]]));
+        let result = Where.eval(vec![mask.into()]).unwrap();
+        assert_eq!(
+            result[0].as_tensor(),
+            &Tensor::from(arr2(&[[0i64, 0], [0, 2], [1, 2]]))
+        );
+    }
+
+    #[test]
+    fn where_numeric() {
+        let input = Tensor::from(arr1(&[0.0f32, 3.0, 0.0, -1.0]));
+        let result = Where.eval(vec![input.into()]).unwrap();
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr2(&[[1i64], [3]])));
+    }
+
+    #[test]
+    fn where_string() {
+        let input = Tensor::String(arr1(&[b"h".to_vec(), b"i".to_vec()]).into_dyn());
+        let err = Where.eval(vec![input.into()]).unwrap_err();
+        assert_eq!(format!("{}", err), "Where does not support String tensors");
+    }
+}
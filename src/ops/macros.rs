@@ -1,5 +1,5 @@
 macro_rules! element_map_float {
-    ($Name:ident, $name:ident, $expr:expr) => {
+    ($Name:ident, $name:ident, $expr:expr $(, $key:expr => $value:expr)*) => {
         pub fn $name(pb: &$crate::tfpb::node_def::NodeDef) -> $crate::Result<Box<Op>> {
             let datatype = pb.get_attr_datatype("T")?;
             let it = match datatype {
@@ -18,7 +18,7 @@ macro_rules! element_map_float {
         impl<T: $crate::tensor::Datum + ::num_traits::Float> ::ops::Op for $Name<T> {
             /// Returns the attributes of the operation and their values.
             fn get_attributes(&self) -> ::std::collections::HashMap<&'static str, ::ops::Attr> {
-                hashmap!{ "T" => $crate::ops::Attr::DataType(T::datatype()) }
+                hashmap!{ "T" => $crate::ops::Attr::DataType(T::datatype()) $(, $key => $value)* }
             }
 
             /// Evaluates the operation given the input tensors.
@@ -28,10 +28,43 @@ macro_rules! element_map_float {
             ) -> $crate::Result<Vec<$crate::ops::TensorView>> {
                 let a = args_1!(inputs);
                 let mut a = T::tensor_into_array(a.into_tensor())?;
-                a.mapv_inplace($expr);
+                $crate::ops::mapv_inplace(&mut a, $expr);
                 Ok(vec![T::array_into_tensor(a).into()])
             }
 
+            /// Evaluates the operation, reusing the buffer of an owned input.
+            fn eval_in_place(
+                &self,
+                inputs: &mut Vec<$crate::ops::TensorView>,
+            ) -> Option<$crate::Result<Vec<$crate::ops::TensorView>>> {
+                let owned = inputs.len() == 1 && match inputs[0] {
+                    $crate::ops::TensorView::Owned(ref t) => t.datatype() == T::datatype(),
+                    _ => false,
+                };
+                if !owned {
+                    return None;
+                }
+                let a = inputs.pop().unwrap().into_tensor();
+                Some(T::tensor_into_array(a).map(|mut a| {
+                    $crate::ops::mapv_inplace(&mut a, $expr);
+                    vec![T::array_into_tensor(a).into()]
+                }))
+            }
+
+            /// Estimates the number of floating point operations.
+            fn flops(
+                &self,
+                _inputs: &[$crate::analyser::TensorFact],
+                outputs: &[$crate::analyser::TensorFact],
+            ) -> Option<u64> {
+                $crate::ops::element_count(outputs.get(0)?)
+            }
+
+            /// Returns whether the operation implements `step`.
+            fn is_streamable(&self) -> bool {
+                true
+            }
+
             /// Evaluates one step of the operation on the given input tensors.
             fn step(
                 &self,
@@ -99,10 +132,43 @@ macro_rules! element_map_signed {
             ) -> $crate::Result<Vec<$crate::ops::TensorView>> {
                 let a = args_1!(inputs);
                 let mut a = T::tensor_into_array(a.into_tensor())?;
-                a.mapv_inplace($expr);
+                $crate::ops::mapv_inplace(&mut a, $expr);
                 Ok(vec![T::array_into_tensor(a).into()])
             }
 
+            /// Evaluates the operation, reusing the buffer of an owned input.
+            fn eval_in_place(
+                &self,
+                inputs: &mut Vec<$crate::ops::TensorView>,
+            ) -> Option<$crate::Result<Vec<$crate::ops::TensorView>>> {
+                let owned = inputs.len() == 1 && match inputs[0] {
+                    $crate::ops::TensorView::Owned(ref t) => t.datatype() == T::datatype(),
+                    _ => false,
+                };
+                if !owned {
+                    return None;
+                }
+                let a = inputs.pop().unwrap().into_tensor();
+                Some(T::tensor_into_array(a).map(|mut a| {
+                    $crate::ops::mapv_inplace(&mut a, $expr);
+                    vec![T::array_into_tensor(a).into()]
+                }))
+            }
+
+            /// Estimates the number of floating point operations.
+            fn flops(
+                &self,
+                _inputs: &[$crate::analyser::TensorFact],
+                outputs: &[$crate::analyser::TensorFact],
+            ) -> Option<u64> {
+                $crate::ops::element_count(outputs.get(0)?)
+            }
+
+            /// Returns whether the operation implements `step`.
+            fn is_streamable(&self) -> bool {
+                true
+            }
+
             /// Evaluates one step of the operation on the given input tensors.
             fn step(
                 &self,
@@ -140,19 +206,52 @@ macro_rules! element_map_signed {
 }
 
 macro_rules! element_bin {
-    ($Name:ident, $name:ident, $expr:expr) => {
+    ($Name:ident, $name:ident, $expr:expr, $scalar:expr) => {
+        /// With `coerce` set, inputs of a narrower datatype than `T` are
+        /// promoted to `T` instead of being rejected. Graphs don't say so:
+        /// see `Model::coerce_inputs`.
         #[derive(Debug, Clone, new)]
-        pub struct $Name<T: ::tensor::Datum>(::std::marker::PhantomData<T>);
+        pub struct $Name<T: ::tensor::Datum> {
+            coerce: bool,
+            _phantom: ::std::marker::PhantomData<T>,
+        }
 
         pub fn $name(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
             let dtype = pb.get_attr_datatype("T")?;
-            Ok(boxed_new!($Name(dtype)()))
+            Ok(boxed_new!($Name(dtype)(false)))
+        }
+
+        impl<T: ::tensor::Datum> $Name<T> {
+            /// Converts an input to `T`, promoting it first in coercion mode.
+            fn input(&self, input: $crate::ops::TensorView) -> Result<$crate::ops::TensorView> {
+                let dt = input.as_tensor().datatype();
+                if !self.coerce || dt == T::datatype() {
+                    return Ok(input);
+                }
+                if dt.promote(T::datatype()) != Some(T::datatype()) {
+                    bail!(
+                        "{} can not coerce a {:?} input to {:?}",
+                        stringify!($Name),
+                        dt,
+                        T::datatype()
+                    );
+                }
+                Ok(input.as_tensor().cast_to(T::datatype())?.into())
+            }
         }
 
         impl<T: ::tensor::Datum> Op for $Name<T> {
             /// Returns the attributes of the operation and their values.
             fn get_attributes(&self) -> ::std::collections::HashMap<&'static str, ::ops::Attr> {
-                hashmap!{ "T" => ::ops::Attr::DataType(T::datatype()) }
+                hashmap!{
+                    "T"      => ::ops::Attr::DataType(T::datatype()),
+                    "coerce" => ::ops::Attr::Bool(self.coerce),
+                }
+            }
+
+            /// Returns a copy of the operation which promotes its inputs.
+            fn coercing(&self) -> Option<Box<Op>> {
+                Some(Box::new($Name::<T>::new(true)))
             }
 
             /// Evaluates the operation given the input tensors.
@@ -161,8 +260,29 @@ macro_rules! element_bin {
                 mut inputs: Vec<$crate::ops::TensorView>,
             ) -> Result<Vec<$crate::ops::TensorView>> {
                 let (a, b) = args_2!(inputs);
-                let a = T::tensor_into_array(a.into_tensor())?;
+                let (a, b) = (self.input(a)?, self.input(b)?);
+                let mut a = T::tensor_into_array(a.into_tensor())?;
                 let b = T::tensor_to_view(&*b)?;
+                match $crate::ops::scalar_operand(a.shape(), b.shape()) {
+                    Some(1) => {
+                        let b = *b.iter().next().unwrap();
+                        $crate::ops::mapv_inplace(&mut a, |a| $scalar(a, b));
+                        return Ok(vec![T::array_into_tensor(a).into()]);
+                    }
+                    Some(_) => {
+                        let a = *a.iter().next().unwrap();
+                        return Ok(vec![T::array_into_tensor(b.mapv(|b| $scalar(a, b))).into()]);
+                    }
+                    None => (),
+                }
+                let shape = $crate::ops::broadcast_shape(a.shape(), b.shape())?;
+                // Only the right operand gets broadcast by the operators.
+                let a = if a.shape() == &*shape {
+                    a
+                } else {
+                    a.broadcast(&*shape).unwrap().to_owned()
+                };
+                let b = b.broadcast(&*shape).unwrap();
                 Ok(vec![T::array_into_tensor($expr(a, b)).into()])
             }
 
@@ -171,6 +291,20 @@ macro_rules! element_bin {
                 Box::new($crate::ops::QueuesBuffer::new(2))
             }
 
+            /// Estimates the number of floating point operations.
+            fn flops(
+                &self,
+                _inputs: &[$crate::analyser::TensorFact],
+                outputs: &[$crate::analyser::TensorFact],
+            ) -> Option<u64> {
+                $crate::ops::element_count(outputs.get(0)?)
+            }
+
+            /// Returns whether the operation implements `step`.
+            fn is_streamable(&self) -> bool {
+                true
+            }
+
             /// Evaluates one step of the operation on the given input tensors.
             fn step(
                 &self,
@@ -192,6 +326,23 @@ macro_rules! element_bin {
                     Ok(Some(self.eval(vec![a, b])?))
                 }
             }
+
+            /// Checks that both streams ended with no chunk left unpaired.
+            fn flush(
+                &self,
+                buffer: &mut Box<$crate::ops::OpBuffer>,
+            ) -> Result<Vec<Vec<$crate::ops::TensorView>>> {
+                let buffer = buffer.downcast_mut::<$crate::ops::QueuesBuffer>()
+                    .ok_or("The buffer can't be downcasted to QueuesBuffer.")?;
+                if !buffer[0].is_empty() || !buffer[1].is_empty() {
+                    bail!(
+                        "Streams ended with {} and {} unpaired chunks",
+                        buffer[0].len(),
+                        buffer[1].len()
+                    );
+                }
+                Ok(vec![])
+            }
         }
 
         impl<T: ::tensor::Datum> ::ops::InferenceRulesOp for $Name<T> {
@@ -208,7 +359,11 @@ macro_rules! element_bin {
 
                 solver
                     .equals(&outputs.len, 1)
-                    .equals_all(wrap![&a.datatype, &b.datatype, &c.datatype, &T::datatype()])
+                    .equals(&c.datatype, T::datatype());
+                if !self.coerce {
+                    solver.equals_all(wrap![&a.datatype, &b.datatype, &c.datatype]);
+                }
+                solver
                     .given(&a.shape, move |solver, a_shape| {
                         solver.given(&b.shape, move |solver, b_shape| {
                             if let Ok(Some(c_shape)) = ::analyser::helpers::infer_shape_broadcasting(vec!(&a_shape, &b_shape)) {
@@ -270,6 +425,43 @@ macro_rules! args_4 {
     }};
 }
 
+macro_rules! args_5 {
+    ($inputs:expr) => {{
+        if $inputs.len() != 5 {
+            Err("Expected 5 args")?
+        }
+        $inputs.reverse();
+        (
+            $inputs.pop().unwrap(),
+            $inputs.pop().unwrap(),
+            $inputs.pop().unwrap(),
+            $inputs.pop().unwrap(),
+            $inputs.pop().unwrap(),
+        )
+    }};
+}
+
+/// Checks that there are between `min` and `max` inputs, for the operators
+/// taking a variable number of them, and returns the inputs.
+macro_rules! args_n {
+    ($inputs:expr, $min:expr, $max:expr) => {{
+        let (min, max) = ($min, $max);
+        if $inputs.len() < min || $inputs.len() > max {
+            if min == max {
+                Err(format!("Expected {} args, got {}", min, $inputs.len()))?
+            } else {
+                Err(format!(
+                    "Expected between {} and {} args, got {}",
+                    min,
+                    max,
+                    $inputs.len()
+                ))?
+            }
+        }
+        $inputs
+    }};
+}
+
 macro_rules! boxed_new {
     ($op:tt($dtype:expr)($($arg:expr),*)) => { {
         use $crate::DataType;
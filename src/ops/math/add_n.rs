@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
 
+use ndarray::ArrayD;
+use num_traits::NumCast;
+
 use analyser::interface::*;
 use ops::prelude::*;
 use tensor::Datum;
@@ -9,26 +12,47 @@ use Result;
 #[derive(Debug, Clone, Default, new)]
 pub struct AddN<T: Datum> {
     n: usize,
+    accumulate_in_f64: bool,
     _phantom: PhantomData<T>,
 }
 
+/// Builds an `AddN`, accumulating in f64 if the node has a true
+/// `accumulate_in_f64` attribute.
+///
+/// The inputs are always added from left to right, so float sums are
+/// rounded the same way whichever inputs are shared.
 pub fn add_n(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     let dtype = pb.get_attr_datatype("T")?;
     let n = pb.get_attr_int("N")?;
-    Ok(boxed_new!(AddN(dtype)(n)))
+    let in_f64 = pb.get_attr_opt_bool("accumulate_in_f64")?.unwrap_or(false);
+    Ok(boxed_new!(AddN(dtype)(n, in_f64)))
 }
 
 impl<T> Op for AddN<T>
 where
-    T: Datum,
+    T: Datum + NumCast,
 {
     /// Evaluates the operation given the input tensors.
     fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
         if inputs.len() != self.n || self.n == 0 {
             bail!("Expected {} inputs", self.n);
         }
-        let mut result = T::tensor_into_array(inputs.pop().unwrap().into_tensor())?; // checked, non empty
-        for input in &inputs[0..] {
+        if self.accumulate_in_f64 {
+            let shape = inputs[0].as_tensor().shape().to_vec();
+            let mut acc = ArrayD::<f64>::zeros(shape);
+            for input in &inputs {
+                let input = T::tensor_to_view(input.as_tensor())?;
+                if input.shape() != acc.shape() {
+                    bail!("Expected inputs of shape {:?}, found {:?}", acc.shape(), input.shape());
+                }
+                acc.zip_mut_with(&input, |a, &x| *a += x.to_f64().unwrap());
+            }
+            return Ok(vec![T::array_into_tensor(super::from_f64(acc)?).into()]);
+        }
+        // Accumulate into the first input, which reuses its buffer if it is
+        // owned, and copies it otherwise.
+        let mut result = T::tensor_into_array(inputs.remove(0).into_tensor())?; // checked, non empty
+        for input in &inputs {
             result += &T::tensor_to_view(input.as_tensor())?;
         }
         Ok(vec![T::array_into_tensor(result).into()])
@@ -37,8 +61,9 @@ where
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
         hashmap!{
-            "T"    => Attr::DataType(T::datatype()),
-            "N"    => Attr::Usize(self.n),
+            "T"                 => Attr::DataType(T::datatype()),
+            "N"                 => Attr::Usize(self.n),
+            "accumulate_in_f64" => Attr::Bool(self.accumulate_in_f64),
         }
     }
 }
@@ -70,3 +95,83 @@ impl<T: Datum> InferenceRulesOp for AddN<T> {
             });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+    use std::sync::Arc;
+    use Tensor;
+
+    #[test]
+    fn add_n_five() {
+        let op = AddN::<f32>::new(5, false);
+        let inputs = (1..6)
+            .map(|i| Tensor::f32s(&[2, 2], &[i as f32; 4]).unwrap().into())
+            .collect();
+        let result = op.eval(inputs).unwrap();
+        assert_eq!(
+            result[0].as_tensor(),
+            &Tensor::f32s(&[2, 2], &[15.0; 4]).unwrap()
+        );
+    }
+
+    #[test]
+    fn add_n_accumulates_in_owned_input() {
+        let op = AddN::<f32>::new(3, false);
+        let shared = Arc::new(Tensor::f32s(&[2, 2], &[1.0; 4]).unwrap());
+        let owned = Tensor::f32s(&[2, 2], &[2.0; 4]).unwrap();
+        let ptr = owned.as_f32s().unwrap().as_ptr();
+        let inputs = vec![owned.into(), shared.clone().into(), shared.into()];
+        let result = op.eval(inputs).unwrap();
+        let sum = result[0].as_tensor().as_f32s().unwrap();
+        assert_eq!(sum.as_ptr(), ptr);
+        assert_eq!(sum, &arr2(&[[4.0, 4.0], [4.0, 4.0]]).into_dyn());
+    }
+
+    #[test]
+    fn add_n_in_f64() {
+        let inputs = || {
+            [1.0f32, 1.0e8, -1.0e8]
+                .iter()
+                .map(|&x| Tensor::f32s(&[1], &[x]).unwrap().into())
+                .collect()
+        };
+        let naive = AddN::<f32>::new(3, false).eval(inputs()).unwrap();
+        assert_eq!(naive[0].as_tensor(), &Tensor::f32s(&[1], &[0.0]).unwrap());
+        let wide = AddN::<f32>::new(3, true).eval(inputs()).unwrap();
+        assert_eq!(wide[0].as_tensor(), &Tensor::f32s(&[1], &[1.0]).unwrap());
+    }
+
+    #[test]
+    fn add_n_deterministic() {
+        // The order of the additions matters: (1e8 - 1e8) + 1 = 1, but
+        // (1 + 1e8) - 1e8 = 0 in f32.
+        let values = [1.0e8f32, -1.0e8, 1.0];
+        let run = |op: &AddN<f32>, owned: usize| {
+            let inputs = values
+                .iter()
+                .enumerate()
+                .map(|(ix, &x)| {
+                    let t = Tensor::f32s(&[1], &[x]).unwrap();
+                    if ix == owned {
+                        t.into()
+                    } else {
+                        Arc::new(t).into()
+                    }
+                })
+                .collect();
+            op.eval(inputs).unwrap().remove(0).into_tensor()
+        };
+        let op = AddN::<f32>::new(3, false);
+        let first = run(&op, 0);
+        assert_eq!(first, Tensor::f32s(&[1], &[1.0]).unwrap());
+        for owned in 0..3 {
+            let found = run(&op, owned);
+            assert_eq!(
+                found.as_f32s().unwrap()[0].to_bits(),
+                first.as_f32s().unwrap()[0].to_bits()
+            );
+        }
+    }
+}
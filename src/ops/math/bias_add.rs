@@ -0,0 +1,206 @@
+use analyser::interface::*;
+use ops::nn::local_patch::DataFormat;
+use ops::prelude::*;
+
+/// TensorFlow `BiasAdd`, which adds a bias to the channel axis of a tensor.
+///
+/// The channel axis is the last one for NHWC tensors, and the second one for
+/// NCHW tensors. Like TensorFlow, the bias may come with extra dimensions of
+/// size 1 (e.g. `[1, 1, C]` instead of `[C]`): they are squeezed before the
+/// bias is broadcast along the channel axis.
+#[derive(Debug, Clone, new)]
+pub struct BiasAdd<T: Datum> {
+    data_format: DataFormat,
+    _phantom: PhantomData<T>,
+}
+
+pub fn bias_add(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    let data_format = match pb.get_attr_opt_raw_str("data_format")?.unwrap_or(b"NHWC") {
+        b"NHWC" => DataFormat::NHWC,
+        b"NCHW" => DataFormat::NCHW,
+        s => bail!("BiasAdd does not support the {} data_format", String::from_utf8_lossy(s)),
+    };
+    Ok(boxed_new!(BiasAdd(dtype)(data_format)))
+}
+
+/// Returns the channel axis of a tensor of rank `rank`, if it has one.
+fn channel_axis(data_format: DataFormat, rank: usize) -> Option<usize> {
+    match data_format {
+        DataFormat::NHWC if rank > 0 => Some(rank - 1),
+        DataFormat::NCHW if rank > 1 => Some(1),
+        _ => None,
+    }
+}
+
+/// Returns the number of channels of a bias, ignoring its dimensions of
+/// size 1.
+fn bias_channels(shape: &[usize]) -> Result<usize> {
+    let squeezed: Vec<usize> = shape.iter().cloned().filter(|&d| d != 1).collect();
+    if squeezed.len() > 1 {
+        bail!("BiasAdd expects a bias with a single channel axis, got {:?}", shape);
+    }
+    Ok(squeezed.get(0).cloned().unwrap_or(1))
+}
+
+impl<T: Datum> Op for BiasAdd<T> {
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T" => Attr::DataType(T::datatype()),
+            "data_format" => Attr::DataFormat(self.data_format),
+        }
+    }
+
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (value, bias) = args_2!(inputs);
+        let mut value = T::tensor_into_array(value.into_tensor())?;
+        let bias = T::tensor_to_view(&bias)?;
+        let channels = bias_channels(bias.shape())?;
+        let axis = match channel_axis(self.data_format, value.ndim()) {
+            Some(axis) if value.shape()[axis] == channels => axis,
+            _ => bail!(
+                "BiasAdd can not add a bias of shape {:?} to a tensor of shape {:?}",
+                bias.shape(),
+                value.shape()
+            ),
+        };
+        let mut shape = vec![1; value.ndim()];
+        shape[axis] = channels;
+        value += &bias.into_shape(shape)?;
+        Ok(vec![T::array_into_tensor(value).into()])
+    }
+
+    /// Returns a new streaming buffer for the operation.
+    fn new_buffer(&self) -> Box<OpBuffer> {
+        Box::new(QueuesBuffer::new(2))
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
+    /// Evaluates one step of the operation on the given input tensors.
+    fn step(
+        &self,
+        mut inputs: Vec<(Option<usize>, Option<TensorView>)>,
+        buffer: &mut Box<OpBuffer>,
+    ) -> Result<Option<Vec<TensorView>>> {
+        let buffer = buffer
+            .downcast_mut::<QueuesBuffer>()
+            .ok_or("The buffer can't be downcasted to QueuesBuffer.")?;
+        buffer.append(&mut inputs)?;
+
+        if buffer[0].is_empty() || buffer[1].is_empty() {
+            Ok(None)
+        } else {
+            let value = buffer[0].pop_front().unwrap();
+            let bias = buffer[1].pop_front().unwrap();
+            Ok(Some(self.eval(vec![value, bias])?))
+        }
+    }
+
+    /// Checks that both streams ended with no chunk left unpaired.
+    fn flush(&self, buffer: &mut Box<OpBuffer>) -> Result<Vec<Vec<TensorView>>> {
+        let buffer = buffer
+            .downcast_mut::<QueuesBuffer>()
+            .ok_or("The buffer can't be downcasted to QueuesBuffer.")?;
+        if !buffer[0].is_empty() || !buffer[1].is_empty() {
+            bail!(
+                "Streams ended with {} and {} unpaired chunks",
+                buffer[0].len(),
+                buffer[1].len()
+            );
+        }
+        Ok(vec![])
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for BiasAdd<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let value = &inputs[0];
+        let bias = &inputs[1];
+        let output = &outputs[0];
+        let data_format = self.data_format;
+        solver
+            .equals(&inputs.len, 2)
+            .equals(&outputs.len, 1)
+            .equals_all(wrap![&value.datatype, &bias.datatype, &output.datatype, T::datatype()])
+            .equals(&value.shape, &output.shape)
+            .given(&value.rank, move |solver, rank: usize| {
+                solver.given(&bias.shape, move |solver, shape: Vec<usize>| {
+                    if let (Some(axis), Ok(channels)) =
+                        (channel_axis(data_format, rank), bias_channels(&shape))
+                    {
+                        solver.equals(&value.shape[axis], channels as isize);
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squeezed_bias() {
+        let op = BiasAdd::<f32>::new(DataFormat::NHWC);
+        let value = Tensor::f32s(&[1, 2, 2, 2], &[0.0; 8]).unwrap();
+        let bias = Tensor::f32s(&[1, 1, 2], &[1.0, 2.0]).unwrap();
+        let found = op.eval(vec![value.into(), bias.into()]).unwrap();
+        let expected =
+            Tensor::f32s(&[1, 2, 2, 2], &[1.0, 2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 2.0]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let value = Tensor::f32s(&[1, 2, 2, 2], &[0.0; 8]).unwrap();
+        let bias = Tensor::f32s(&[2, 1], &[1.0, 2.0]).unwrap();
+        let found = op.eval(vec![value.into(), bias.into()]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let value = Tensor::f32s(&[1, 2, 2, 2], &[0.0; 8]).unwrap();
+        let bias = Tensor::f32s(&[3], &[1.0, 2.0, 3.0]).unwrap();
+        assert!(op.eval(vec![value.into(), bias.into()]).is_err());
+    }
+    #[test]
+    fn nchw() {
+        let op = BiasAdd::<f32>::new(DataFormat::NCHW);
+        let value = Tensor::f32s(&[1, 2, 2, 2], &[0.0; 8]).unwrap();
+        let bias = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap();
+        let found = op.eval(vec![value.into(), bias.clone().into()]).unwrap();
+        let expected =
+            Tensor::f32s(&[1, 2, 2, 2], &[1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0]).unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let value = Tensor::f32s(&[1, 3, 2, 2], &[0.0; 12]).unwrap();
+        assert!(op.eval(vec![value.into(), bias.into()]).is_err());
+    }
+
+    #[test]
+    fn flush_unpaired_chunks() {
+        let op = BiasAdd::<f32>::new(DataFormat::NHWC);
+        let mut buffer = op.new_buffer();
+        let value = Tensor::f32s(&[1, 2], &[0.0, 0.0]).unwrap();
+        let bias = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap();
+        let found = op.step(vec![(Some(0), Some(value.clone().into())), (None, None)], &mut buffer)
+            .unwrap();
+        assert!(found.is_none());
+        assert!(op.flush(&mut buffer).is_err());
+
+        let mut buffer = op.new_buffer();
+        let found = op.step(
+            vec![(Some(0), Some(value.into())), (None, Some(bias.into()))],
+            &mut buffer,
+        ).unwrap()
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::f32s(&[1, 2], &[1.0, 2.0]).unwrap());
+        assert!(op.flush(&mut buffer).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+use ndarray::prelude::*;
+
+use analyser::interface::*;
+use ops::prelude::*;
+
+/// TensorFlow `MatMul`, the product of two matrices, each of which can be
+/// transposed first.
+#[derive(Debug, Clone, new)]
+pub struct MatMul<T: Datum> {
+    transpose_a: bool,
+    transpose_b: bool,
+    _phantom: PhantomData<T>,
+}
+
+pub fn matmul(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    let transpose_a = pb.get_attr_opt_bool("transpose_a")?.unwrap_or(false);
+    let transpose_b = pb.get_attr_opt_bool("transpose_b")?.unwrap_or(false);
+    Ok(boxed_new!(MatMul(dtype)(transpose_a, transpose_b)))
+}
+
+impl<T: Datum> MatMul<T> {
+    /// Returns the axes of `a` holding its rows and the contracted
+    /// dimension.
+    fn a_axes(&self) -> (usize, usize) {
+        if self.transpose_a {
+            (1, 0)
+        } else {
+            (0, 1)
+        }
+    }
+
+    /// Returns the axes of `b` holding the contracted dimension and its
+    /// columns.
+    fn b_axes(&self) -> (usize, usize) {
+        if self.transpose_b {
+            (1, 0)
+        } else {
+            (0, 1)
+        }
+    }
+
+    /// Multiplies two matrices, transposing them first as needed.
+    fn product<U: ::ndarray::LinalgScalar>(&self, a: ArrayViewD<U>, b: ArrayViewD<U>) -> Result<Array2<U>> {
+        if a.ndim() != 2 || b.ndim() != 2 {
+            bail!(
+                "MatMul expects two matrices, got shapes {:?} and {:?}",
+                a.shape(),
+                b.shape()
+            );
+        }
+        let a = a.into_dimensionality::<Ix2>()?;
+        let b = b.into_dimensionality::<Ix2>()?;
+        let a = if self.transpose_a { a.reversed_axes() } else { a };
+        let b = if self.transpose_b { b.reversed_axes() } else { b };
+        if a.cols() != b.rows() {
+            bail!(
+                "MatMul can not multiply a {:?} matrix by a {:?} one",
+                a.dim(),
+                b.dim()
+            );
+        }
+        Ok(a.dot(&b))
+    }
+}
+
+impl<T: Datum> Op for MatMul<T> {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (a, b) = args_2!(inputs);
+        let (a, b) = (a.as_tensor(), b.as_tensor());
+        // BLAS, when enabled, is fastest on standard layout f32 operands, so
+        // strided ones are copied once.
+        if T::datatype() == DataType::F32 && a.datatype() == T::datatype()
+            && b.datatype() == T::datatype()
+        {
+            let a = a.as_f32_contiguous()?;
+            let b = b.as_f32_contiguous()?;
+            let c = self.product(a.view(), b.view())?;
+            return Ok(vec![Tensor::F32(c.into_dyn()).into()]);
+        }
+        let a = T::tensor_to_view(a)?;
+        let b = T::tensor_to_view(b)?;
+        Ok(vec![T::array_into_tensor(self.product(a, b)?.into_dyn()).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T"           => Attr::DataType(T::datatype()),
+            "transpose_a" => Attr::Bool(self.transpose_a),
+            "transpose_b" => Attr::Bool(self.transpose_b),
+        }
+    }
+}
+
+impl<T: Datum> InferenceRulesOp for MatMul<T> {
+    /// Registers the inference rules of the operator.
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let a = &inputs[0];
+        let b = &inputs[1];
+        let c = &outputs[0];
+        let (a_rows, a_inner) = self.a_axes();
+        let (b_inner, b_cols) = self.b_axes();
+        solver
+            .equals(&inputs.len, 2)
+            .equals(&outputs.len, 1)
+            .equals_all(wrap![&a.datatype, &b.datatype, &c.datatype, T::datatype()])
+            .equals(&a.rank, 2)
+            .equals(&b.rank, 2)
+            .equals(&c.rank, 2)
+            .equals(&a.shape[a_inner], &b.shape[b_inner])
+            .equals(&c.shape[0], &a.shape[a_rows])
+            .equals(&c.shape[1], &b.shape[b_cols]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    #[test]
+    fn transposes() {
+        let a = Tensor::f32s(&[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        let b = Tensor::f32s(&[3, 1], &[1.0, 0.0, -1.0]).unwrap();
+        let expected = Tensor::f32s(&[2, 1], &[-2.0, -2.0]).unwrap();
+        let found = MatMul::<f32>::new(false, false)
+            .eval(vec![a.clone().into(), b.clone().into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        let at = Tensor::f32s(&[3, 2], &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]).unwrap();
+        let bt = Tensor::f32s(&[1, 3], &[1.0, 0.0, -1.0]).unwrap();
+        let found = MatMul::<f32>::new(true, true)
+            .eval(vec![at.into(), bt.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &expected);
+
+        assert!(
+            MatMul::<f32>::new(true, false)
+                .eval(vec![a.into(), b.into()])
+                .is_err()
+        );
+        let vector = Tensor::f32s(&[3], &[1.0, 0.0, -1.0]).unwrap();
+        let matrix = Tensor::f32s(&[1, 3], &[1.0, 0.0, -1.0]).unwrap();
+        assert!(
+            MatMul::<f32>::new(false, false)
+                .eval(vec![matrix.into(), vector.into()])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn strided_and_mistyped_operands() {
+        let a = Tensor::from(
+            arr2(&[[1.0f32, 4.0], [2.0, 5.0], [3.0, 6.0]])
+                .reversed_axes()
+                .into_dyn(),
+        );
+        let b = Tensor::f32s(&[3, 1], &[1.0, 0.0, -1.0]).unwrap();
+        let found = MatMul::<f32>::new(false, false)
+            .eval(vec![a.clone().into(), b.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::f32s(&[2, 1], &[-2.0, -2.0]).unwrap());
+
+        let ints = Tensor::i32s(&[3, 1], &[1, 0, -1]).unwrap();
+        assert!(
+            MatMul::<f32>::new(false, false)
+                .eval(vec![a.into(), ints.into()])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn transposed_shapes() {
+        let mut a = TensorFact::new();
+        a.shape = shapefact![3, 2];
+        let mut b = TensorFact::new();
+        b.shape = shapefact![4, _];
+        let (inputs, outputs) = MatMul::<f32>::new(true, true)
+            .infer(vec![a, b], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(inputs[1].shape, shapefact![4, 3]);
+        assert_eq!(outputs[0].shape, shapefact![2, 4]);
+        assert_eq!(outputs[0].datatype, typefact!(DataType::F32));
+    }
+}
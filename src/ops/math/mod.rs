@@ -1,51 +1,186 @@
-use super::{Op, OpRegister};
+use ndarray::ArrayD;
+use num_traits::NumCast;
+
+use super::{zip_inplace, Attr, Op, OpRegister};
+use tensor::WrappingOps;
 use Result;
 
 mod add_n;
+mod bias_add;
+mod matmul;
+mod reduce;
 
 pub fn register_all_ops(reg: &mut OpRegister) {
     reg.insert("Abs", abs);
     reg.insert("Add", add);
     reg.insert("AddN", add_n::add_n);
-    reg.insert("BiasAdd", add);
+    reg.insert("BiasAdd", bias_add::bias_add);
     reg.insert("Div", div);
     reg.insert("FloorMod", rem);
+    reg.insert("MatMul", matmul::matmul);
+    reg.insert("Max", reduce::max);
+    reg.insert("Mean", reduce::mean);
+    reg.insert("Min", reduce::min);
     reg.insert("Mul", mul);
     reg.insert("Neg", neg);
+    reg.insert("Prod", reduce::prod);
     reg.insert("Rsqrt", rsqrt);
     reg.insert("Sub", sub);
+    reg.insert("Sum", reduce::sum);
     reg.insert("Tanh", tanh);
 }
 
+/// Casts back values accumulated in f64 by the operators with a true
+/// `accumulate_in_f64` attribute.
+pub fn from_f64<T: NumCast>(acc: ArrayD<f64>) -> Result<ArrayD<T>> {
+    let values = acc.iter()
+        .map(|&x| T::from(x).ok_or_else(|| format!("Can not cast back accumulated value {}", x)))
+        .collect::<::std::result::Result<Vec<T>, _>>()?;
+    Ok(ArrayD::from_shape_vec(acc.shape(), values)?)
+}
+
 element_map_signed!(Abs, abs, |x| x.abs());
 element_map_signed!(Neg, neg, |x| x.neg());
 element_map_float!(Rsqrt, rsqrt, |x| x.sqrt().recip());
-element_map_float!(Tanh, tanh, |x| x.tanh());
-
-element_bin!(Add, add, |mut a, b| {
-    a += &b;
-    a
-});
-element_bin!(Div, div, |mut a, b| {
-    a /= &b;
-    a
-});
-element_bin!(Mul, mul, |mut a, b| {
-    a *= &b;
-    a
-});
-element_bin!(Sub, sub, |mut a, b| {
-    a -= &b;
-    a
-});
-element_bin!(Rem, rem, |mut a, b| {
-    a %= &b;
-    a
-});
+element_map_float!(Tanh, exact_tanh, |x| x.tanh(), "approximate" => Attr::Bool(false));
+element_map_float!(FastTanh, fast_tanh, |x| tanh_approx(x), "approximate" => Attr::Bool(true));
+
+/// Builds a `Tanh`, or a `FastTanh` if the node has a true `approximate`
+/// attribute.
+pub fn tanh(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    if pb.get_attr_opt_bool("approximate")?.unwrap_or(false) {
+        fast_tanh(pb)
+    } else {
+        exact_tanh(pb)
+    }
+}
+
+/// Rational approximation of tanh (13th over 6th degree polynomials, as in
+/// Eigen). Its absolute error stays below 1e-6 over the whole real line.
+fn tanh_approx<T: ::num_traits::Float>(x: T) -> T {
+    let c = |v: f64| T::from(v).unwrap();
+    // The approximation reaches 1 at this point.
+    let x = x.max(c(-7.90531110763549805)).min(c(7.90531110763549805));
+    if x.abs() < c(0.0004) {
+        return x;
+    }
+    let x2 = x * x;
+    let p = x2 * c(-2.76076847742355e-16) + c(2.00018790482477e-13);
+    let p = x2 * p + c(-8.60467152213735e-11);
+    let p = x2 * p + c(5.12229709037114e-08);
+    let p = x2 * p + c(1.48572235717979e-05);
+    let p = x2 * p + c(6.37261928875436e-04);
+    let p = x2 * p + c(4.89352455891786e-03);
+    let q = x2 * c(1.19825839466702e-06) + c(1.18534705686654e-04);
+    let q = x2 * q + c(2.26843463243900e-03);
+    let q = x2 * q + c(4.89352518554385e-03);
+    x * p / q
+}
+
+element_bin!(
+    Add,
+    add,
+    |a, b| zip_inplace(a, b, WrappingOps::add_wrapping),
+    WrappingOps::add_wrapping
+);
+element_bin!(
+    Div,
+    div,
+    |mut a, b| {
+        a /= &b;
+        a
+    },
+    |a, b| a / b
+);
+element_bin!(
+    Mul,
+    mul,
+    |a, b| zip_inplace(a, b, WrappingOps::mul_wrapping),
+    WrappingOps::mul_wrapping
+);
+element_bin!(
+    Sub,
+    sub,
+    |a, b| zip_inplace(a, b, WrappingOps::sub_wrapping),
+    WrappingOps::sub_wrapping
+);
+element_bin!(
+    Rem,
+    rem,
+    |mut a, b| {
+        a %= &b;
+        a
+    },
+    |mut a, b| {
+        a %= b;
+        a
+    }
+);
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use ndarray::arr2;
+    use ops::TensorView;
+    use Tensor;
+
+    #[test]
+    fn fast_tanh_precision() {
+        let input: Vec<f32> = (-10000..10001).map(|i| i as f32 / 1000.0).collect();
+        let input = Tensor::f32s(&[input.len()], &input).unwrap();
+        let exact = Tanh::<f32>::new().eval(vec![input.clone().into()]).unwrap();
+        let fast = FastTanh::<f32>::new().eval(vec![input.into()]).unwrap();
+        let exact = exact[0].as_f32s().unwrap();
+        let fast = fast[0].as_f32s().unwrap();
+        for (e, f) in exact.iter().zip(fast.iter()) {
+            assert!((e - f).abs() < 1e-6, "tanh: {} approximated as {}", e, f);
+        }
+    }
+
+    #[test]
+    fn abs_in_place_reuses_owned_input() {
+        let input = Tensor::f32s(&[3], &[-1.0, 0.0, 2.0]).unwrap();
+        let ptr = input.as_f32s().unwrap().as_ptr();
+        let mut inputs = vec![TensorView::Owned(input)];
+        let output = Abs::<f32>::new()
+            .eval_in_place(&mut inputs)
+            .unwrap()
+            .unwrap()
+            .remove(0);
+        assert_eq!(output.as_f32s().unwrap().as_ptr(), ptr);
+        assert_eq!(
+            output.into_tensor(),
+            Tensor::f32s(&[3], &[1.0, 0.0, 2.0]).unwrap()
+        );
+    }
+
+    #[test]
+    fn abs_in_place_declines_shared_input() {
+        let input: TensorView = Tensor::f32s(&[1], &[-1.0]).unwrap().into();
+        let mut inputs = vec![input.into_shared()];
+        assert!(Abs::<f32>::new().eval_in_place(&mut inputs).is_none());
+        assert_eq!(inputs.len(), 1);
+    }
+
+    #[test]
+    fn streamable() {
+        assert!(Tanh::<f32>::new().is_streamable());
+        assert!(!add_n::AddN::<f32>::new(2, false).is_streamable());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_map_matches_serial() {
+        use ops::PARALLEL_MAP_THRESHOLD;
+        let len = 4 * PARALLEL_MAP_THRESHOLD + 3;
+        let input: Vec<f32> = (0..len).map(|i| (i as f32 - 1000.0) / 777.0).collect();
+        let input = Tensor::f32s(&[len], &input).unwrap();
+        let mut serial = input.as_f32s().unwrap().to_owned();
+        serial.mapv_inplace(|x| x.tanh());
+        let parallel = Tanh::<f32>::new().eval(vec![input.into()]).unwrap();
+        assert_eq!(parallel[0].as_tensor(), &Tensor::from(serial));
+    }
+
     #[test]
     fn mul() {
         let a = arr2(&[[1., 2.], [3., 4.]]);
@@ -58,4 +193,91 @@ mod tests {
         let b = arr2(&[[1., 0.], [0., 0.]]);
         assert_eq!(a.dot(&b), arr2(&[[1., 0.], [3., 0.]]));
     }
+
+    #[test]
+    fn integer_overflow_wraps() {
+        use std::i32::{MAX, MIN};
+        let a = Tensor::i32s(&[2], &[MAX, MAX - 1]).unwrap();
+        let b = Tensor::i32s(&[2], &[1, 3]).unwrap();
+        let found = Add::<i32>::new(false)
+            .eval(vec![a.clone().into(), b.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[2], &[MIN, MIN + 1]).unwrap());
+
+        let two = Tensor::i32s(&[], &[2]).unwrap();
+        let found = Mul::<i32>::new(false)
+            .eval(vec![a.into(), two.clone().into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[2], &[-2, -4]).unwrap());
+
+        let min = Tensor::i32s(&[1], &[MIN]).unwrap();
+        let found = Sub::<i32>::new(false)
+            .eval(vec![min.into(), two.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::i32s(&[1], &[MAX - 1]).unwrap());
+    }
+
+    #[test]
+    fn add_zero_rows() {
+        let empty = Tensor::f32s(&[0, 3], &[]).unwrap();
+        let row = Tensor::f32s(&[1, 3], &[1.0, 2.0, 3.0]).unwrap();
+        let found = Add::<f32>::new(false)
+            .eval(vec![empty.clone().into(), row.clone().into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &empty);
+        let found = Add::<f32>::new(false)
+            .eval(vec![row.into(), empty.clone().into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &empty);
+
+        let other = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap();
+        assert!(Add::<f32>::new(false).eval(vec![empty.into(), other.into()]).is_err());
+    }
+
+    #[test]
+    fn scalar_fast_path() {
+        use ops::scalar_operand;
+        let data: Vec<f32> = (0..1000).map(|i| i as f32).collect();
+        let large = Tensor::f32s(&[10, 100], &data).unwrap();
+        let shapes: &[&[usize]] = &[&[], &[1], &[1, 1]];
+        for shape in shapes {
+            let scalar = Tensor::f32s(shape, &[4.0]).unwrap();
+            assert_eq!(scalar_operand(large.shape(), shape), Some(1));
+            assert_eq!(scalar_operand(shape, large.shape()), Some(0));
+
+            // Same computation, through the general broadcasting path.
+            let broadcast = Tensor::from(
+                scalar.as_f32s().unwrap().broadcast(large.shape()).unwrap().to_owned(),
+            );
+            assert_eq!(scalar_operand(large.shape(), broadcast.shape()), None);
+            let found = Sub::<f32>::new(false)
+                .eval(vec![large.clone().into(), scalar.clone().into()])
+                .unwrap();
+            let expected = Sub::<f32>::new(false)
+                .eval(vec![large.clone().into(), broadcast.clone().into()])
+                .unwrap();
+            assert_eq!(found[0].as_tensor(), expected[0].as_tensor());
+            let found = Sub::<f32>::new(false)
+                .eval(vec![scalar.into(), large.clone().into()])
+                .unwrap();
+            let expected = Sub::<f32>::new(false)
+                .eval(vec![broadcast.into(), large.clone().into()])
+                .unwrap();
+            assert_eq!(found[0].as_tensor(), expected[0].as_tensor());
+        }
+        assert_eq!(scalar_operand(&[10, 100], &[1, 1, 1]), None);
+    }
+
+    #[test]
+    fn add_coerce() {
+        let a = Tensor::i32s(&[2], &[1, 2]).unwrap();
+        let b = Tensor::f32s(&[2], &[0.5, 0.25]).unwrap();
+        assert!(Add::<f32>::new(false).eval(vec![a.clone().into(), b.clone().into()]).is_err());
+        let found = Add::<f32>::new(true).eval(vec![a.into(), b.into()]).unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::f32s(&[2], &[1.5, 2.25]).unwrap());
+
+        let wide = Tensor::f64s(&[2], &[1.0, 2.0]).unwrap();
+        let b = Tensor::f32s(&[2], &[0.5, 0.25]).unwrap();
+        assert!(Add::<f32>::new(true).eval(vec![wide.into(), b.into()]).is_err());
+    }
 }
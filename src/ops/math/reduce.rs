@@ -0,0 +1,394 @@
+use analyser::interface::*;
+use ndarray::prelude::*;
+use num_traits::{Bounded, NumCast};
+use ops::prelude::*;
+
+use super::from_f64;
+
+/// The reduction applied by a `Reduce` operator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reducer {
+    Max,
+    Mean,
+    Min,
+    Prod,
+    Sum,
+}
+
+impl Reducer {
+    /// Returns the reduction of an empty axis: the mean is NaN, the maximum
+    /// is the lowest value of the type, and the minimum the highest one.
+    fn empty<T>(&self) -> Result<T>
+    where
+        T: Datum + NumCast + Bounded,
+    {
+        Ok(match self {
+            Reducer::Sum => T::zero(),
+            Reducer::Prod => T::one(),
+            Reducer::Mean => T::from(::std::f64::NAN).ok_or_else(|| {
+                format!("Can not compute the mean of an empty axis of {:?}", T::datatype())
+            })?,
+            Reducer::Max => T::min_value(),
+            Reducer::Min => T::max_value(),
+        })
+    }
+
+    /// Reduces `data` along `axis`, removing the axis.
+    ///
+    /// With `in_f64`, sums and means are accumulated in f64 and cast back.
+    fn reduce_axis<T>(&self, data: ArrayViewD<T>, axis: usize, in_f64: bool) -> Result<ArrayD<T>>
+    where
+        T: Datum + PartialOrd + NumCast + Bounded,
+    {
+        let len = data.shape()[axis];
+        if len == 0 {
+            let mut shape = data.shape().to_vec();
+            shape.remove(axis);
+            return Ok(ArrayD::from_elem(shape, self.empty()?));
+        }
+        let axis = Axis(axis);
+        let sum_f64 = || data.fold_axis(axis, 0.0, |&acc, &x| acc + x.to_f64().unwrap());
+        Ok(match self {
+            Reducer::Sum if in_f64 => from_f64(sum_f64())?,
+            Reducer::Mean if in_f64 => from_f64(sum_f64().mapv(|x| x / len as f64))?,
+            Reducer::Sum => data.fold_axis(axis, T::zero(), |&acc, &x| acc + x),
+            Reducer::Prod => data.fold_axis(axis, T::one(), |&acc, &x| acc * x),
+            Reducer::Mean => {
+                let len = T::from(len).ok_or("Axis too long for the mean datatype")?;
+                data.fold_axis(axis, T::zero(), |&acc, &x| acc + x)
+                    .mapv(|x| x / len)
+            }
+            Reducer::Max | Reducer::Min => {
+                let max = *self == Reducer::Max;
+                data.fold_axis(axis, None, |acc: &Option<T>, &x| match *acc {
+                    Some(m) if (max && !(x > m)) || (!max && !(x < m)) => Some(m),
+                    _ => Some(x),
+                }).mapv(|x| x.unwrap())
+            }
+        })
+    }
+}
+
+/// Reduces a tensor along the axes given by its second input, like
+/// TensorFlow `Sum`, `Prod`, `Mean`, `Max` and `Min`.
+#[derive(Debug, Clone, new)]
+pub struct Reduce<T: Datum> {
+    reducer: Reducer,
+    keep_dims: bool,
+    accumulate_in_f64: bool,
+    _phantom: PhantomData<T>,
+}
+
+/// Builds a reduction.
+///
+/// TensorFlow graphs name the attribute keeping the reduced dimensions
+/// either `keep_dims` or `keepdims`, depending on the version. Either is
+/// accepted, and a node with both must give them the same value. Without
+/// any of them, the reduced dimensions are removed.
+///
+/// Sums and means over long float axes lose precision when accumulated in
+/// the element type: a true `accumulate_in_f64` attribute makes them
+/// accumulate in f64 instead.
+pub fn build(pb: &::tfpb::node_def::NodeDef, reducer: Reducer) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    let keep_dims = match (
+        pb.get_attr_opt_bool("keep_dims")?,
+        pb.get_attr_opt_bool("keepdims")?,
+    ) {
+        (Some(a), Some(b)) if a != b => bail!(
+            "Node {} has conflicting keep_dims ({}) and keepdims ({}) attributes",
+            pb.get_name(),
+            a,
+            b
+        ),
+        (a, b) => a.or(b).unwrap_or(false),
+    };
+    let in_f64 = pb.get_attr_opt_bool("accumulate_in_f64")?.unwrap_or(false);
+    Ok(boxed_new!(Reduce(dtype)(reducer, keep_dims, in_f64)))
+}
+
+pub fn max(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    build(pb, Reducer::Max)
+}
+
+pub fn mean(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    build(pb, Reducer::Mean)
+}
+
+pub fn min(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    build(pb, Reducer::Min)
+}
+
+pub fn prod(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    build(pb, Reducer::Prod)
+}
+
+pub fn sum(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    build(pb, Reducer::Sum)
+}
+
+/// Returns the sorted and deduplicated axes from a reduction indices
+/// tensor, resolving negative axes against `rank`.
+fn axes(indices: &Tensor, rank: usize) -> Result<Vec<usize>> {
+    let indices = indices.as_i32s().ok_or("Expected reduction indices of type i32")?;
+    let mut axes = indices
+        .iter()
+        .map(|&axis| {
+            let resolved = if axis < 0 { axis + rank as i32 } else { axis };
+            if resolved < 0 || resolved as usize >= rank {
+                bail!("Invalid reduction axis {} for rank {}", axis, rank);
+            }
+            Ok(resolved as usize)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    axes.sort();
+    axes.dedup();
+    Ok(axes)
+}
+
+impl<T: Datum + PartialOrd + NumCast + Bounded> Op for Reduce<T> {
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T"                 => Attr::DataType(T::datatype()),
+            "Tidx"              => Attr::DataType(DataType::I32),
+            "keep_dims"         => Attr::Bool(self.keep_dims),
+            "accumulate_in_f64" => Attr::Bool(self.accumulate_in_f64),
+        }
+    }
+
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (data, indices) = args_2!(inputs);
+        let data = T::tensor_to_view(&data)?;
+        let axes = axes(&indices, data.ndim())?;
+
+        let mut shape = data.shape().to_vec();
+        let mut result = data.to_owned();
+        for &axis in axes.iter().rev() {
+            result = self.reducer
+                .reduce_axis(result.view(), axis, self.accumulate_in_f64)?;
+            shape[axis] = 1;
+        }
+        if self.keep_dims {
+            result = result.into_shape(shape)?;
+        }
+        Ok(vec![T::array_into_tensor(result).into()])
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
+    /// Evaluates one step of the operation on the given input tensors.
+    ///
+    /// Each chunk is reduced on its own, so this only works when the
+    /// streaming axis is not one of the reduced axes.
+    fn step(
+        &self,
+        mut inputs: Vec<(Option<usize>, Option<TensorView>)>,
+        _: &mut Box<OpBuffer>,
+    ) -> Result<Option<Vec<TensorView>>> {
+        let (data, indices) = args_2!(inputs);
+
+        if indices.0.is_some() || indices.1.is_none() {
+            bail!("Reduction indices input should not be streamed.");
+        }
+        let indices = indices.1.unwrap();
+
+        match data {
+            (_, None) => Ok(None),
+            (Some(dim), Some(chunk)) => {
+                if axes(&indices, chunk.as_tensor().shape().len())?.contains(&dim) {
+                    bail!(
+                        "{:?} can not reduce over the streaming axis ({}).",
+                        self.reducer,
+                        dim
+                    );
+                }
+                Ok(Some(self.eval(vec![chunk, indices])?))
+            }
+            (None, Some(data)) => Ok(Some(self.eval(vec![data, indices])?)),
+        }
+    }
+}
+
+impl<T: Datum + PartialOrd + NumCast + Bounded> InferenceRulesOp for Reduce<T> {
+    /// Registers the inference rules of the operator.
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let data = &inputs[0];
+        let indices = &inputs[1];
+        let output = &outputs[0];
+        solver
+            .equals(&inputs.len, 2)
+            .equals(&outputs.len, 1)
+            .equals(&data.datatype, T::datatype())
+            .equals(&output.datatype, T::datatype())
+            .equals(&indices.datatype, DataType::I32)
+            .given(&data.rank, move |solver, rank: usize| {
+                solver.given(&indices.value, move |solver, indices: Tensor| {
+                    let axes = match axes(&indices, rank) {
+                        Ok(axes) => axes,
+                        Err(_) => return,
+                    };
+                    let mut kept = 0;
+                    for d in 0..rank {
+                        if !axes.contains(&d) {
+                            solver.equals(&output.shape[kept], &data.shape[d]);
+                            kept += 1;
+                        } else if self.keep_dims {
+                            solver.equals(&output.shape[kept], 1);
+                            kept += 1;
+                        }
+                    }
+                    solver.equals(&output.rank, kept as isize);
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+
+    #[test]
+    fn reduce_eval() {
+        let data = Tensor::from(arr2(&[[1, 5, 3], [4, 2, 6]]));
+        let reduce = |reducer, keep_dims, axes: &[i32]| {
+            let indices = Tensor::i32s(&[axes.len()], axes).unwrap();
+            Reduce::<i32>::new(reducer, keep_dims, false)
+                .eval(vec![data.clone().into(), indices.into()])
+                .unwrap()
+                .remove(0)
+                .into_tensor()
+        };
+        assert_eq!(reduce(Reducer::Sum, false, &[1]), Tensor::i32s(&[2], &[9, 12]).unwrap());
+        assert_eq!(reduce(Reducer::Max, false, &[0]), Tensor::i32s(&[3], &[4, 5, 6]).unwrap());
+        assert_eq!(reduce(Reducer::Min, true, &[-1]), Tensor::i32s(&[2, 1], &[1, 2]).unwrap());
+        assert_eq!(reduce(Reducer::Prod, false, &[0, 1]), Tensor::i32s(&[], &[720]).unwrap());
+        assert_eq!(reduce(Reducer::Mean, false, &[1]), Tensor::i32s(&[2], &[3, 4]).unwrap());
+    }
+
+    #[test]
+    fn reduce_keepdims_spellings() {
+        use tfpb::types::DataType::DT_FLOAT;
+        let data = Tensor::f32s(&[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        let indices = Tensor::i32s(&[1], &[1]).unwrap();
+        let node = |attr: &str, value: bool| {
+            ::tfpb::node()
+                .op("Sum")
+                .attr("T", DT_FLOAT)
+                .attr(attr, value)
+        };
+        for &attr in &["keep_dims", "keepdims"] {
+            let found = sum(&node(attr, true))
+                .unwrap()
+                .eval(vec![data.clone().into(), indices.clone().into()])
+                .unwrap();
+            assert_eq!(found[0].as_tensor(), &Tensor::f32s(&[2, 1], &[6.0, 15.0]).unwrap());
+        }
+        let found = sum(&::tfpb::node().op("Sum").attr("T", DT_FLOAT))
+            .unwrap()
+            .eval(vec![data.into(), indices.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor().shape(), &[2]);
+        assert!(sum(&node("keep_dims", true).attr("keepdims", false)).is_err());
+    }
+
+    #[test]
+    fn reduce_in_f64() {
+        let len = 1_000_000;
+        let data = Tensor::f32s(&[len], &vec![0.1; len]).unwrap();
+        let indices = Tensor::i32s(&[1], &[0]).unwrap();
+        let sum = |in_f64| {
+            let found = Reduce::<f32>::new(Reducer::Sum, false, in_f64)
+                .eval(vec![data.clone().into(), indices.clone().into()])
+                .unwrap();
+            found[0].as_f32s().unwrap()[[]] as f64
+        };
+        let exact = 0.1f32 as f64 * len as f64;
+        let (naive, wide) = (sum(false), sum(true));
+        assert!((wide - exact).abs() < (naive - exact).abs());
+        assert!((wide - exact).abs() < 1e-2, "{} for {}", wide, exact);
+
+        let mean = Reduce::<f32>::new(Reducer::Mean, false, true)
+            .eval(vec![data.into(), indices.into()])
+            .unwrap();
+        assert!((mean[0].as_f32s().unwrap()[[]] - 0.1).abs() < 1e-7);
+    }
+
+    #[test]
+    fn reduce_streaming() {
+        let input = Tensor::from(arr2(&[[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]));
+        let indices = Tensor::i32s(&[1], &[1]).unwrap();
+        let reduce = Reduce::<f32>::new(Reducer::Mean, true, false);
+        let expected = reduce
+            .eval(vec![input.clone().into(), indices.clone().into()])
+            .unwrap();
+
+        let mut buffer = reduce.new_buffer();
+        let mut chunks = vec![];
+        for row in input.as_f32s().unwrap().outer_iter() {
+            let chunk = Tensor::from(row.to_owned().insert_axis(Axis(0)));
+            let inputs = vec![
+                (Some(0), Some(chunk.into())),
+                (None, Some(indices.clone().into())),
+            ];
+            chunks.extend(reduce.step(inputs, &mut buffer).unwrap());
+        }
+
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|c| c[0].as_f32s().unwrap().view())
+            .collect();
+        let streamed = ::ndarray::stack(Axis(0), &chunks).unwrap();
+        assert_eq!(&Tensor::from(streamed), expected[0].as_tensor());
+
+        let chunk = Tensor::f32s(&[1, 3], &[1.0, 2.0, 3.0]).unwrap();
+        let inputs = vec![
+            (Some(0), Some(chunk.into())),
+            (None, Some(Tensor::i32s(&[1], &[0]).unwrap().into())),
+        ];
+        assert!(reduce.step(inputs, &mut buffer).is_err());
+    }
+
+    #[test]
+    fn reduce_empty_axis() {
+        let data = Tensor::f32s(&[2, 0], &[]).unwrap();
+        let indices = Tensor::i32s(&[1], &[1]).unwrap();
+        let reduce = |reducer, in_f64| {
+            Reduce::<f32>::new(reducer, false, in_f64)
+                .eval(vec![data.clone().into(), indices.clone().into()])
+                .unwrap()
+                .remove(0)
+                .into_tensor()
+                .take_f32s()
+                .unwrap()
+        };
+        assert!(reduce(Reducer::Mean, false).iter().all(|x| x.is_nan()));
+        assert!(reduce(Reducer::Mean, true).iter().all(|x| x.is_nan()));
+        assert_eq!(reduce(Reducer::Max, false).as_slice().unwrap(), &[::std::f32::MIN; 2]);
+        assert_eq!(reduce(Reducer::Min, false).as_slice().unwrap(), &[::std::f32::MAX; 2]);
+        assert_eq!(reduce(Reducer::Sum, true).as_slice().unwrap(), &[0.0, 0.0]);
+        assert_eq!(reduce(Reducer::Prod, false).as_slice().unwrap(), &[1.0, 1.0]);
+
+        let data = Tensor::i32s(&[0], &[]).unwrap();
+        let indices = Tensor::i32s(&[1], &[0]).unwrap();
+        let max = Reduce::<i32>::new(Reducer::Max, false, false)
+            .eval(vec![data.clone().into(), indices.clone().into()])
+            .unwrap();
+        assert_eq!(max[0].as_tensor(), &Tensor::i32s(&[], &[i32::min_value()]).unwrap());
+        assert!(
+            Reduce::<i32>::new(Reducer::Mean, false, false)
+                .eval(vec![data.into(), indices.into()])
+                .is_err()
+        );
+    }
+}
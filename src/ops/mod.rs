@@ -11,9 +11,11 @@ use std::sync::Arc;
 use analyser::interface::{Solver, TensorsProxy};
 use analyser::prelude::*;
 use ops::nn::local_patch::{DataFormat, Padding};
+use tensor::Datum;
 use {DataType, Result, Tensor};
 
 use downcast_rs::Downcast;
+use ndarray::{Array, ArrayBase, ArrayD, ArrayViewD, Data, Dimension};
 use objekt;
 #[cfg(feature = "serialize")]
 use serde::ser::{Serialize, Serializer};
@@ -26,8 +28,10 @@ mod cast;
 #[cfg(features = "image_ops")]
 pub mod image;
 pub mod konst;
+mod logic;
 mod math;
 pub mod nn;
+pub mod quant;
 
 pub mod prelude {
     pub use super::{Attr, InferenceRulesOp, Op, OpRegister};
@@ -126,11 +130,102 @@ impl PartialEq for TensorView {
     }
 }
 
+/// Returns the number of elements of a tensor, if its shape is known.
+pub fn element_count(fact: &TensorFact) -> Option<u64> {
+    fact.shape
+        .concretize()
+        .map(|shape| shape.iter().product::<usize>() as u64)
+}
+
+/// Computes the shape resulting from the broadcasting of two shapes, with
+/// the same rules as numpy and TensorFlow.
+pub fn broadcast_shape(a: &[usize], b: &[usize]) -> Result<Vec<usize>> {
+    let rank = a.len().max(b.len());
+    let dim = |s: &[usize], i: usize| {
+        if i + s.len() < rank {
+            1
+        } else {
+            s[i + s.len() - rank]
+        }
+    };
+    (0..rank)
+        .map(|i| match (dim(a, i), dim(b, i)) {
+            (x, y) if x == y => Ok(x),
+            (1, y) => Ok(y),
+            (x, 1) => Ok(x),
+            _ => bail!("Shapes {:?} and {:?} can not be broadcast together", a, b),
+        })
+        .collect()
+}
+
+/// Returns the index of the operand which is a single element broadcasting
+/// to the shape of the other operand, preferring the right one.
+///
+/// Binary operators use this to apply such an operand with `mapv` instead
+/// of going through the general broadcasting.
+pub fn scalar_operand(a: &[usize], b: &[usize]) -> Option<usize> {
+    let single = |s: &[usize]| s.iter().all(|&d| d == 1);
+    if single(b) && b.len() <= a.len() {
+        Some(1)
+    } else if single(a) && a.len() <= b.len() {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+/// Element count above which element-wise operators map their input in
+/// parallel, when the `rayon` feature is enabled.
+pub const PARALLEL_MAP_THRESHOLD: usize = 1 << 16;
+
+/// Maps an array in place, splitting the work across threads for large
+/// arrays when the `rayon` feature is enabled.
+pub fn mapv_inplace<T, F>(a: &mut ArrayD<T>, f: F)
+where
+    T: Datum,
+    F: Fn(T) -> T + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use ndarray_parallel::prelude::*;
+        if a.len() > PARALLEL_MAP_THRESHOLD {
+            return a.par_mapv_inplace(f);
+        }
+    }
+    a.mapv_inplace(f)
+}
+
+/// Combines an array in place with another one of the same shape, element
+/// by element.
+pub fn zip_inplace<T, F>(mut a: ArrayD<T>, b: ArrayViewD<T>, f: F) -> ArrayD<T>
+where
+    T: Datum,
+    F: Fn(T, T) -> T,
+{
+    a.zip_mut_with(&b, |a, &b| *a = f(*a, b));
+    a
+}
+
+/// Copies an array in standard (row-major and contiguous) layout.
+///
+/// Operators which rely on products or reshapes should use it on strided
+/// operands, e.g. transposed tensors, instead of looping over them.
+pub fn standard_layout<T, S, D>(a: &ArrayBase<S, D>) -> Array<T, D>
+where
+    T: Clone,
+    S: Data<Elem = T>,
+    D: Dimension,
+{
+    Array::from_shape_vec(a.raw_dim(), a.iter().cloned().collect()).unwrap()
+}
+
 // TODO(liautaud): Find a more generic way to do this.
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[derive(Debug, Clone)]
 pub enum Attr {
     I64(i64),
+    F32(f32),
+    Bool(bool),
     Usize(usize),
     DataType(DataType),
     DataFormat(DataFormat),
@@ -138,6 +233,10 @@ pub enum Attr {
     Tensor(Tensor),
     UsizeVec(Vec<usize>),
     IsizeVec(Vec<isize>),
+    /// A list of integers, like the `strides` of the convolutions.
+    IntList(Vec<i64>),
+    /// A tensor shape, with -1 for the unknown dimensions.
+    Shape(Vec<isize>),
 }
 
 /// A Tensorflow operation.
@@ -148,11 +247,56 @@ pub trait Op: Debug + objekt::Clone + Send + Sync + 'static + InferenceOp {
     /// Evaluates the operation given the input tensors.
     fn eval(&self, inputs: Vec<TensorView>) -> Result<Vec<TensorView>>;
 
+    /// Returns the number of outputs of the operation, or None when it is
+    /// not known.
+    fn output_arity(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    /// Estimates the number of floating point operations needed to compute
+    /// the outputs, given what is known about the inputs and outputs.
+    ///
+    /// Returns None when the operator doesn't implement the estimation, or
+    /// when the facts are not precise enough.
+    fn flops(&self, _inputs: &[TensorFact], _outputs: &[TensorFact]) -> Option<u64> {
+        None
+    }
+
+    /// Evaluates the operation, reusing the buffers of the input tensors.
+    ///
+    /// This is only implemented for operators which can write their result
+    /// over one of their inputs (e.g. unary element-wise operators). The
+    /// function should return None, leaving `inputs` untouched, when it can't
+    /// work in place on the given inputs, in which case `eval` gets called.
+    fn eval_in_place(&self, _inputs: &mut Vec<TensorView>) -> Option<Result<Vec<TensorView>>> {
+        None
+    }
+
+    /// Returns a copy of the operation which promotes its narrower inputs to
+    /// its datatype instead of rejecting them, if it supports coercion.
+    fn coercing(&self) -> Option<Box<Op>> {
+        None
+    }
+
+    /// Returns whether the outputs of the operation only depend on its
+    /// inputs, so that they can be cached and reused for equal inputs.
+    fn is_stateless(&self) -> bool {
+        true
+    }
+
     /// Returns a new streaming buffer for the operation.
     fn new_buffer(&self) -> Box<OpBuffer> {
         Box::new(EmptyBuffer {})
     }
 
+    /// Returns whether the operation implements `step`.
+    ///
+    /// `step` can legitimately return None, so this is the only way to know
+    /// beforehand whether an operator can be part of a streaming graph.
+    fn is_streamable(&self) -> bool {
+        false
+    }
+
     /// Evaluates one step of the operation on the given input tensors.
     /// This is only implemented for operators which support streaming.
     ///
@@ -182,6 +326,15 @@ pub trait Op: Debug + objekt::Clone + Send + Sync + 'static + InferenceOp {
         bail!("Streaming is not available for operator {:?}", self)
     }
 
+    /// Flushes the streaming buffer of the operation once all its streamed
+    /// inputs have ended.
+    ///
+    /// Returns the chunks which the operator was still holding back (e.g.
+    /// trailing padding), in order, with one TensorView per output port.
+    fn flush(&self, _buffer: &mut Box<OpBuffer>) -> Result<Vec<Vec<TensorView>>> {
+        Ok(vec![])
+    }
+
     /// Infers properties about the input and output tensors.
     ///
     /// The `inputs` and `outputs` arguments correspond to properties about
@@ -273,18 +426,31 @@ impl OpBuilder {
         array::register_all_ops(&mut reg);
         cast::register_all_ops(&mut reg);
         konst::register_all_ops(&mut reg);
+        logic::register_all_ops(&mut reg);
         math::register_all_ops(&mut reg);
         nn::register_all_ops(&mut reg);
+        quant::register_all_ops(&mut reg);
         OpBuilder(reg)
     }
 
+    /// Tells whether there is a builder registered for the operation `op`.
+    pub fn knows(&self, op: &str) -> bool {
+        self.0.contains_key(op)
+    }
+
     pub fn build(&self, pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        match pb.get_op() {
+            "Enter" | "Exit" | "LoopCond" | "Merge" | "NextIteration" | "Switch" => bail!(
+                ::ErrorKind::ControlFlowUnsupported(
+                    pb.get_op().to_string(),
+                    pb.get_name().to_string(),
+                )
+            ),
+            _ => (),
+        }
         match self.0.get(pb.get_op()) {
             Some(builder) => builder(pb),
-            None => Ok(Box::new(UnimplementedOp(
-                pb.get_op().to_string(),
-                pb.to_owned(),
-            ))),
+            None => Ok(Box::new(UnimplementedOp::new(pb))),
         }
     }
 }
@@ -292,12 +458,31 @@ impl OpBuilder {
 #[derive(Debug, Clone)]
 pub struct UnimplementedOp(String, ::tfpb::node_def::NodeDef);
 
+impl UnimplementedOp {
+    /// Returns a placeholder for a node which can not be evaluated.
+    pub fn new(pb: &::tfpb::node_def::NodeDef) -> UnimplementedOp {
+        UnimplementedOp(pb.get_op().to_string(), pb.to_owned())
+    }
+}
+
 impl Op for UnimplementedOp {
     /// Evaluates the operation given the input tensors.
     fn eval(&self, _inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
         Err(format!("unimplemented operation: {}", self.0))?
     }
 
+    /// Returns whether the operation is stateless: nothing is known about
+    /// an unimplemented operation, so it is assumed not to be.
+    fn is_stateless(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of outputs of the operation, which is not known
+    /// for an unimplemented operation.
+    fn output_arity(&self) -> Option<usize> {
+        None
+    }
+
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
         hashmap!{} // FIXME
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use analyser::interface::*;
+use ndarray::prelude::*;
+use num_traits::Float;
+use ops::nn::local_patch::DataFormat;
+use ops::prelude::*;
+use Result;
+
+/// Batch normalization with frozen statistics, on NHWC tensors.
+///
+/// Only the inference path is supported: the op returns the normalized tensor
+/// as its single output.
+#[derive(Debug, Clone, new)]
+pub struct FusedBatchNorm<T: Datum + Float> {
+    epsilon: T,
+}
+
+pub fn fused_batch_norm(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    let epsilon = pb.get_attr_opt_float("epsilon")?.unwrap_or(0.0001);
+    if pb.get_attr_opt_bool("is_training")? == Some(true) {
+        bail!("FusedBatchNorm is only supported for inference (is_training=false)");
+    }
+    let data_format = pb.get_attr_opt_raw_str("data_format")?.unwrap_or(b"NHWC");
+    if data_format != b"NHWC" {
+        bail!("FusedBatchNorm only supports NHWC data_format");
+    }
+    Ok(match dtype {
+        DataType::F32 => Box::new(FusedBatchNorm::new(epsilon)) as Box<Op>,
+        DataType::F64 => Box::new(FusedBatchNorm::new(epsilon as f64)) as Box<Op>,
+        _ => bail!("FusedBatchNorm does not support {:?}", dtype),
+    })
+}
+
+impl<T: Datum + Float> FusedBatchNorm<T> {
+    /// Folds the statistics in one factor and one term per channel.
+    pub fn coefficients(
+        &self,
+        scale: &ArrayViewD<T>,
+        offset: &ArrayViewD<T>,
+        mean: &ArrayViewD<T>,
+        variance: &ArrayViewD<T>,
+    ) -> Result<(Array1<T>, Array1<T>)> {
+        if [scale.ndim(), offset.ndim(), mean.ndim(), variance.ndim()] != [1; 4] {
+            bail!("FusedBatchNorm parameters must be vectors");
+        }
+        let channels = scale.len();
+        if [offset.len(), mean.len(), variance.len()]
+            .iter()
+            .any(|&l| l != channels)
+        {
+            bail!("FusedBatchNorm parameters must all have the same length");
+        }
+        let factor: Array1<T> = scale
+            .iter()
+            .zip(variance.iter())
+            .map(|(&s, &v)| s / (v + self.epsilon).sqrt())
+            .collect();
+        let term: Array1<T> = offset
+            .iter()
+            .zip(mean.iter())
+            .zip(factor.iter())
+            .map(|((&o, &m), &f)| o - m * f)
+            .collect();
+        Ok((factor, term))
+    }
+}
+
+impl<T: Datum + Float> Op for FusedBatchNorm<T> {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (x, scale, offset, mean, variance) = args_5!(inputs);
+        let (factor, term) = self.coefficients(
+            &T::tensor_to_view(&scale)?,
+            &T::tensor_to_view(&offset)?,
+            &T::tensor_to_view(&mean)?,
+            &T::tensor_to_view(&variance)?,
+        )?;
+        let mut x = T::tensor_into_array(x.into_tensor())?;
+        if x.ndim() == 0 || x.shape()[x.ndim() - 1] != factor.len() {
+            bail!(
+                "FusedBatchNorm expects {} channels on the last axis, got {:?}",
+                factor.len(),
+                x.shape()
+            );
+        }
+        let channels = factor.len();
+        for (i, v) in x.iter_mut().enumerate() {
+            let c = i % channels;
+            *v = *v * factor[c] + term[c];
+        }
+        Ok(vec![T::array_into_tensor(x).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T"           => Attr::DataType(T::datatype()),
+            "epsilon"     => Attr::F32(self.epsilon.to_f32().unwrap()),
+            "data_format" => Attr::DataFormat(DataFormat::NHWC),
+        }
+    }
+}
+
+impl<T: Datum + Float> InferenceRulesOp for FusedBatchNorm<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let input = &inputs[0];
+        let output = &outputs[0];
+        solver
+            .equals(&inputs.len, 5)
+            .equals(&outputs.len, 1)
+            .equals_all(wrap![
+                &input.datatype,
+                &inputs[1].datatype,
+                &inputs[2].datatype,
+                &inputs[3].datatype,
+                &inputs[4].datatype,
+                &output.datatype,
+                T::datatype()
+            ])
+            .equals_all(wrap![
+                &inputs[1].rank,
+                &inputs[2].rank,
+                &inputs[3].rank,
+                &inputs[4].rank,
+                1
+            ])
+            // The four parameter vectors share their length, so knowing any
+            // of them is enough to find the others.
+            .equals_all(wrap![
+                &inputs[1].shape[0],
+                &inputs[2].shape[0],
+                &inputs[3].shape[0],
+                &inputs[4].shape[0]
+            ])
+            .equals(&input.rank, 4)
+            .equals(&output.rank, 4)
+            .equals(&input.shape[3], &inputs[1].shape[0]);
+        for dim in 0..4 {
+            solver.equals(&output.shape[dim], &input.shape[dim]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyser::TensorFact;
+    use ops::InferenceOp;
+
+    #[test]
+    fn batchnorm_eval() {
+        let op = FusedBatchNorm::<f32>::new(0.0);
+        let x = Tensor::f32s(&[1, 1, 2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        let result = op.eval(vec![
+            x.into(),
+            Tensor::f32s(&[2], &[1.0, 2.0]).unwrap().into(),
+            Tensor::f32s(&[2], &[0.0, 1.0]).unwrap().into(),
+            Tensor::f32s(&[2], &[1.0, 2.0]).unwrap().into(),
+            Tensor::f32s(&[2], &[4.0, 1.0]).unwrap().into(),
+        ]).unwrap();
+        assert_eq!(
+            result[0].as_tensor(),
+            &Tensor::f32s(&[1, 1, 2, 2], &[0.0, 1.0, 1.0, 5.0]).unwrap()
+        );
+    }
+
+    #[test]
+    fn batchnorm_rejects_non_vector_parameters() {
+        let op = FusedBatchNorm::<f32>::new(0.0);
+        let x = Tensor::f32s(&[1, 1, 2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        let param = || Tensor::f32s(&[2], &[1.0, 2.0]).unwrap().into();
+        let result = op.eval(vec![
+            x.into(),
+            Tensor::f32s(&[1, 2], &[1.0, 2.0]).unwrap().into(),
+            param(),
+            param(),
+            param(),
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn batchnorm_channels_from_scale() {
+        let op = FusedBatchNorm::<f32>::new(0.001);
+        let mut scale = TensorFact::new();
+        scale.shape = shapefact![3];
+        let inputs = vec![
+            TensorFact::new(),
+            scale,
+            TensorFact::new(),
+            TensorFact::new(),
+            TensorFact::new(),
+        ];
+        let (inputs, outputs) = op.infer(inputs, vec![TensorFact::new()]).unwrap();
+        assert_eq!(inputs[0].shape, shapefact![_, _, _, 3]);
+        assert_eq!(inputs[4].shape, shapefact![3]);
+        assert_eq!(outputs[0].shape, shapefact![_, _, _, 3]);
+        assert_eq!(outputs[0].datatype, typefact!(DataType::F32));
+    }
+}
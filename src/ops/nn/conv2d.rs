@@ -7,8 +7,14 @@ use ndarray::prelude::*;
 use ndarray::{stack, Axis, Slice};
 use ops::prelude::*;
 
+/// 2D convolution on NHWC data with an HWIO filter.
+///
+/// The second field is the number of groups: the input channels are split
+/// in that many groups, each convolved with its own slice of the output
+/// channels of the filter, whose input channel dimension is the size of one
+/// group. A single group is the usual convolution.
 #[derive(Debug, Clone, new)]
-pub struct Conv2D<T: Datum>(LocalPatch, PhantomData<T>);
+pub struct Conv2D<T: Datum>(LocalPatch, usize, PhantomData<T>);
 
 #[derive(Debug, Clone)]
 pub struct Buffer<T: Datum> {
@@ -25,17 +31,76 @@ impl<T: Datum> OpBuffer for Buffer<T> {}
 pub fn conv2d(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     let dtype = pb.get_attr_datatype("T")?;
     let patch = LocalPatch::build(pb)?;
-    Ok(boxed_new!(Conv2D(dtype)(patch)))
+    let groups = pb.get_attr_opt_int("groups")?.unwrap_or(1);
+    if groups == 0 {
+        bail!("Conv2D needs at least one group");
+    }
+    let dilations: Vec<usize> = pb.get_attr_opt_list_int("dilations")?.unwrap_or(vec![1; 4]);
+    if dilations.iter().any(|&d| d != 1) {
+        bail!("Conv2D does not support dilations, found {:?}", dilations);
+    }
+    Ok(match dtype {
+        DataType::I32 => Box::new(Conv2D::<i32>::new(patch, groups)) as Box<Op>,
+        DataType::F32 => Box::new(Conv2D::<f32>::new(patch, groups)) as Box<Op>,
+        DataType::F64 => Box::new(Conv2D::<f64>::new(patch, groups)) as Box<Op>,
+        _ => bail!("Conv2D does not support {:?}", dtype),
+    })
 }
 
 impl<T: Datum> Conv2D<T> {
-    /// Performs a 2D convolution on an input tensor and a filter.
+    /// Performs a 2D convolution on an input tensor and a filter, one group
+    /// of channels at a time.
     fn convolve(
         &self,
         data: &Array4<T>,
         filter: ArrayViewD<T>,
         pad_rows: bool,
         pad_cols: bool,
+    ) -> Result<(Array4<T>)> {
+        // Strided operands, like transposed tensors, would make the patch
+        // extraction and the products much slower, so they are copied once.
+        let contiguous_data;
+        let data = if data.is_standard_layout() {
+            data
+        } else {
+            contiguous_data = ::ops::standard_layout(data);
+            &contiguous_data
+        };
+        let contiguous_filter;
+        let filter = if filter.is_standard_layout() {
+            filter.view()
+        } else {
+            contiguous_filter = ::ops::standard_layout(&filter);
+            contiguous_filter.view()
+        };
+
+        let groups = self.1;
+        if groups == 1 {
+            return self.convolve_group(data, filter, pad_rows, pad_cols);
+        }
+        let in_depth = filter.shape()[2];
+        let out_depth = filter.shape()[3] / groups;
+        let outputs = (0..groups)
+            .map(|g| {
+                let data = data.slice_axis(Axis(3), Slice::from(g * in_depth..(g + 1) * in_depth));
+                // The filter slice must be contiguous to be reshaped.
+                let filter = filter
+                    .slice_axis(Axis(3), Slice::from(g * out_depth..(g + 1) * out_depth))
+                    .to_owned();
+                self.convolve_group(&data.to_owned(), filter.view(), pad_rows, pad_cols)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let views: Vec<_> = outputs.iter().map(|o| o.view()).collect();
+        Ok(stack(Axis(3), &views)?)
+    }
+
+    /// Performs a 2D convolution on an input tensor and a filter.
+    fn convolve_group(
+        &self,
+        data: &Array4<T>,
+        filter: ArrayViewD<T>,
+        pad_rows: bool,
+        pad_cols: bool,
     ) -> Result<(Array4<T>)> {
         let images = BatchImageWrapper(data.view());
 
@@ -76,7 +141,8 @@ impl<T: Datum> Op for Conv2D<T> {
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
         let mut attributes = hashmap!{
-            "T" => Attr::DataType(T::datatype()),
+            "T"      => Attr::DataType(T::datatype()),
+            "groups" => Attr::Usize(self.1),
         };
 
         attributes.extend(self.0.get_attributes());
@@ -89,6 +155,21 @@ impl<T: Datum> Op for Conv2D<T> {
         let data = T::tensor_into_array(m_data.into_tensor())?;
         let filter = T::tensor_to_view(&*m_filter)?;
         let data = into_4d(data)?;
+        if filter.ndim() != 4 || filter.shape()[2] * self.1 != data.shape()[3] {
+            bail!(
+                "Conv2D filter {:?} doesn't match the {} input channels in {} group(s)",
+                filter.shape(),
+                data.shape()[3],
+                self.1
+            );
+        }
+        if filter.shape()[3] % self.1 != 0 {
+            bail!(
+                "Conv2D can not split the {} output channels in {} groups",
+                filter.shape()[3],
+                self.1
+            );
+        }
 
         Ok(vec![
             T::array_into_tensor(self.convolve(&data, filter, true, true)?.into_dyn()).into(),
@@ -105,6 +186,19 @@ impl<T: Datum> Op for Conv2D<T> {
         Box::new(buffer)
     }
 
+    /// Estimates the number of floating point operations: one multiplication
+    /// and one addition per filter weight and output value.
+    fn flops(&self, inputs: &[TensorFact], outputs: &[TensorFact]) -> Option<u64> {
+        let filter = inputs.get(1)?.shape.concretize()?;
+        let output = ::ops::element_count(outputs.get(0)?)?;
+        Some(2 * output * (filter[0] * filter[1] * filter[2]) as u64)
+    }
+
+    /// Returns whether the operation implements `step`.
+    fn is_streamable(&self) -> bool {
+        true
+    }
+
     /// Evaluates one step of the operation on the given input tensors.
     fn step(
         &self,
@@ -228,8 +322,59 @@ impl<T: Datum> InferenceRulesOp for Conv2D<T> {
             .equals(&inputs[1].rank, 4)
             .equals(&outputs[0].rank, 4)
             .equals(&inputs[0].shape[0], &outputs[0].shape[0])
-            .equals(&inputs[0].shape[3], &inputs[1].shape[2])
-            .equals(&outputs[0].shape[3], &inputs[1].shape[3])
+            .equals(&outputs[0].shape[3], &inputs[1].shape[3]);
+        // The filter is [filter_h, filter_w, in_channels / groups, out_channels].
+        let groups = self.1;
+        solver
+            .given(&inputs[1].shape[2], move |solver, filter_in: usize| {
+                solver.given(&inputs[0].shape[3], move |solver, in_channels: DimFact| {
+                    match in_channels {
+                        DimFact::Only(c) if filter_in == 0 || c % filter_in != 0 => {
+                            solver.fail(format!(
+                                "Conv2D can not split the {} input channels (inputs[0].shape[3]) \
+                                 in groups of {} (inputs[1].shape[2])",
+                                c, filter_in
+                            ));
+                        }
+                        DimFact::Only(c) if c / filter_in != groups => {
+                            solver.fail(format!(
+                                "Conv2D filter expects {} group(s) of {} input channels \
+                                 (inputs[1].shape[2]), got {}",
+                                groups, filter_in, c
+                            ));
+                        }
+                        DimFact::Any => {
+                            solver.equals(&inputs[0].shape[3], (filter_in * groups) as isize);
+                        }
+                        _ => {}
+                    }
+                });
+            })
+            .given(&inputs[0].shape[3], move |solver, in_channels: usize| {
+                solver.given(&inputs[1].shape[2], move |solver, filter_in: DimFact| {
+                    // Known filters are checked against the input above.
+                    if filter_in != DimFact::Any {
+                        return;
+                    }
+                    if in_channels % groups != 0 {
+                        solver.fail(format!(
+                            "Conv2D can not split the {} input channels in {} groups",
+                            in_channels, groups
+                        ));
+                    } else {
+                        solver.equals(&inputs[1].shape[2], (in_channels / groups) as isize);
+                    }
+                });
+            })
+            .given(&inputs[1].shape[3], move |solver, out: usize| {
+                if out % groups != 0 {
+                    solver.fail(format!(
+                        "Conv2D can not split the {} output channels in {} groups",
+                        out, groups
+                    ));
+                }
+            });
+        solver
             .given(&inputs[0].shape[1], move |solver, h: DimFact| match h {
                 DimFact::Only(h) => {
                     solver.given(&inputs[1].shape[0], move |solver, kh| {
@@ -269,6 +414,53 @@ mod tests {
     use super::*;
     use Tensor;
 
+    #[test]
+    fn conv_attributes() {
+        let pb = ::tfpb::node()
+            .op("Conv2D")
+            .attr("T", ::tfpb::types::DataType::DT_FLOAT)
+            .attr("strides", vec![1i64, 2, 3, 1])
+            .attr("padding", "VALID");
+        match conv2d(&pb).unwrap().get_attributes().get("strides") {
+            Some(&Attr::IntList(ref strides)) => assert_eq!(strides, &vec![1, 2, 3, 1]),
+            other => panic!("Unexpected strides {:?}", other),
+        }
+    }
+
+    #[test]
+    fn conv_unsupported_attributes() {
+        let pb = || {
+            ::tfpb::node()
+                .op("Conv2D")
+                .attr("T", ::tfpb::types::DataType::DT_FLOAT)
+                .attr("padding", "SAME")
+        };
+        assert!(conv2d(&pb().attr("strides", vec![1i64, 1, 1, 1])).is_ok());
+        assert!(conv2d(&pb().attr("strides", vec![2i64, 1, 1, 1])).is_err());
+        assert!(
+            conv2d(
+                &pb()
+                    .attr("strides", vec![1i64, 1, 1, 1])
+                    .attr("dilations", vec![1i64, 1, 1, 1])
+            ).is_ok()
+        );
+        assert!(
+            conv2d(
+                &pb()
+                    .attr("strides", vec![1i64, 1, 1, 1])
+                    .attr("dilations", vec![1i64, 2, 2, 1])
+            ).is_err()
+        );
+        let error = conv2d(
+            &::tfpb::node()
+                .op("Conv2D")
+                .attr("T", ::tfpb::types::DataType::DT_UINT8)
+                .attr("strides", vec![1i64, 1, 1, 1])
+                .attr("padding", "SAME"),
+        ).unwrap_err();
+        assert!(format!("{}", error).contains("Conv2D does not support"));
+    }
+
     fn mk(sizes: &[usize]) -> Tensor {
         ::ndarray::Array::range(1f32, sizes.iter().product::<usize>() as f32 + 1.0, 1.0)
             .into_shape(sizes)
@@ -277,12 +469,15 @@ mod tests {
     }
 
     fn verify(input: &[usize], filter: &[usize], stride: usize, padding: Padding, expect: &[f32]) {
-        let result = Conv2D::<f32>::new(LocalPatch {
-            padding: padding,
-            h_stride: stride,
-            v_stride: stride,
-            _data_format: DataFormat::NHWC,
-        }).eval(vec![mk(input).into(), mk(filter).into()])
+        let result = Conv2D::<f32>::new(
+            LocalPatch {
+                padding: padding,
+                h_stride: stride,
+                v_stride: stride,
+                _data_format: DataFormat::NHWC,
+            },
+            1,
+        ).eval(vec![mk(input).into(), mk(filter).into()])
             .unwrap()
             .remove(0);
         assert_eq!(expect.len(), result.shape().iter().product::<usize>());
@@ -341,12 +536,15 @@ mod tests {
 
     #[test]
     fn test_conv_1() {
-        let conv = Conv2D::<f32>::new(LocalPatch {
-            padding: Padding::Same,
-            h_stride: 1,
-            v_stride: 1,
-            _data_format: DataFormat::NHWC,
-        });
+        let conv = Conv2D::<f32>::new(
+            LocalPatch {
+                padding: Padding::Same,
+                h_stride: 1,
+                v_stride: 1,
+                _data_format: DataFormat::NHWC,
+            },
+            1,
+        );
         // NHWC
         let data: Tensor = Tensor::f32s(&[1, 1, 1, 1], &[1f32]).unwrap();
         // HWIO
@@ -361,12 +559,15 @@ mod tests {
 
     #[test]
     fn test_conv_2() {
-        let conv = Conv2D::<f32>::new(LocalPatch {
-            padding: Padding::Same,
-            h_stride: 1,
-            v_stride: 1,
-            _data_format: DataFormat::NHWC,
-        });
+        let conv = Conv2D::<f32>::new(
+            LocalPatch {
+                padding: Padding::Same,
+                h_stride: 1,
+                v_stride: 1,
+                _data_format: DataFormat::NHWC,
+            },
+            1,
+        );
         let data =
             Tensor::f32s(&[1, 2, 2, 1], &[142.3088, 48.891083, 208.3187, -11.274994]).unwrap();
         let filter: Tensor = Tensor::f32s(
@@ -376,6 +577,128 @@ mod tests {
         let exp: Tensor =
             Tensor::f32s(&[1, 2, 2, 1], &[80142.31, 5067.5586, 32266.81, -1812.2109]).unwrap();
 
-        assert!(exp.close_enough(&conv.eval(vec![data.into(), filter.into()]).unwrap()[0]))
+        assert!(exp.close_enough(&conv.eval(vec![data.into(), filter.into()]).unwrap()[0]).unwrap())
+    }
+
+    #[test]
+    fn conv_strided_operands() {
+        let conv = Conv2D::<f32>::new(
+            LocalPatch {
+                padding: Padding::Same,
+                h_stride: 1,
+                v_stride: 1,
+                _data_format: DataFormat::NHWC,
+            },
+            1,
+        );
+        // Transposing NCHW data and an OIWH filter gives non-contiguous NHWC
+        // data and HWIO filter.
+        let data = f32::tensor_into_array(mk(&[1, 2, 5, 4])).unwrap();
+        let data = data.permuted_axes(vec![0, 2, 3, 1]);
+        let filter = f32::tensor_into_array(mk(&[3, 2, 3, 2])).unwrap().reversed_axes();
+        assert!(!data.is_standard_layout() && !filter.is_standard_layout());
+
+        let contiguous = conv.eval(vec![
+            Tensor::from(::ops::standard_layout(&data)).into(),
+            Tensor::from(::ops::standard_layout(&filter)).into(),
+        ]).unwrap();
+        let strided = conv.eval(vec![Tensor::from(data).into(), Tensor::from(filter).into()])
+            .unwrap();
+        assert_eq!(strided, contiguous);
+    }
+
+    #[test]
+    fn conv_wrong_in_channels() {
+        use analyser::TensorFact;
+        use ops::InferenceOp;
+
+        let conv = Conv2D::<f32>::new(
+            LocalPatch {
+                padding: Padding::Valid,
+                h_stride: 1,
+                v_stride: 1,
+                _data_format: DataFormat::NHWC,
+            },
+            1,
+        );
+        let mut input = TensorFact::new();
+        input.shape = shapefact![1, 5, 5, 3];
+        let mut filter = TensorFact::new();
+        filter.shape = shapefact![3, 3, 2, 4];
+        let error = conv.infer(vec![input, filter], vec![TensorFact::new()])
+            .unwrap_err();
+        assert!(format!("{}", error).contains("inputs[1].shape[2]"));
+
+        let error = conv.eval(vec![mk(&[1, 5, 5, 3]).into(), mk(&[3, 3, 2, 4]).into()])
+            .unwrap_err();
+        assert!(format!("{}", error).contains("input channels"));
+    }
+
+    #[test]
+    fn grouped_conv() {
+        use analyser::TensorFact;
+        use ops::InferenceOp;
+
+        let patch = LocalPatch::same(1, 1);
+        let data = mk(&[1, 3, 3, 4]);
+        let filter = mk(&[2, 2, 2, 6]);
+        let found = Conv2D::<f32>::new(patch.clone(), 2)
+            .eval(vec![data.clone().into(), filter.clone().into()])
+            .unwrap()
+            .remove(0)
+            .into_tensor();
+
+        // Each half of the input channels goes through its half of the filter.
+        let data = data.take_f32s().unwrap();
+        let filter = filter.take_f32s().unwrap();
+        let halves: Vec<_> = (0..2)
+            .map(|g| {
+                let data = data.slice_axis(Axis(3), Slice::from(2 * g..2 * g + 2));
+                let filter = filter.slice_axis(Axis(3), Slice::from(3 * g..3 * g + 3));
+                Conv2D::<f32>::new(patch.clone(), 1)
+                    .eval(vec![
+                        Tensor::from(data.to_owned()).into(),
+                        Tensor::from(filter.to_owned()).into(),
+                    ])
+                    .unwrap()
+                    .remove(0)
+                    .into_tensor()
+                    .take_f32s()
+                    .unwrap()
+            })
+            .collect();
+        let expected = stack(Axis(3), &[halves[0].view(), halves[1].view()]).unwrap();
+        assert_eq!(found, Tensor::from(expected));
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![1, 3, 3, 4];
+        let mut filter = TensorFact::new();
+        filter.shape = shapefact![2, 2, _, 6];
+        let (inputs, outputs) = Conv2D::<f32>::new(patch.clone(), 2)
+            .infer(vec![input.clone(), filter], vec![TensorFact::new()])
+            .unwrap();
+        assert_eq!(inputs[1].shape, shapefact![2, 2, 2, 6]);
+        assert_eq!(outputs[0].shape, shapefact![1, 3, 3, 6]);
+
+        let mut filter = TensorFact::new();
+        filter.shape = shapefact![2, 2, 2, 5];
+        assert!(
+            Conv2D::<f32>::new(patch.clone(), 2)
+                .infer(vec![input, filter], vec![TensorFact::new()])
+                .is_err()
+        );
+
+        let mut input = TensorFact::new();
+        input.shape = shapefact![1, 3, 3, 5];
+        let mut filter = TensorFact::new();
+        filter.shape = shapefact![2, 2, 2, 6];
+        let error = Conv2D::<f32>::new(patch, 2)
+            .infer(vec![input, filter], vec![TensorFact::new()])
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("can not split the 5 input channels"),
+            "{}",
+            error
+        );
     }
 }
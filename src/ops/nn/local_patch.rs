@@ -2,12 +2,14 @@ use ndarray::prelude::*;
 use std::collections::HashMap;
 use Result;
 
+use ops::nn::compute_padded_output_dim;
 use ops::Attr;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 pub enum DataFormat {
     NHWC,
+    NCHW,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -94,7 +96,7 @@ impl LocalPatch {
             Err("NCHW data_format not implemented")?
         }
         let strides: Vec<usize> = pb.get_attr_list_int("strides")?;
-        if strides.len() != 4 || strides[0] != 1 && strides[3] != 1 {
+        if strides.len() != 4 || strides[0] != 1 || strides[3] != 1 {
             Err(format!(
                 "strides must be of the form [1, h, v, 1], found {:?}",
                 strides
@@ -125,7 +127,7 @@ impl LocalPatch {
         hashmap! {
             "data_format" => Attr::DataFormat(self._data_format),
             "padding" => Attr::Padding(self.padding),
-            "strides" => Attr::UsizeVec(vec![1, self.v_stride, self.h_stride, 1]),
+            "strides" => Attr::IntList(vec![1, self.v_stride as i64, self.h_stride as i64, 1]),
         }
     }
 
@@ -142,21 +144,11 @@ impl LocalPatch {
     }
 
     pub fn adjusted_dim_rows(&self, in_rows: usize, filter_rows: usize) -> usize {
-        match self.padding {
-            Padding::Same => (in_rows as f32 / self.v_stride as f32).ceil() as usize,
-            Padding::Valid => {
-                ((in_rows - filter_rows + 1) as f32 / self.v_stride as f32).ceil() as usize
-            }
-        }
+        compute_padded_output_dim(in_rows, filter_rows, self.v_stride, 1, self.padding).0
     }
 
     pub fn adjusted_dim_cols(&self, in_cols: usize, filter_cols: usize) -> usize {
-        match self.padding {
-            Padding::Same => (in_cols as f32 / self.h_stride as f32).ceil() as usize,
-            Padding::Valid => {
-                ((in_cols - filter_cols + 1) as f32 / self.h_stride as f32).ceil() as usize
-            }
-        }
+        compute_padded_output_dim(in_cols, filter_cols, self.h_stride, 1, self.padding).0
     }
 
     pub fn pad<T>(
@@ -180,16 +172,13 @@ impl LocalPatch {
         if self.padding == Padding::Same {
             // https://www.tensorflow.org/api_guides/python/nn#Convolution
             let padded_cols = if pad_cols {
-                let h_padding = ::std::cmp::max(
-                    0,
-                    filter_cols - if img.width() % self.h_stride == 0 {
-                        self.h_stride
-                    } else {
-                        img.width() % self.h_stride
-                    },
+                let (_, left_padding, right_padding) = compute_padded_output_dim(
+                    img.width(),
+                    filter_cols,
+                    self.h_stride,
+                    1,
+                    self.padding,
                 );
-                let left_padding = h_padding / 2;
-                let right_padding = h_padding - left_padding;
                 let left_padding = ::ndarray::Array4::<T>::from_elem(
                     (img.count(), img.height(), left_padding, img.depth()),
                     item,
@@ -208,16 +197,13 @@ impl LocalPatch {
             };
 
             let padded_rows = if pad_rows {
-                let v_padding = ::std::cmp::max(
-                    0,
-                    filter_rows - if img.height() % self.v_stride == 0 {
-                        self.v_stride
-                    } else {
-                        img.height() % self.v_stride
-                    },
+                let (_, top_padding, bottom_padding) = compute_padded_output_dim(
+                    img.height(),
+                    filter_rows,
+                    self.v_stride,
+                    1,
+                    self.padding,
                 );
-                let top_padding = v_padding / 2;
-                let bottom_padding = v_padding - top_padding;
                 let top_padding = ::ndarray::Array4::<T>::from_elem(
                     (
                         img.count(),
@@ -307,11 +293,6 @@ pub fn into_4d<T>(data: ArrayD<T>) -> Result<Array4<T>> {
     if data.shape().len() != 4 {
         Err(format!("Expected 4D shape, found: {:?}", data.shape()))?
     }
-    let shape = (
-        data.shape()[0],
-        data.shape()[1],
-        data.shape()[2],
-        data.shape()[3],
-    );
-    Ok(data.into_shape(shape)?)
+    // Unlike a reshape, this keeps the strides of non-contiguous data.
+    Ok(data.into_dimensionality()?)
 }
@@ -1,70 +1,74 @@
-use analyser::interface::*;
 use ops::prelude::*;
 
+use self::local_patch::Padding;
+
+pub mod batchnorm;
 pub mod conv2d;
 pub mod local_patch;
 pub mod pools;
+pub mod softmax;
 pub mod space_to_batch;
+pub mod xent;
 
 pub fn register_all_ops(reg: &mut OpRegister) {
     reg.insert("AvgPool", pools::pool::<pools::AvgPooler>);
     reg.insert("Conv2D", conv2d::conv2d);
+    reg.insert("FusedBatchNorm", batchnorm::fused_batch_norm);
     reg.insert("MaxPool", pools::pool::<pools::MaxPooler>);
+    reg.insert("MaxPoolWithArgmax", pools::MaxPoolWithArgmax::build);
+    reg.insert("Elu", elu);
     reg.insert("Relu", relu);
+    reg.insert("Relu6", relu6);
     reg.insert("Sigmoid", sigmoid);
-    reg.insert("Softmax", Softmax::build);
+    reg.insert("Softmax", softmax::softmax);
+    reg.insert("SoftmaxCrossEntropyWithLogits", xent::softmax_cross_entropy_with_logits);
+    reg.insert("Softplus", softplus);
     reg.insert("SpaceToBatchND", space_to_batch::space_to_batch_nd);
     reg.insert("BatchToSpaceND", space_to_batch::batch_to_space_nd);
 }
 
-element_map_signed!(Relu, relu, |x| if x.is_negative() { T::zero() } else { x });
-element_map_float!(Sigmoid, sigmoid, |x| T::one() / (T::one() + x.neg().exp()));
-
-#[derive(Debug, Clone)]
-pub struct Softmax {}
-
-impl Softmax {
-    pub fn build(_pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
-        Ok(Box::new(Softmax {}))
+/// Computes the output size of a convolution or a pooling along one spatial
+/// dimension, together with the padding to add before and after the input.
+///
+/// With `Same` padding, the output has `ceil(input / stride)` values, and an
+/// odd total padding puts the extra value after the input, like TensorFlow.
+/// With `Valid` padding, only the windows which fit entirely in the input
+/// are kept.
+pub fn compute_padded_output_dim(
+    input: usize,
+    kernel: usize,
+    stride: usize,
+    dilation: usize,
+    padding: Padding,
+) -> (usize, usize, usize) {
+    let kernel = (kernel.max(1) - 1) * dilation + 1;
+    match padding {
+        Padding::Valid if input < kernel => (0, 0, 0),
+        Padding::Valid => ((input - kernel) / stride + 1, 0, 0),
+        Padding::Same => {
+            let output = (input + stride - 1) / stride;
+            let total = (output.saturating_sub(1) * stride + kernel).saturating_sub(input);
+            (output, total / 2, total - total / 2)
+        }
     }
 }
 
-impl Op for Softmax {
-    /// Evaluates the operation given the input tensors.
-    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
-        let m_input = args_1!(inputs);
-        let mut input = m_input
-            .into_tensor()
-            .take_f32s()
-            .ok_or("Expect input #0 to be f32")?;
-        input.map_inplace(|a| *a = a.exp());
-        let norm: f32 = input.iter().sum();
-        input.map_inplace(|a| *a = *a / norm);
-        let result = Tensor::from(input);
-        Ok(vec![result.into()])
-    }
-
-    /// Returns the attributes of the operation and their values.
-    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
-        hashmap!{}
+element_map_signed!(Relu, relu, |x| if x.is_negative() { T::zero() } else { x });
+element_map_float!(Sigmoid, sigmoid, |x| T::one() / (T::one() + x.neg().exp()));
+// Written with comparisons, which are false on NaN, so that NaN propagates.
+element_map_float!(Relu6, relu6, |x| {
+    let six = T::from(6).unwrap();
+    if x < T::zero() {
+        T::zero()
+    } else if x > six {
+        six
+    } else {
+        x
     }
-}
-
-impl InferenceRulesOp for Softmax {
-    /// Registers the inference rules of the operator.
-    fn rules<'r, 'p: 'r, 's: 'r>(
-        &'s self,
-        solver: &mut Solver<'r>,
-        inputs: &'p TensorsProxy,
-        outputs: &'p TensorsProxy,
-    ) {
-        solver
-            .equals(&inputs.len, 1)
-            .equals(&outputs.len, 1)
-            .equals(&inputs[0].datatype, &outputs[0].datatype)
-            .equals(&inputs[0].shape, &outputs[0].shape);
-    }
-}
+});
+element_map_float!(Elu, elu, |x| if x < T::zero() { x.exp_m1() } else { x });
+// log(1 + e^x), written so that e^x can not overflow.
+element_map_float!(Softplus, softplus, |x| x.max(T::zero()) + x.abs().neg().exp().ln_1p());
 
 pub fn arr4<A, V, U, T>(xs: &[V]) -> ::ndarray::Array4<A>
 where
@@ -93,3 +97,57 @@ where
         ArrayBase::from_shape_vec_unchecked(dim, v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activation(op: &Op, expected: &[f32]) {
+        let input = Tensor::f32s(&[6], &[-10.0, -1.0, 0.0, 1.0, 10.0, ::std::f32::NAN]).unwrap();
+        let found = op.eval(vec![input.into()]).unwrap();
+        let found = found[0].as_f32s().unwrap();
+        assert_eq!(found.len(), expected.len() + 1);
+        for (f, e) in found.iter().zip(expected.iter()) {
+            assert!(
+                (f - e).abs() <= 1e-6 * f.abs().max(e.abs()),
+                "expected {:?}, found {:?}",
+                expected,
+                found
+            );
+        }
+        assert!(found[expected.len()].is_nan(), "NaN input gave {:?}", found);
+    }
+
+    #[test]
+    fn activations() {
+        activation(&Relu::<f32>::new(), &[0.0, 0.0, 0.0, 1.0, 10.0]);
+        activation(&Relu6::<f32>::new(), &[0.0, 0.0, 0.0, 1.0, 6.0]);
+        let e = 1f32.exp();
+        activation(&Elu::<f32>::new(), &[-0.9999546, 1.0 / e - 1.0, 0.0, 1.0, 10.0]);
+        activation(
+            &Softplus::<f32>::new(),
+            &[4.5398899e-5, (1.0 + 1.0 / e).ln(), 2f32.ln(), (1.0 + e).ln(), 10.0000454],
+        );
+    }
+
+    #[test]
+    fn padded_output_dim_valid() {
+        assert_eq!(compute_padded_output_dim(5, 3, 1, 1, Padding::Valid), (3, 0, 0));
+        assert_eq!(compute_padded_output_dim(5, 3, 2, 1, Padding::Valid), (2, 0, 0));
+        assert_eq!(compute_padded_output_dim(6, 3, 2, 1, Padding::Valid), (2, 0, 0));
+        assert_eq!(compute_padded_output_dim(2, 3, 1, 1, Padding::Valid), (0, 0, 0));
+        assert_eq!(compute_padded_output_dim(7, 3, 1, 2, Padding::Valid), (3, 0, 0));
+    }
+
+    #[test]
+    fn padded_output_dim_same() {
+        assert_eq!(compute_padded_output_dim(5, 3, 1, 1, Padding::Same), (5, 1, 1));
+        // Three windows need (3 - 1) * 2 + 3 = 7 values, so one is added.
+        assert_eq!(compute_padded_output_dim(6, 3, 2, 1, Padding::Same), (3, 0, 1));
+        assert_eq!(compute_padded_output_dim(5, 3, 2, 1, Padding::Same), (3, 1, 1));
+        assert_eq!(compute_padded_output_dim(5, 4, 3, 1, Padding::Same), (2, 1, 1));
+        // Kernels smaller than the stride may need no padding at all.
+        assert_eq!(compute_padded_output_dim(4, 1, 2, 1, Padding::Same), (2, 0, 0));
+        assert_eq!(compute_padded_output_dim(5, 3, 1, 2, Padding::Same), (5, 2, 2));
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use super::compute_padded_output_dim;
 use super::local_patch::*;
 use analyser::interface::*;
 use ndarray::prelude::*;
@@ -17,6 +18,9 @@ pub struct Pool<P: Pooler>(LocalPatch, (usize, usize), PhantomData<P>);
 
 pub fn pool<P: Pooler>(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
     let ksize: Vec<usize> = pb.get_attr_list_int("ksize")?;
+    if ksize.len() != 4 || ksize[0] != 1 || ksize[3] != 1 {
+        bail!("ksize must be of the form [1, h, w, 1], found {:?}", ksize);
+    }
     Ok(Box::new(Pool::<P>(
         LocalPatch::build(pb)?,
         (ksize[1], ksize[2]),
@@ -34,6 +38,14 @@ impl<P: Pooler + ::std::fmt::Debug> Op for Pool<P> {
             .ok_or("Expected a f32 matrix")?;
         let data = into_4d(data)?;
         let images = BatchImageWrapper(data.view());
+        if self.0.padding == Padding::Valid && (images.h() < (self.1).0 || images.w() < (self.1).1)
+        {
+            bail!(
+                "Pooling window {:?} is larger than the input {:?} with VALID padding",
+                self.1,
+                (images.h(), images.w())
+            );
+        }
 
         let (out_h, out_w) = self.0.adjusted_dim(images.h(), images.w(), self.1);
 
@@ -61,7 +73,7 @@ impl<P: Pooler + ::std::fmt::Debug> Op for Pool<P> {
     /// Returns the attributes of the operation and their values.
     fn get_attributes(&self) -> HashMap<&'static str, Attr> {
         let mut attributes = hashmap!{
-            "ksize" => Attr::UsizeVec(vec![(self.1).0, (self.1).1]),
+            "ksize" => Attr::IntList(vec![1, (self.1).0 as i64, (self.1).1 as i64, 1]),
         };
 
         attributes.extend(self.0.get_attributes());
@@ -85,6 +97,7 @@ impl<P: Pooler + ::std::fmt::Debug> InferenceRulesOp for Pool<P> {
             .equals(&inputs[0].rank, 4)
             .equals(&outputs[0].rank, 4)
             .equals(&inputs[0].shape[0], &outputs[0].shape[0])
+            .equals(&inputs[0].shape[3], &outputs[0].shape[3])
             .given(&inputs[0].shape[1], move |solver, h| {
                 solver.given(&inputs[0].shape[2], move |solver, w| {
                     let (oh, ow) = self.0.adjusted_dim(h, w, self.1);
@@ -129,6 +142,133 @@ impl Pooler for AvgPooler {
     }
 }
 
+/// Max pooling which also outputs the position of each maximum.
+///
+/// The positions are flattened indices in the input, computed as
+/// `((b * height + y) * width + x) * channels + c`, where the batch term is
+/// only included with the `include_batch_in_index` attribute. Padding values
+/// are never selected, and ties go to the lowest index, so the positions are
+/// deterministic.
+#[derive(Debug, Clone, new)]
+pub struct MaxPoolWithArgmax {
+    patch: LocalPatch,
+    ksize: (usize, usize),
+    include_batch_in_index: bool,
+}
+
+impl MaxPoolWithArgmax {
+    pub fn build(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        let ksize: Vec<usize> = pb.get_attr_list_int("ksize")?;
+        if ksize.len() != 4 {
+            bail!("ksize must be of the form [1, h, w, 1], found {:?}", ksize);
+        }
+        if let Some(dt) = pb.get_attr_opt_datatype("Targmax")? {
+            if dt != DataType::I64 {
+                bail!("MaxPoolWithArgmax only supports int64 indices, found {:?}", dt);
+            }
+        }
+        let include_batch_in_index = pb.get_attr_opt_bool("include_batch_in_index")?
+            .unwrap_or(false);
+        Ok(Box::new(MaxPoolWithArgmax::new(
+            LocalPatch::build(pb)?,
+            (ksize[1], ksize[2]),
+            include_batch_in_index,
+        )))
+    }
+}
+
+impl Op for MaxPoolWithArgmax {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let data = into_4d(f32::tensor_into_array(args_1!(inputs).into_tensor())?)?;
+        let images = BatchImageWrapper(data.view());
+        let (kh, kw) = self.ksize;
+        let (out_h, top, _) =
+            compute_padded_output_dim(images.h(), kh, self.patch.v_stride, 1, self.patch.padding);
+        let (out_w, left, _) =
+            compute_padded_output_dim(images.w(), kw, self.patch.h_stride, 1, self.patch.padding);
+        let out_shape = (images.n(), out_h, out_w, images.d());
+
+        let mut output = Array4::<f32>::zeros(out_shape);
+        let mut argmax = Array4::<i64>::zeros(out_shape);
+        for ((b, h, w, d), o) in output.indexed_iter_mut() {
+            let mut best: Option<(f32, usize)> = None;
+            // Scanning rows then columns visits the positions by increasing
+            // index, so keeping the first maximum breaks ties on the lowest.
+            for y in (h * self.patch.v_stride)..(h * self.patch.v_stride + kh) {
+                for x in (w * self.patch.h_stride)..(w * self.patch.h_stride + kw) {
+                    if y < top || x < left || y - top >= images.h() || x - left >= images.w() {
+                        continue;
+                    }
+                    let (y, x) = (y - top, x - left);
+                    let v = data[(b, y, x, d)];
+                    if best.map(|(m, _)| v > m).unwrap_or(true) {
+                        let batch = if self.include_batch_in_index { b } else { 0 };
+                        let index = ((batch * images.h() + y) * images.w() + x) * images.d() + d;
+                        best = Some((v, index));
+                    }
+                }
+            }
+            let (v, index) = best.ok_or("MaxPoolWithArgmax window outside of the input")?;
+            *o = v;
+            argmax[(b, h, w, d)] = index as i64;
+        }
+
+        Ok(vec![
+            Tensor::from(output.into_dyn()).into(),
+            Tensor::from(argmax.into_dyn()).into(),
+        ])
+    }
+
+    /// Returns the number of outputs of the operation.
+    fn output_arity(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        let mut attributes = hashmap!{
+            "ksize"                  => Attr::IntList(vec![1, self.ksize.0 as i64, self.ksize.1 as i64, 1]),
+            "Targmax"                => Attr::DataType(DataType::I64),
+            "include_batch_in_index" => Attr::Bool(self.include_batch_in_index),
+        };
+
+        attributes.extend(self.patch.get_attributes());
+        attributes
+    }
+}
+
+impl InferenceRulesOp for MaxPoolWithArgmax {
+    /// Registers the inference rules of the operator.
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 1)
+            .equals(&outputs.len, 2)
+            .equals(&inputs[0].datatype, DataType::F32)
+            .equals(&outputs[0].datatype, DataType::F32)
+            .equals(&outputs[1].datatype, DataType::I64)
+            .equals(&inputs[0].rank, 4)
+            .equals(&outputs[0].rank, 4)
+            .equals(&outputs[1].rank, 4)
+            .equals(&outputs[0].shape, &outputs[1].shape)
+            .equals(&inputs[0].shape[0], &outputs[0].shape[0])
+            .equals(&inputs[0].shape[3], &outputs[0].shape[3])
+            .given(&inputs[0].shape[1], move |solver, h| {
+                solver.given(&inputs[0].shape[2], move |solver, w| {
+                    let (oh, ow) = self.patch.adjusted_dim(h, w, self.ksize);
+                    solver
+                        .equals(&outputs[0].shape[1], oh as isize)
+                        .equals(&outputs[0].shape[2], ow as isize);
+                });
+            });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(non_snake_case)]
@@ -143,7 +283,7 @@ mod tests {
         let found = pool.eval(vec![data.into()]).unwrap();
 
         assert!(
-            exp.close_enough(&found[0]),
+            exp.close_enough(&found[0]).unwrap(),
             "expected: {:?} found: {:?}",
             exp,
             found[0]
@@ -158,7 +298,7 @@ mod tests {
         let found = pool.eval(vec![data.into()]).unwrap();
 
         assert!(
-            exp.close_enough(&found[0]),
+            exp.close_enough(&found[0]).unwrap(),
             "expected: {:?} found: {:?}",
             exp,
             found[0]
@@ -173,11 +313,79 @@ mod tests {
         let found = pool.eval(vec![data.into()]).unwrap();
 
         assert!(
-            exp.close_enough(&found[0]),
+            exp.close_enough(&found[0]).unwrap(),
             "expected: {:?} found: {:?}",
             exp,
             found[0]
         )
     }
 
+    #[test]
+    fn test_avgpool_same_counts_valid_values() {
+        let pool = Pool::<AvgPooler>(LocalPatch::same(1, 1), (3, 3), PhantomData);
+        let data = Tensor::f32s(&[1, 2, 2, 1], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        let exp: Tensor = Tensor::f32s(&[1, 2, 2, 1], &[2.5; 4]).unwrap();
+        let found = pool.eval(vec![data.into()]).unwrap();
+        assert_eq!(found[0].as_tensor(), &exp);
+    }
+
+    #[test]
+    fn test_avgpool_same_borders() {
+        let pool = Pool::<AvgPooler>(LocalPatch::same(1, 1), (3, 3), PhantomData);
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let data = Tensor::f32s(&[1, 3, 3, 1], &[
+            1.0, 2.0, 3.0,
+            4.0, 5.0, 6.0,
+            7.0, 8.0, 9.0,
+        ]).unwrap();
+        // Corners average 4 values, edges 6 and the center all 9.
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let exp = Tensor::f32s(&[1, 3, 3, 1], &[
+            12.0 / 4.0, 21.0 / 6.0, 16.0 / 4.0,
+            27.0 / 6.0, 45.0 / 9.0, 33.0 / 6.0,
+            24.0 / 4.0, 39.0 / 6.0, 28.0 / 4.0,
+        ]).unwrap();
+        let found = pool.eval(vec![data.into()]).unwrap();
+        assert!(
+            exp.close_enough(&found[0]).unwrap(),
+            "expected: {:?} found: {:?}",
+            exp,
+            found[0]
+        )
+    }
+
+    #[test]
+    fn test_pool_window_larger_than_input() {
+        let pool = Pool::<MaxPooler>(LocalPatch::valid(1, 1), (3, 3), PhantomData);
+        let data = Tensor::f32s(&[1, 2, 2, 1], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert!(pool.eval(vec![data.into()]).is_err());
+
+        let pb = ::tfpb::node()
+            .op("MaxPool")
+            .attr("T", ::tfpb::types::DataType::DT_FLOAT)
+            .attr("strides", vec![1i64, 1, 1, 1])
+            .attr("padding", "VALID")
+            .attr("ksize", vec![3i64, 3]);
+        assert!(super::pool::<MaxPooler>(&pb).is_err());
+    }
+
+    #[test]
+    fn test_maxpool_with_argmax_ties() {
+        let pool = MaxPoolWithArgmax::new(LocalPatch::valid(2, 2), (2, 2), false);
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let data = Tensor::f32s(&[1, 2, 4, 1], &[
+            1.0, 3.0, 5.0, 2.0,
+            3.0, 3.0, 5.0, 5.0,
+        ]).unwrap();
+        let found = pool.eval(vec![data.into()]).unwrap();
+        assert_eq!(
+            found[0].as_tensor(),
+            &Tensor::f32s(&[1, 1, 2, 1], &[3.0, 5.0]).unwrap()
+        );
+        assert_eq!(
+            found[1].as_tensor(),
+            &Tensor::i64s(&[1, 1, 2, 1], &[1, 2]).unwrap()
+        );
+    }
+
 }
@@ -0,0 +1,87 @@
+use num_traits::Float;
+
+use analyser::interface::*;
+use ops::prelude::*;
+
+/// TensorFlow `Softmax`, normalizing each row along the last axis.
+///
+/// The largest logit of each row is subtracted before exponentiating, so
+/// that large logits do not overflow.
+#[derive(Debug, Clone, new)]
+pub struct Softmax<T: Datum + Float> {
+    _phantom: PhantomData<T>,
+}
+
+pub fn softmax(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    Ok(match dtype {
+        DataType::F32 => Box::new(Softmax::<f32>::new()) as Box<Op>,
+        DataType::F64 => Box::new(Softmax::<f64>::new()) as Box<Op>,
+        _ => bail!("Softmax does not support {:?}", dtype),
+    })
+}
+
+impl<T: Datum + Float> Op for Softmax<T> {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let mut input = T::tensor_into_array(args_1!(inputs).into_tensor())?;
+        if input.ndim() == 0 {
+            bail!("Softmax expects an input of rank 1 or more");
+        }
+        for mut row in input.genrows_mut() {
+            let max = row.iter().fold(T::neg_infinity(), |m, &x| m.max(x));
+            row.mapv_inplace(|x| (x - max).exp());
+            let sum = row.iter().fold(T::zero(), |s, &x| s + x);
+            row.mapv_inplace(|x| x / sum);
+        }
+        Ok(vec![T::array_into_tensor(input).into()])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T" => Attr::DataType(T::datatype()),
+        }
+    }
+}
+
+impl<T: Datum + Float> InferenceRulesOp for Softmax<T> {
+    /// Registers the inference rules of the operator.
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 1)
+            .equals(&outputs.len, 1)
+            .equals(&inputs[0].datatype, T::datatype())
+            .equals(&inputs[0].datatype, &outputs[0].datatype)
+            .equals(&inputs[0].shape, &outputs[0].shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let input = Tensor::f32s(&[2, 2], &[0.0, 2f32.ln(), 1000.0, 1000.0]).unwrap();
+        let found = Softmax::<f32>::new().eval(vec![input.into()]).unwrap();
+        let expected = Tensor::f32s(&[2, 2], &[1.0 / 3.0, 2.0 / 3.0, 0.5, 0.5]).unwrap();
+        assert!(found[0].as_tensor().close_enough(&expected).unwrap());
+    }
+
+    #[test]
+    fn large_logit() {
+        let input = Tensor::f32s(&[3], &[1000.0, 0.0, 1000.0 - 2f32.ln()]).unwrap();
+        let found = Softmax::<f32>::new().eval(vec![input.into()]).unwrap();
+        let found = found[0].as_f32s().unwrap();
+        let expected = [2.0 / 3.0, 0.0, 1.0 / 3.0];
+        for (f, e) in found.iter().zip(expected.iter()) {
+            assert!((f - e).abs() < 1e-4, "found {:?}", found);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use analyser::interface::*;
+use ndarray::prelude::*;
+use num_traits::Float;
+use ops::prelude::*;
+use Result;
+
+/// TensorFlow `SoftmaxCrossEntropyWithLogits`, as found in graphs exported
+/// with their loss head.
+///
+/// Takes logits and labels of shape [batch, classes], and returns the loss
+/// of each example ([batch]) and the gradient of the loss with regard to the
+/// logits ([batch, classes]). The softmax is computed in log space, shifted
+/// by the largest logit of each row, so that large logits do not overflow.
+#[derive(Debug, Clone, new)]
+pub struct SoftmaxCrossEntropyWithLogits<T: Datum + Float> {
+    _phantom: PhantomData<T>,
+}
+
+pub fn softmax_cross_entropy_with_logits(pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+    let dtype = pb.get_attr_datatype("T")?;
+    Ok(match dtype {
+        DataType::F32 => Box::new(SoftmaxCrossEntropyWithLogits::<f32>::new()) as Box<Op>,
+        DataType::F64 => Box::new(SoftmaxCrossEntropyWithLogits::<f64>::new()) as Box<Op>,
+        _ => bail!("SoftmaxCrossEntropyWithLogits does not support {:?}", dtype),
+    })
+}
+
+impl<T: Datum + Float> Op for SoftmaxCrossEntropyWithLogits<T> {
+    /// Returns the number of outputs of the operation.
+    fn output_arity(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        let (logits, labels) = args_2!(inputs);
+        let logits = T::tensor_to_view(&logits)?;
+        let labels = T::tensor_to_view(&labels)?;
+        if logits.ndim() != 2 || logits.shape() != labels.shape() {
+            bail!(
+                "SoftmaxCrossEntropyWithLogits expects logits and labels of the same \
+                 [batch, classes] shape, got {:?} and {:?}",
+                logits.shape(),
+                labels.shape()
+            );
+        }
+        let logits = logits.into_dimensionality::<Ix2>()?;
+        let labels = labels.into_dimensionality::<Ix2>()?;
+
+        let mut loss = Array1::<T>::zeros(logits.rows());
+        let mut backprop = Array2::<T>::zeros(logits.dim());
+        for (b, (logits, labels)) in logits.outer_iter().zip(labels.outer_iter()).enumerate() {
+            let max = logits.iter().fold(T::neg_infinity(), |m, &x| m.max(x));
+            let sum = logits.iter().fold(T::zero(), |s, &x| s + (x - max).exp());
+            let log_sum = max + sum.ln();
+            for (c, (&x, &label)) in logits.iter().zip(labels.iter()).enumerate() {
+                let log_softmax = x - log_sum;
+                loss[b] = loss[b] - label * log_softmax;
+                backprop[(b, c)] = log_softmax.exp() - label;
+            }
+        }
+        Ok(vec![
+            T::array_into_tensor(loss.into_dyn()).into(),
+            T::array_into_tensor(backprop.into_dyn()).into(),
+        ])
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        hashmap!{
+            "T" => Attr::DataType(T::datatype()),
+        }
+    }
+}
+
+impl<T: Datum + Float> InferenceRulesOp for SoftmaxCrossEntropyWithLogits<T> {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        let logits = &inputs[0];
+        let labels = &inputs[1];
+        let loss = &outputs[0];
+        let backprop = &outputs[1];
+        solver
+            .equals(&inputs.len, 2)
+            .equals(&outputs.len, 2)
+            .equals_all(wrap![
+                &logits.datatype,
+                &labels.datatype,
+                &loss.datatype,
+                &backprop.datatype,
+                T::datatype()
+            ])
+            .equals(&logits.rank, 2)
+            .equals(&labels.shape, &logits.shape)
+            .equals(&backprop.shape, &logits.shape)
+            .equals(&loss.rank, 1)
+            .equals(&loss.shape[0], &logits.shape[0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_classes() {
+        let logits = Tensor::f32s(&[2, 2], &[1.0, 2.0, 0.0, 0.0]).unwrap();
+        let labels = Tensor::f32s(&[2, 2], &[0.0, 1.0, 0.5, 0.5]).unwrap();
+        let found = SoftmaxCrossEntropyWithLogits::<f32>::new()
+            .eval(vec![logits.into(), labels.into()])
+            .unwrap();
+
+        // softmax([1, 2]) = [1 / (1 + e), e / (1 + e)]
+        let p = 1.0 / (1.0 + 1f32.exp());
+        let loss = Tensor::f32s(&[2], &[-(1.0 - p).ln(), 2f32.ln()]).unwrap();
+        let backprop = Tensor::f32s(&[2, 2], &[p, -p, 0.0, 0.0]).unwrap();
+        assert!(found[0].as_tensor().close_enough(&loss).unwrap());
+        assert!(found[1].as_tensor().close_enough(&backprop).unwrap());
+    }
+
+    #[test]
+    fn large_logits() {
+        let logits = Tensor::f32s(&[1, 2], &[1000.0, 0.0]).unwrap();
+        let labels = Tensor::f32s(&[1, 2], &[0.0, 1.0]).unwrap();
+        let found = SoftmaxCrossEntropyWithLogits::<f32>::new()
+            .eval(vec![logits.into(), labels.into()])
+            .unwrap();
+        assert_eq!(found[0].as_tensor(), &Tensor::f32s(&[1], &[1000.0]).unwrap());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use analyser::interface::*;
+use ndarray::prelude::*;
+use ndarray::Zip;
+use ops::prelude::*;
+use Result;
+
+/// Adds two quantized u8 tensors.
+///
+/// The inputs are `x`, `y`, `min_x`, `max_x`, `min_y` and `max_y`, and the
+/// outputs `z`, `min_z` and `max_z`. The output range defaults to the sum of
+/// the input ranges, but can be narrowed with `Model::set_output_range`, in
+/// which case the values falling outside of it saturate.
+#[derive(Debug, Clone)]
+pub struct QuantizedAdd {
+    range: Option<(f32, f32)>,
+}
+
+impl QuantizedAdd {
+    pub fn new(range: Option<(f32, f32)>) -> QuantizedAdd {
+        QuantizedAdd { range }
+    }
+
+    pub fn build(_pb: &::tfpb::node_def::NodeDef) -> Result<Box<Op>> {
+        Ok(Box::new(QuantizedAdd::new(None)))
+    }
+}
+
+fn scalar(tensor: &TensorView) -> Result<f32> {
+    let view = f32::tensor_to_view(tensor)?;
+    match view.iter().next() {
+        Some(&v) if view.len() == 1 => Ok(v),
+        _ => bail!("Expected a scalar range, got {:?}", tensor.as_tensor()),
+    }
+}
+
+impl Op for QuantizedAdd {
+    /// Evaluates the operation given the input tensors.
+    fn eval(&self, mut inputs: Vec<TensorView>) -> Result<Vec<TensorView>> {
+        if inputs.len() != 6 {
+            bail!("Expected 6 arguments, got {:?}", inputs.len());
+        }
+        let ranges = inputs
+            .split_off(2)
+            .iter()
+            .map(scalar)
+            .collect::<Result<Vec<f32>>>()?;
+        let (x, y) = args_2!(inputs);
+        let x = u8::tensor_to_view(&x)?;
+        let y = u8::tensor_to_view(&y)?;
+        let y = y.broadcast(x.shape())
+            .ok_or(format!("Could not broadcast {:?} to {:?}", y.shape(), x.shape()))?;
+
+        let (min_x, max_x, min_y, max_y) = (ranges[0], ranges[1], ranges[2], ranges[3]);
+        let (min_z, max_z) = self.range.unwrap_or((min_x + min_y, max_x + max_y));
+        if max_x <= min_x || max_y <= min_y || max_z <= min_z {
+            bail!("QuantizedAdd needs non-empty ranges");
+        }
+        let step_x = (max_x - min_x) / 255.0;
+        let step_y = (max_y - min_y) / 255.0;
+        let step_z = (max_z - min_z) / 255.0;
+
+        let mut z = Array::zeros(x.shape());
+        Zip::from(&mut z).and(&x).and(&y).apply(|z, &x, &y| {
+            let sum = min_x + x as f32 * step_x + min_y + y as f32 * step_y;
+            // Saturate instead of wrapping around.
+            *z = ((sum - min_z) / step_z).round().max(0.0).min(255.0) as u8;
+        });
+
+        Ok(vec![
+            Tensor::U8(z).into(),
+            Tensor::F32(arr0(min_z).into_dyn()).into(),
+            Tensor::F32(arr0(max_z).into_dyn()).into(),
+        ])
+    }
+
+    /// Returns the number of outputs of the operation: the sum, and the
+    /// float values of its smallest and largest quantized values.
+    fn output_arity(&self) -> Option<usize> {
+        Some(3)
+    }
+
+    /// Returns the attributes of the operation and their values.
+    fn get_attributes(&self) -> HashMap<&'static str, Attr> {
+        match self.range {
+            Some((min, max)) => hashmap!{
+                "min_z" => Attr::F32(min),
+                "max_z" => Attr::F32(max),
+            },
+            None => hashmap!{},
+        }
+    }
+}
+
+impl InferenceRulesOp for QuantizedAdd {
+    fn rules<'r, 'p: 'r, 's: 'r>(
+        &'s self,
+        solver: &mut Solver<'r>,
+        inputs: &'p TensorsProxy,
+        outputs: &'p TensorsProxy,
+    ) {
+        solver
+            .equals(&inputs.len, 6)
+            .equals(&outputs.len, 3)
+            .equals_all(wrap![&inputs[0].datatype, &inputs[1].datatype, DataType::U8])
+            .equals(&outputs[0].datatype, DataType::U8)
+            .equals(&outputs[0].rank, &inputs[0].rank)
+            .given(&inputs[0].rank, move |solver, rank: usize| {
+                for dim in 0..rank {
+                    solver.equals(&outputs[0].shape[dim], &inputs[0].shape[dim]);
+                }
+            });
+        for i in 2..6 {
+            solver
+                .equals(&inputs[i].datatype, DataType::F32)
+                .equals(&inputs[i].rank, 0);
+        }
+        for i in 1..3 {
+            solver
+                .equals(&outputs[i].datatype, DataType::F32)
+                .equals(&outputs[i].rank, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(v: f32) -> TensorView {
+        Tensor::F32(arr0(v).into_dyn()).into()
+    }
+
+    #[test]
+    fn quantized_add_default_range() {
+        let x = Tensor::from(arr1(&[0u8, 255, 51]));
+        let y = Tensor::from(arr1(&[0u8, 255, 102]));
+        let result = QuantizedAdd::new(None)
+            .eval(vec![x.into(), y.into(), range(0.0), range(1.0), range(0.0), range(1.0)])
+            .unwrap();
+        // 0.2 + 0.4 in [0, 2].
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr1(&[0u8, 255, 77])));
+        assert_eq!(result[1], range(0.0));
+        assert_eq!(result[2], range(2.0));
+    }
+
+    #[test]
+    fn quantized_add_saturates() {
+        let x = Tensor::from(arr1(&[200u8, 255, 0]));
+        let y = Tensor::from(arr1(&[200u8, 255, 0]));
+        let result = QuantizedAdd::new(Some((0.0, 1.0)))
+            .eval(vec![x.into(), y.into(), range(0.0), range(1.0), range(0.0), range(1.0)])
+            .unwrap();
+        assert_eq!(result[0].as_tensor(), &Tensor::from(arr1(&[255u8, 255, 0])));
+    }
+}
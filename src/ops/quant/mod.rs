@@ -0,0 +1,7 @@
+use super::OpRegister;
+
+pub mod add;
+
+pub fn register_all_ops(reg: &mut OpRegister) {
+    reg.insert("QuantizedAdd", add::QuantizedAdd::build);
+}
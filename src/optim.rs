@@ -0,0 +1,664 @@
+//! Graph rewrites which speed up inference without changing the results.
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use ops::konst::Const;
+use ops::nn::batchnorm::FusedBatchNorm;
+use ops::nn::local_patch::DataFormat;
+use ops::{Attr, OpBuilder, TensorView};
+use tfpb::types::DataType::DT_FLOAT;
+use {Model, Node, Result, Tensor};
+
+/// A graph rewrite, to be applied by `Model::optimize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Bypasses the `Identity` nodes.
+    FoldIdentities,
+    /// Replaces the nodes which only depend on constants by their value.
+    FoldConstants,
+    /// Folds batch normalizations into the preceding convolutions.
+    FoldBatchNorm,
+    /// Merges the nodes computing the same operation on the same inputs.
+    EliminateCommonSubexpressions,
+}
+
+impl Model {
+    /// Applies the given passes in order, until none of them changes the
+    /// model anymore, and returns the optimized model along with the number
+    /// of nodes removed by each pass.
+    ///
+    /// The nodes which are not used by any other node are considered as
+    /// outputs, and kept along with their dependencies. Every other node is
+    /// removed as soon as it becomes unused.
+    pub fn optimize(&self, passes: &[Pass]) -> Result<(Model, Vec<(Pass, usize)>)> {
+        let mut model = self.clone();
+        let outputs: HashSet<String> = model
+            .nodes
+            .iter()
+            .filter(|n| model.consumers(n.id).is_empty())
+            .map(|n| n.name.clone())
+            .collect();
+        let mut removed = vec![0; passes.len()];
+        loop {
+            let mut changed = false;
+            for (ix, pass) in passes.iter().enumerate() {
+                let rewritten = match *pass {
+                    Pass::FoldIdentities => model.fold_identities(),
+                    Pass::FoldConstants => model.fold_constants()?,
+                    Pass::FoldBatchNorm => model.fold_batchnorm_into_conv()?,
+                    Pass::EliminateCommonSubexpressions => model.eliminate_common_subexpressions(),
+                };
+                let pruned = model.prune(&outputs);
+                removed[ix] += pruned;
+                changed |= rewritten > 0 || pruned > 0;
+            }
+            if !changed {
+                break;
+            }
+        }
+        let report: Vec<(Pass, usize)> = passes.iter().cloned().zip(removed).collect();
+        for &(pass, removed) in &report {
+            info!("{:?} removed {} nodes.", pass, removed);
+        }
+        Ok((model, report))
+    }
+
+    /// Bypasses the `Identity` nodes, making their consumers use the input of
+    /// the identity instead. Returns the number of bypassed nodes.
+    fn fold_identities(&mut self) -> usize {
+        let mut folded = 0;
+        for id in 0..self.nodes.len() {
+            if self.nodes[id].op_name != "Identity" || self.nodes[id].inputs.len() != 1 {
+                continue;
+            }
+            let source = self.nodes[id].inputs[0];
+            let consumers = self.consumers(id);
+            for &c in &consumers {
+                for input in self.nodes[c].inputs.iter_mut().filter(|i| i.0 == id) {
+                    // Control dependencies stay control dependencies.
+                    *input = match input.1 {
+                        Some(_) => source,
+                        None => (source.0, None),
+                    };
+                }
+            }
+            if !consumers.is_empty() {
+                folded += 1;
+            }
+        }
+        folded
+    }
+
+    /// Evaluates the nodes whose inputs are all constant, and turns them into
+    /// `Const` nodes. Returns the number of folded nodes.
+    fn fold_constants(&mut self) -> Result<usize> {
+        let mut folded = 0;
+        for id in 0..self.nodes.len() {
+            let inputs = {
+                let node = &self.nodes[id];
+                if node.op_name == "Const" || node.inputs.is_empty() {
+                    continue;
+                }
+                if node.inputs.iter().any(|i| i.1.is_none()) {
+                    continue;
+                }
+                let inputs = (0..node.inputs.len())
+                    .map(|ix| self.const_input(node, ix).map(TensorView::from))
+                    .collect::<Option<Vec<_>>>();
+                match inputs {
+                    Some(inputs) => inputs,
+                    None => continue,
+                }
+            };
+            let mut outputs = match self.nodes[id].op.eval(inputs) {
+                Ok(outputs) => outputs,
+                // Leave the failure to the actual evaluation.
+                Err(_) => continue,
+            };
+            if outputs.len() != 1 {
+                continue;
+            }
+            let value = outputs.remove(0).into_tensor();
+            let node = &mut self.nodes[id];
+            node.op_name = "Const".to_string();
+            node.op = Arc::new(Box::new(Const::for_shared(Arc::new(value))));
+            node.inputs = vec![];
+            folded += 1;
+        }
+        Ok(folded)
+    }
+
+    /// Removes the nodes which are not needed to compute the given outputs,
+    /// and returns the number of removed nodes.
+    fn prune(&mut self, outputs: &HashSet<String>) -> usize {
+        let mut needed = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = self.nodes
+            .iter()
+            .filter(|n| outputs.contains(&n.name))
+            .map(|n| n.id)
+            .collect();
+        while let Some(id) = stack.pop() {
+            if !needed[id] {
+                needed[id] = true;
+                stack.extend(self.nodes[id].inputs.iter().map(|i| i.0));
+            }
+        }
+
+        let removed = needed.iter().filter(|&&n| !n).count();
+        if removed == 0 {
+            return 0;
+        }
+        let mut mapping = vec![None; self.nodes.len()];
+        let nodes: Vec<Node> = self.nodes.drain(..).filter(|n| needed[n.id]).collect();
+        for (new, node) in nodes.iter().enumerate() {
+            mapping[node.id] = Some(new);
+        }
+        self.nodes_by_name.clear();
+        self.annotations = self.annotations
+            .drain()
+            .filter_map(|((id, port), fact)| mapping[id].map(|id| ((id, port), fact)))
+            .collect();
+        for (new, mut node) in nodes.into_iter().enumerate() {
+            node.id = new;
+            for input in node.inputs.iter_mut() {
+                input.0 = mapping[input.0].unwrap();
+            }
+            self.nodes_by_name.insert(node.name.clone(), new);
+            self.nodes.push(node);
+        }
+        removed
+    }
+
+    /// Returns the ids of the nodes using an output of the given node.
+    fn consumers(&self, id: usize) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .filter(|n| n.inputs.iter().any(|i| i.0 == id))
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Returns the value of an input of a node, if it comes from a constant.
+    fn const_input(&self, node: &Node, ix: usize) -> Option<Tensor> {
+        node.inputs
+            .get(ix)
+            .and_then(|i| self.nodes[i.0].op.const_value())
+    }
+
+    fn add_const(&mut self, name: String, value: Tensor) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            id,
+            name: name.clone(),
+            op_name: "Const".to_string(),
+            inputs: vec![],
+            op: Arc::new(Box::new(Const::for_shared(Arc::new(value)))),
+        });
+        self.nodes_by_name.insert(name, id);
+        id
+    }
+
+    /// Folds the `FusedBatchNorm` nodes following a `Conv2D` into the filter
+    /// of the convolution and a bias addition.
+    ///
+    /// This only applies to f32 convolutions with a constant filter, which
+    /// have the batch normalization as their single consumer, and when all
+    /// the batch normalization parameters are constant. The batch
+    /// normalization node is turned into an `Add` node with the same name, so
+    /// it can still be used as an output. Returns the number of folded nodes.
+    pub fn fold_batchnorm_into_conv(&mut self) -> Result<usize> {
+        let mut folded = 0;
+        for bn in 0..self.nodes.len() {
+            if self.nodes[bn].op_name != "FusedBatchNorm" || self.nodes[bn].inputs.len() != 5 {
+                continue;
+            }
+            let conv = self.nodes[bn].inputs[0].0;
+            if self.nodes[conv].op_name != "Conv2D" || self.consumers(conv) != vec![bn] {
+                continue;
+            }
+            if !is_nhwc(&self.nodes[conv]) || !is_nhwc(&self.nodes[bn]) {
+                continue;
+            }
+            // The batch statistics outputs would disappear with the node.
+            let uses_statistics = self.nodes.iter().any(|n| {
+                n.inputs
+                    .iter()
+                    .any(|&(id, port)| id == bn && port.map_or(false, |p| p > 0))
+            });
+            if uses_statistics {
+                continue;
+            }
+            let filter = match self.const_input(&self.nodes[conv], 1) {
+                Some(Tensor::F32(filter)) => filter,
+                _ => continue,
+            };
+            let params = (1..5)
+                .map(|i| self.const_input(&self.nodes[bn], i).and_then(|t| t.take_f32s()))
+                .collect::<Option<Vec<_>>>();
+            let params = match params {
+                Some(params) => params,
+                None => continue,
+            };
+            let epsilon = match self.nodes[bn].op.get_attributes().get("epsilon") {
+                Some(&Attr::F32(epsilon)) => epsilon,
+                _ => continue,
+            };
+            let (factor, term) = FusedBatchNorm::new(epsilon).coefficients(
+                &params[0].view(),
+                &params[1].view(),
+                &params[2].view(),
+                &params[3].view(),
+            )?;
+            if filter.ndim() != 4 || filter.shape()[3] != factor.len() {
+                continue;
+            }
+
+            // Filters are HWIO, so the output channel is the last axis.
+            let filter = &filter * &factor;
+            let name = self.nodes[bn].name.clone();
+            let filter = self.add_const(format!("{}/folded_filter", name), filter.into());
+            let bias = self.add_const(format!("{}/folded_bias", name), term.into_dyn().into());
+
+            self.nodes[conv].inputs[1] = (filter, Some(0));
+            let add = OpBuilder::new().build(&::tfpb::node().op("Add").attr("T", DT_FLOAT))?;
+            let node = &mut self.nodes[bn];
+            node.op_name = "Add".to_string();
+            node.op = Arc::new(add);
+            node.inputs = vec![(conv, Some(0)), (bias, Some(0))];
+            folded += 1;
+        }
+        Ok(folded)
+    }
+
+    /// Makes the consumers of nodes which compute the same operation, with
+    /// the same attributes, on the same inputs use a single one of them.
+    /// Returns the number of nodes whose consumers were moved.
+    ///
+    /// Nodes without inputs are only merged when they are constants: two
+    /// placeholders with the same attributes are still different inputs.
+    /// Stateful nodes are never merged, and neither are nodes which report
+    /// no attributes, as there is no telling whether they really are equal.
+    pub fn eliminate_common_subexpressions(&mut self) -> usize {
+        let mut canonical: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut merged = 0;
+        for id in 0..self.nodes.len() {
+            if self.nodes[id].inputs.is_empty() && self.nodes[id].op.const_value().is_none() {
+                continue;
+            }
+            if !self.nodes[id].op.is_stateless() || self.nodes[id].op.get_attributes().is_empty() {
+                continue;
+            }
+            let candidates = canonical
+                .entry(subexpression_key(&self.nodes[id]))
+                .or_insert(vec![]);
+            let same = candidates
+                .iter()
+                .cloned()
+                .find(|&c| same_tensor_attributes(&self.nodes[c], &self.nodes[id]));
+            let same = match same {
+                Some(same) => same,
+                None => {
+                    candidates.push(id);
+                    continue;
+                }
+            };
+            let consumers = self.consumers(id);
+            for &c in &consumers {
+                for input in self.nodes[c].inputs.iter_mut().filter(|i| i.0 == id) {
+                    input.0 = same;
+                }
+            }
+            if !consumers.is_empty() {
+                merged += 1;
+            }
+        }
+        merged
+    }
+}
+
+/// Returns whether a node reports that it works on NHWC data.
+fn is_nhwc(node: &Node) -> bool {
+    match node.op.get_attributes().get("data_format") {
+        Some(&Attr::DataFormat(DataFormat::NHWC)) => true,
+        _ => false,
+    }
+}
+
+/// Returns a key which is the same for nodes computing the same operation on
+/// the same inputs. Tensor attributes are only hashed, so nodes with the same
+/// key must still be compared with `same_tensor_attributes`.
+fn subexpression_key(node: &Node) -> String {
+    let mut attributes: Vec<_> = node.op.get_attributes().into_iter().collect();
+    attributes.sort_by_key(|a| a.0);
+    let attributes: Vec<String> = attributes
+        .into_iter()
+        .map(|(name, value)| match value {
+            Attr::Tensor(t) => format!("{}={:x}", name, t.content_hash()),
+            value => format!("{}={:?}", name, value),
+        })
+        .collect();
+    // Control inputs only order the computations.
+    let inputs: Vec<_> = node.inputs.iter().filter(|i| i.1.is_some()).collect();
+    format!("{} {:?} {}", node.op_name, inputs, attributes.join(" "))
+}
+
+/// Returns whether the tensor attributes of two nodes are equal.
+fn same_tensor_attributes(a: &Node, b: &Node) -> bool {
+    let b_attributes = b.op.get_attributes();
+    a.op.get_attributes().iter().all(|(name, value)| match (value, b_attributes.get(name)) {
+        (&Attr::Tensor(ref a), Some(&Attr::Tensor(ref b))) => a == b,
+        _ => true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfpb;
+
+    fn konst(name: &str, shape: &[usize], values: &[f32]) -> ::tfpb::node_def::NodeDef {
+        tfpb::node()
+            .op("Const")
+            .name(name)
+            .attr("dtype", DT_FLOAT)
+            .attr("value", Tensor::f32s(shape, values).unwrap().to_pb().unwrap())
+    }
+
+    #[test]
+    fn optimize() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(konst("a", &[2], &[1.0, 2.0]))
+            .node(konst("b", &[2], &[3.0, 4.0]))
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("a+b")
+                    .attr("T", DT_FLOAT)
+                    .input("a")
+                    .input("b"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Identity")
+                    .name("id")
+                    .attr("T", DT_FLOAT)
+                    .input("a+b"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Identity")
+                    .name("input_id")
+                    .attr("T", DT_FLOAT)
+                    .input("input"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Mul")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input_id")
+                    .input("id"),
+            );
+        let model = Model::new(graph).unwrap();
+        let input = Tensor::f32s(&[2], &[1.0, -1.0]).unwrap();
+        let before = model
+            .run_with_names(vec![("input", input.clone())], "output")
+            .unwrap();
+
+        let (optimized, report) = model
+            .optimize(&[Pass::FoldIdentities, Pass::FoldConstants, Pass::FoldBatchNorm])
+            .unwrap();
+        // input, the folded a+b and output remain.
+        assert_eq!(optimized.nodes.len(), 3);
+        assert_eq!(
+            report,
+            vec![
+                (Pass::FoldIdentities, 2),
+                (Pass::FoldConstants, 2),
+                (Pass::FoldBatchNorm, 0),
+            ]
+        );
+        assert_eq!(optimized.get_node("a+b").unwrap().op_name, "Const");
+        let after = optimized
+            .run_with_names(vec![("input", input)], "output")
+            .unwrap();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn eliminate_common_subexpressions() {
+        let add = |name: &str| {
+            tfpb::node()
+                .op("Add")
+                .name(name)
+                .attr("T", DT_FLOAT)
+                .input("input")
+                .input("three")
+        };
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("other")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(konst("three", &[1], &[3.0]))
+            .node(add("left"))
+            .node(add("right"))
+            .node(
+                tfpb::node()
+                    .op("Mul")
+                    .name("product")
+                    .attr("T", DT_FLOAT)
+                    .input("left")
+                    .input("right"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("product")
+                    .input("other"),
+            );
+        let model = Model::new(graph).unwrap();
+        let inputs = vec![
+            ("input", Tensor::f32s(&[2], &[1.0, -1.0]).unwrap()),
+            ("other", Tensor::f32s(&[2], &[0.5, 0.5]).unwrap()),
+        ];
+        let before = model.run_with_names(inputs.clone(), "output").unwrap();
+
+        let (optimized, report) = model
+            .optimize(&[Pass::EliminateCommonSubexpressions])
+            .unwrap();
+        assert_eq!(report, vec![(Pass::EliminateCommonSubexpressions, 1)]);
+        assert!(optimized.get_node("right").is_err());
+        let left = optimized.node_id_by_name("left").unwrap();
+        let product = optimized.get_node("product").unwrap();
+        assert_eq!(product.inputs, vec![(left, Some(0)), (left, Some(0))]);
+        // The placeholders have the same attributes, but are kept apart.
+        assert!(optimized.get_node("other").is_ok());
+
+        let after = optimized.run_with_names(inputs, "output").unwrap();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn keep_apart_exact_and_approximate_tanh() {
+        let tanh = |name: &str, approximate: bool| {
+            tfpb::node()
+                .op("Tanh")
+                .name(name)
+                .attr("T", DT_FLOAT)
+                .attr("approximate", approximate)
+                .input("input")
+        };
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(tanh("exact", false))
+            .node(tanh("fast", true));
+        let (optimized, report) = Model::new(graph)
+            .unwrap()
+            .optimize(&[Pass::EliminateCommonSubexpressions])
+            .unwrap();
+        assert_eq!(report, vec![(Pass::EliminateCommonSubexpressions, 0)]);
+        assert!(optimized.get_node("exact").is_ok());
+        assert!(optimized.get_node("fast").is_ok());
+    }
+
+    #[test]
+    fn keep_apart_unimplemented_and_unchecked_ops() {
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(tfpb::node().op("RandomThing").name("first").input("input"))
+            .node(tfpb::node().op("RandomThing").name("second").input("input"))
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("sum")
+                    .attr("T", DT_FLOAT)
+                    .input("first")
+                    .input("second"),
+            )
+            .node(
+                tfpb::node()
+                    .op("CheckNumerics")
+                    .name("check_first")
+                    .attr("T", DT_FLOAT)
+                    .attr("message", "first")
+                    .input("sum"),
+            )
+            .node(
+                tfpb::node()
+                    .op("CheckNumerics")
+                    .name("check_second")
+                    .attr("T", DT_FLOAT)
+                    .attr("message", "second")
+                    .input("sum"),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("check_first")
+                    .input("check_second"),
+            );
+        let (optimized, report) = Model::new(graph)
+            .unwrap()
+            .optimize(&[Pass::EliminateCommonSubexpressions])
+            .unwrap();
+        assert_eq!(report, vec![(Pass::EliminateCommonSubexpressions, 0)]);
+        let first = optimized.node_id_by_name("first").unwrap();
+        let second = optimized.node_id_by_name("second").unwrap();
+        let sum = optimized.get_node("sum").unwrap();
+        assert_eq!(sum.inputs, vec![(first, Some(0)), (second, Some(0))]);
+        let check_first = optimized.node_id_by_name("check_first").unwrap();
+        let check_second = optimized.node_id_by_name("check_second").unwrap();
+        let output = optimized.get_node("output").unwrap();
+        assert_eq!(
+            output.inputs,
+            vec![(check_first, Some(0)), (check_second, Some(0))]
+        );
+    }
+
+    fn conv_batchnorm() -> ::tfpb::graph::GraphDef {
+        let filter: Vec<f32> = (0..18).map(|i| (i as f32 - 9.0) / 7.0).collect();
+        tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(konst("filter", &[3, 3, 1, 2], &filter))
+            .node(
+                tfpb::node()
+                    .op("Conv2D")
+                    .name("conv")
+                    .attr("T", DT_FLOAT)
+                    .attr("strides", vec![1, 1, 1, 1])
+                    .attr("padding", "SAME")
+                    .input("input")
+                    .input("filter"),
+            )
+            .node(konst("scale", &[2], &[0.5, 2.0]))
+            .node(konst("offset", &[2], &[1.0, -1.0]))
+            .node(konst("mean", &[2], &[0.2, -0.3]))
+            .node(konst("variance", &[2], &[1.5, 0.25]))
+            .node(
+                tfpb::node()
+                    .op("FusedBatchNorm")
+                    .name("bn")
+                    .attr("T", DT_FLOAT)
+                    .attr("epsilon", 0.001f32)
+                    .input("conv")
+                    .input("scale")
+                    .input("offset")
+                    .input("mean")
+                    .input("variance"),
+            )
+    }
+
+    #[test]
+    fn fold_batchnorm() {
+        let mut model = Model::new(conv_batchnorm()).unwrap();
+        let input: Vec<f32> = (0..25).map(|i| i as f32 / 5.0).collect();
+        let input = Tensor::f32s(&[1, 5, 5, 1], &input).unwrap();
+        let before = model
+            .run_with_names(vec![("input", input.clone())], "bn")
+            .unwrap();
+
+        assert_eq!(model.fold_batchnorm_into_conv().unwrap(), 1);
+        assert!(model.nodes.iter().all(|n| n.op_name != "FusedBatchNorm"));
+
+        let after = model.run_with_names(vec![("input", input)], "bn").unwrap();
+        assert!(before[0].close_enough(&after[0]).unwrap());
+    }
+    #[test]
+    fn fold_batchnorm_requires_nhwc() {
+        use ops::nn::conv2d::Conv2D;
+        use ops::nn::local_patch::{LocalPatch, Padding};
+        let mut model = Model::new(conv_batchnorm()).unwrap();
+        let conv = model.node_id_by_name("conv").unwrap();
+        let patch = LocalPatch::new(DataFormat::NCHW, Padding::Same, 1, 1);
+        model.nodes[conv].op = Arc::new(Box::new(Conv2D::<f32>::new(patch, 1)));
+        assert_eq!(model.fold_batchnorm_into_conv().unwrap(), 0);
+    }
+
+    #[test]
+    fn fold_batchnorm_keeps_used_statistics() {
+        let graph = conv_batchnorm().node(
+            tfpb::node()
+                .op("Identity")
+                .name("batch_mean")
+                .attr("T", DT_FLOAT)
+                .input("bn:1"),
+        );
+        let mut model = Model::new(graph).unwrap();
+        assert_eq!(model.fold_batchnorm_into_conv().unwrap(), 0);
+        assert_eq!(model.get_node("bn").unwrap().op_name, "FusedBatchNorm");
+    }
+}
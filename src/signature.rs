@@ -0,0 +1,112 @@
+//! The contract of a model: what it expects as inputs and what it produces.
+use analyser::prelude::*;
+use analyser::{Analyser, TensorFact};
+use {DataType, Model, Result};
+
+/// The name, datatype and shape of an input or output of a model, as far as
+/// the analyser could infer them.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorSignature {
+    pub name: String,
+    pub datatype: Option<DataType>,
+    /// The dimensions of the tensor, with None for the unknown ones, or None
+    /// if even the rank is unknown.
+    pub shape: Option<Vec<Option<usize>>>,
+}
+
+impl TensorSignature {
+    fn new(name: &str, fact: &TensorFact) -> TensorSignature {
+        let shape = if fact.shape.open {
+            None
+        } else {
+            Some(fact.shape.dims.iter().map(|d| d.concretize()).collect())
+        };
+        TensorSignature {
+            name: name.to_string(),
+            datatype: fact.datatype.concretize(),
+            shape,
+        }
+    }
+}
+
+/// The inputs and outputs of a model.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSignature {
+    /// The `Placeholder` nodes.
+    pub inputs: Vec<TensorSignature>,
+    /// The nodes whose result is not used by any other node.
+    pub outputs: Vec<TensorSignature>,
+}
+
+impl Model {
+    /// Returns the signature of the model, with the datatypes and shapes
+    /// the analyser can infer without knowing anything about the inputs.
+    pub fn signature(&self) -> Result<ModelSignature> {
+        let inputs: Vec<usize> = self.nodes
+            .iter()
+            .filter(|n| n.op_name == "Placeholder")
+            .map(|n| n.id)
+            .collect();
+        let outputs: Vec<usize> = self.nodes
+            .iter()
+            .filter(|n| !self.nodes.iter().any(|o| o.inputs.iter().any(|i| i.0 == n.id)))
+            .map(|n| n.id)
+            .collect();
+
+        // The analyser only follows the nodes which lead to its output, so
+        // the facts about the inputs are gathered from every output.
+        let mut input_facts = vec![TensorFact::new(); inputs.len()];
+        let mut output_facts = vec![];
+        for &output in &outputs {
+            let mut analyser = Analyser::new(self.clone(), output)?;
+            analyser.run()?;
+            let fact = |analyser: &Analyser, node: usize| -> TensorFact {
+                analyser.next_edges[node]
+                    .iter()
+                    .map(|&e| &analyser.edges[e])
+                    .find(|e| e.from_out == 0)
+                    .map(|e| e.fact.clone())
+                    .unwrap_or(TensorFact::new())
+            };
+            for (ix, &input) in inputs.iter().enumerate() {
+                input_facts[ix] = input_facts[ix].unify(&fact(&analyser, input))?;
+            }
+            output_facts.push(fact(&analyser, output));
+        }
+
+        let signature = |ids: &[usize], facts: &[TensorFact]| -> Vec<TensorSignature> {
+            ids.iter()
+                .zip(facts)
+                .map(|(&id, fact)| TensorSignature::new(&self.nodes[id].name, fact))
+                .collect()
+        };
+        Ok(ModelSignature {
+            inputs: signature(&inputs, &input_facts),
+            outputs: signature(&outputs, &output_facts),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plus3_signature() {
+        let model = Model::for_path("tests/models/plus3.pb").unwrap();
+        let signature = model.signature().unwrap();
+        assert_eq!(
+            signature.inputs,
+            vec![TensorSignature {
+                name: "input".to_string(),
+                datatype: Some(DataType::F32),
+                shape: None,
+            }]
+        );
+        assert_eq!(signature.outputs.len(), 1);
+        assert_eq!(signature.outputs[0].name, "output");
+        assert_eq!(signature.outputs[0].datatype, Some(DataType::F32));
+    }
+}
@@ -98,6 +98,21 @@ impl StreamingModel {
         }
 
         let model = analyser.into_model();
+
+        // Every node receiving a streamed input must be able to step.
+        for &(source, port) in dimensions.keys() {
+            for &(_, target) in successors[source].iter().filter(|s| s.0 == port) {
+                let node = model.get_node_by_id(target)?;
+                if !node.op.is_streamable() {
+                    bail!(
+                        "Node {} ({}) receives a streamed input, but doesn't support streaming.",
+                        node.name,
+                        node.op_name
+                    );
+                }
+            }
+        }
+
         Ok(StreamingModel {
             model,
             output,
@@ -170,6 +185,67 @@ impl<'a> StreamingModelState<'a> {
             queue.push_back((input, port, target, input_view.clone()));
         }
 
+        self.propagate(queue, &mut outputs, &mut node_step)?;
+
+        // Convert the output TensorViews to Tensors.
+        let outputs = outputs
+            .into_iter()
+            .map(|chunks| chunks.into_iter().map(|tv| tv.into_tensor()).collect())
+            .collect();
+
+        Ok(outputs)
+    }
+
+    /// Signals the end of the streamed inputs.
+    ///
+    /// Every node gets a chance to flush the chunks it was holding back, in
+    /// topological order, so that the flushed chunks of a node are stepped
+    /// through its successors before they get flushed in turn.
+    ///
+    /// Returns the remaining chunks produced by the output node, like `step`.
+    pub fn flush(&mut self) -> Result<Vec<Vec<Tensor>>> {
+        let mut outputs = vec![];
+        let order = Plan::for_model(&self.model.model, &[self.model.output])?.order;
+        let mut node_step = |node: &Node,
+                             inputs: Vec<(Option<usize>, Option<TensorView>)>,
+                             buffer: &mut Box<OpBuffer>| {
+            node.op.step(inputs, buffer)
+        };
+        for node in order {
+            let chunks = {
+                let node = self.model.model.get_node_by_id(node)?;
+                node.op.flush(&mut self.buffers[node.id])?
+            };
+            for output_chunks in chunks {
+                if node == self.model.output {
+                    outputs.push(output_chunks.clone());
+                }
+                let mut queue = VecDeque::new();
+                for &(port, successor) in &self.model.successors[node] {
+                    queue.push_back((node, port, successor, output_chunks[port].clone()));
+                }
+                self.propagate(queue, &mut outputs, &mut node_step)?;
+            }
+        }
+
+        Ok(outputs
+            .into_iter()
+            .map(|chunks| chunks.into_iter().map(|tv| tv.into_tensor()).collect())
+            .collect())
+    }
+
+    /// Steps chunks through the graph in breadth-first ordering, starting
+    /// with the given (source, port, target, chunk) edges.
+    fn propagate<W>(
+        &mut self,
+        mut queue: VecDeque<(usize, usize, usize, TensorView)>,
+        outputs: &mut Vec<Vec<TensorView>>,
+        node_step: &mut W,
+    ) -> Result<()>
+    where
+        W: FnMut(&Node, Vec<(Option<usize>, Option<TensorView>)>, &mut Box<OpBuffer>)
+            -> Result<Option<Vec<TensorView>>>,
+    {
         while let Some((source, port, target, chunk)) = queue.pop_front() {
             debug!(
                 "Executing new edge: source={:?}, port={:?}, target={:?}, chunk={:?}",
@@ -185,6 +261,10 @@ impl<'a> StreamingModelState<'a> {
             let mut chunk = Some(chunk);
 
             for &(k, kp) in &target.inputs {
+                // Control inputs only order the computations.
+                if kp.is_none() {
+                    continue;
+                }
                 let pred = self.model.model.get_node_by_id(k)?;
                 let dimension = self.model.dimensions.get(&(k, kp.unwrap_or(0))).map(|i| *i);
 
@@ -231,13 +311,7 @@ impl<'a> StreamingModelState<'a> {
             }
         }
 
-        // Convert the output TensorViews to Tensors.
-        let outputs = outputs
-            .into_iter()
-            .map(|chunks| chunks.into_iter().map(|tv| tv.into_tensor()).collect())
-            .collect();
-
-        Ok(outputs)
+        Ok(())
     }
 
     pub fn streaming_model(&self) -> &StreamingModel {
@@ -254,3 +328,103 @@ impl<'a> StreamingModelState<'a> {
             .collect::<Vec<_>>();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+    use tfpb::types::DataType::{DT_FLOAT, DT_INT32};
+
+    fn padded_model() -> Model {
+        let paddings = Tensor::from(arr2(&[[1, 2], [0, 0]]));
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Const")
+                    .name("paddings")
+                    .attr("dtype", DT_INT32)
+                    .attr("value", paddings.to_pb().unwrap()),
+            )
+            .node(
+                tfpb::node()
+                    .op("Pad")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("paddings"),
+            );
+        Model::new(graph).unwrap()
+    }
+
+    #[test]
+    fn flush_emits_trailing_padding() {
+        let model = padded_model();
+        let input = model.node_id_by_name("input").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let streaming = StreamingModel::new(
+            model,
+            vec![(input, StreamingInput::Streamed(DataType::F32, vec![None, Some(2)]))],
+            Some(output),
+        ).unwrap();
+        let mut state = streaming.state();
+
+        let mut chunks = vec![];
+        for i in 0..3 {
+            let chunk = Tensor::f32s(&[1, 2], &[i as f32, i as f32]).unwrap();
+            chunks.extend(state.step(input, chunk).unwrap());
+        }
+        // The last row is held back until the end of the stream is known.
+        assert_eq!(chunks.len(), 3);
+        chunks.extend(state.flush().unwrap());
+
+        let rows: Vec<f32> = chunks
+            .iter()
+            .map(|c| c[0].as_f32s().unwrap()[[0, 0]])
+            .collect();
+        assert_eq!(rows, vec![0.0, 0.0, 1.0, 2.0, 0.0, 0.0]);
+    }
+    #[test]
+    fn control_inputs() {
+        let one = Tensor::f32s(&[1], &[1.0]).unwrap();
+        let graph = tfpb::graph()
+            .node(
+                tfpb::node()
+                    .op("Placeholder")
+                    .name("input")
+                    .attr("dtype", DT_FLOAT),
+            )
+            .node(
+                tfpb::node()
+                    .op("Const")
+                    .name("one")
+                    .attr("dtype", DT_FLOAT)
+                    .attr("value", one.to_pb().unwrap()),
+            )
+            .node(
+                tfpb::node()
+                    .op("Add")
+                    .name("output")
+                    .attr("T", DT_FLOAT)
+                    .input("input")
+                    .input("one")
+                    .input("^one"),
+            );
+        let model = Model::new(graph).unwrap();
+        let input = model.node_id_by_name("input").unwrap();
+        let output = model.node_id_by_name("output").unwrap();
+        let streaming = StreamingModel::new(
+            model,
+            vec![(input, StreamingInput::Streamed(DataType::F32, vec![None]))],
+            Some(output),
+        ).unwrap();
+        let mut state = streaming.state();
+        let chunks = state.step(input, Tensor::f32s(&[1], &[2.0]).unwrap()).unwrap();
+        assert_eq!(chunks, vec![vec![Tensor::f32s(&[1], &[3.0]).unwrap()]]);
+    }
+}
@@ -1,18 +1,21 @@
 //! `Tensor` is the equivalent of Tensorflow Tensor.
 use ndarray::prelude::*;
+use std::borrow::Cow;
 use std::fmt;
 
 #[cfg(feature = "serialize")]
 use serde::ser::{Serialize, Serializer};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 pub enum DataType {
     U8,
     I8,
     I32,
+    I64,
     F32,
     F64,
+    Bool,
     String,
 }
 
@@ -23,8 +26,10 @@ impl DataType {
             &Tfpb::DT_UINT8 => Ok(DataType::U8),
             &Tfpb::DT_INT8 => Ok(DataType::I8),
             &Tfpb::DT_INT32 => Ok(DataType::I32),
+            &Tfpb::DT_INT64 => Ok(DataType::I64),
             &Tfpb::DT_FLOAT => Ok(DataType::F32),
             &Tfpb::DT_DOUBLE => Ok(DataType::F64),
+            &Tfpb::DT_BOOL => Ok(DataType::Bool),
             &Tfpb::DT_STRING => Ok(DataType::String),
             _ => Err(format!("Unknown DataType {:?}", t))?,
         }
@@ -36,13 +41,96 @@ impl DataType {
             DataType::U8 => Tfpb::DT_UINT8,
             DataType::I8 => Tfpb::DT_INT8,
             DataType::I32 => Tfpb::DT_INT32,
+            DataType::I64 => Tfpb::DT_INT64,
             DataType::F32 => Tfpb::DT_FLOAT,
             DataType::F64 => Tfpb::DT_DOUBLE,
+            DataType::Bool => Tfpb::DT_BOOL,
             DataType::String => Tfpb::DT_STRING,
         }
     }
+
+    /// Returns the size in bytes of one element, or None for strings.
+    pub fn size_of(&self) -> Option<usize> {
+        use std::mem::size_of;
+        match self {
+            DataType::U8 => Some(size_of::<u8>()),
+            DataType::I8 => Some(size_of::<i8>()),
+            DataType::I32 => Some(size_of::<i32>()),
+            DataType::I64 => Some(size_of::<i64>()),
+            DataType::F32 => Some(size_of::<f32>()),
+            DataType::F64 => Some(size_of::<f64>()),
+            DataType::Bool => Some(size_of::<bool>()),
+            DataType::String => None,
+        }
+    }
+
+    /// Returns the datatype both `self` and `other` can be converted to
+    /// without overflow, or None if one of them is not numeric.
+    ///
+    /// This follows numpy, except that integers never widen a float: an
+    /// `i32` and an `f32` promote to `f32`, as TensorFlow would do.
+    pub fn promote(self, other: DataType) -> Option<DataType> {
+        use self::DataType::*;
+        let rank = |dt| match dt {
+            U8 | I8 => Some(0),
+            I32 => Some(1),
+            I64 => Some(2),
+            F32 => Some(3),
+            F64 => Some(4),
+            Bool | String => None,
+        };
+        let (a, b) = (rank(self)?, rank(other)?);
+        Some(match (self, other) {
+            (U8, I8) | (I8, U8) => I32,
+            (F32, _) | (_, F32) if a.max(b) == 3 => F32,
+            _ if a >= b => self,
+            _ => other,
+        })
+    }
+}
+
+/// The arithmetic of the element-wise operators.
+///
+/// Integers wrap around on overflow, like in TensorFlow, instead of
+/// panicking in debug builds.
+pub trait WrappingOps: Sized {
+    fn add_wrapping(self, other: Self) -> Self;
+    fn sub_wrapping(self, other: Self) -> Self;
+    fn mul_wrapping(self, other: Self) -> Self;
+}
+
+macro_rules! wrapping_ops {
+    (int $($t:ty),*) => {
+        $(impl WrappingOps for $t {
+            fn add_wrapping(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+            fn sub_wrapping(self, other: Self) -> Self {
+                self.wrapping_sub(other)
+            }
+            fn mul_wrapping(self, other: Self) -> Self {
+                self.wrapping_mul(other)
+            }
+        })*
+    };
+    (float $($t:ty),*) => {
+        $(impl WrappingOps for $t {
+            fn add_wrapping(self, other: Self) -> Self {
+                self + other
+            }
+            fn sub_wrapping(self, other: Self) -> Self {
+                self - other
+            }
+            fn mul_wrapping(self, other: Self) -> Self {
+                self * other
+            }
+        })*
+    };
 }
 
+wrapping_ops!(int i8, u8, i32, i64);
+wrapping_ops!(float f32, f64);
+
 pub trait Datum:
     Copy
     + Clone
@@ -59,6 +147,7 @@ pub trait Datum:
     + ::std::ops::DivAssign
     + ::std::ops::SubAssign
     + ::std::ops::RemAssign
+    + WrappingOps
 {
     fn name() -> &'static str;
     fn datatype() -> DataType;
@@ -72,9 +161,12 @@ pub enum Tensor {
     F32(ArrayD<f32>),
     F64(ArrayD<f64>),
     I32(ArrayD<i32>),
+    I64(ArrayD<i64>),
     I8(ArrayD<i8>),
     U8(ArrayD<u8>),
-    String(ArrayD<i8>),
+    Bool(ArrayD<bool>),
+    /// TensorFlow strings are arbitrary bytes, not necessarily UTF-8.
+    String(ArrayD<Vec<u8>>),
 }
 
 impl Tensor {
@@ -91,31 +183,93 @@ impl Tensor {
         let content = t.get_tensor_content();
         let mat: Tensor = if content.len() != 0 {
             match dtype {
-                DT_FLOAT => Self::from_content::<f32, u8>(dims, content)?.into(),
-                DT_INT32 => Self::from_content::<i32, u8>(dims, content)?.into(),
-                _ => unimplemented!("missing type"),
+                DT_FLOAT => Self::from_content::<f32>(dims, content)?.into(),
+                DT_INT32 => Self::from_content::<i32>(dims, content)?.into(),
+                DT_INT64 => Self::from_content::<i64>(dims, content)?.into(),
+                DT_DOUBLE => Self::from_content::<f64>(dims, content)?.into(),
+                DT_INT8 => Self::from_content::<i8>(dims, content)?.into(),
+                DT_UINT8 => Self::from_content::<u8>(dims, content)?.into(),
+                // One byte per bool, which may not be a valid bool value.
+                DT_BOOL => Array1::from_iter(content.iter().map(|&b| b != 0))
+                    .into_shape(dims)?
+                    .into_dyn()
+                    .into(),
+                _ => bail!("Can not load a {:?} tensor from its content", dtype),
             }
         } else {
             match dtype {
-                DT_INT32 => Self::from_content::<i32, i32>(dims, t.get_int_val())?.into(),
-                DT_FLOAT => Self::from_content::<f32, f32>(dims, t.get_float_val())?.into(),
-                _ => unimplemented!("missing type"),
+                DT_INT32 => Self::from_values(dims, t.get_int_val())?.into(),
+                DT_INT64 => Self::from_values(dims, t.get_int64_val())?.into(),
+                DT_FLOAT => Self::from_values(dims, t.get_float_val())?.into(),
+                DT_DOUBLE => Self::from_values(dims, t.get_double_val())?.into(),
+                // Like TensorFlow, 8-bit integers are stored as int32 values.
+                DT_INT8 => {
+                    let values: Vec<i8> = t.get_int_val().iter().map(|&v| v as i8).collect();
+                    Self::from_values(dims, &values)?.into()
+                }
+                DT_UINT8 => {
+                    let values: Vec<u8> = t.get_int_val().iter().map(|&v| v as u8).collect();
+                    Self::from_values(dims, &values)?.into()
+                }
+                DT_BOOL => Self::from_values(dims, t.get_bool_val())?.into(),
+                DT_STRING => Tensor::String(Self::from_values(dims, t.get_string_val())?),
+                _ => bail!("Can not load a {:?} tensor", dtype),
             }
         };
         assert_eq!(rank, mat.shape().len());
         Ok(mat)
     }
 
-    pub fn from_content<T: Copy, V: Copy>(dims: Vec<usize>, content: &[V]) -> ::Result<ArrayD<T>> {
-        let value: &[T] = unsafe {
-            ::std::slice::from_raw_parts(
-                content.as_ptr() as _,
-                content.len() * ::std::mem::size_of::<V>() / ::std::mem::size_of::<T>(),
-            )
-        };
-        Ok(Array1::from_iter(value.iter().cloned())
-            .into_shape(dims)?
-            .into_dyn())
+    /// Builds an array from the raw bytes of a TensorProto content, in the
+    /// native endianness of the host.
+    ///
+    /// The bytes are copied, as they may not be aligned for `T`.
+    pub fn from_content<T: Copy + Default>(dims: Vec<usize>, content: &[u8]) -> ::Result<ArrayD<T>> {
+        // Building the array with its final shape directly also covers
+        // scalars, whose empty shape holds exactly one value.
+        let len = dims.iter().product::<usize>();
+        if content.len() != len * ::std::mem::size_of::<T>() {
+            bail!(
+                "Expected {} bytes for a tensor of shape {:?}, got {}",
+                len * ::std::mem::size_of::<T>(),
+                dims,
+                content.len()
+            );
+        }
+        let mut values = vec![T::default(); len];
+        unsafe {
+            ::std::ptr::copy_nonoverlapping(
+                content.as_ptr(),
+                values.as_mut_ptr() as *mut u8,
+                content.len(),
+            );
+        }
+        Ok(ArrayD::from_shape_vec(dims, values)?)
+    }
+
+    /// Builds an array from the typed values of a TensorProto.
+    ///
+    /// Like TensorFlow, this repeats the last value when there are fewer
+    /// values than elements (e.g. a single value for a constant fill), and
+    /// fills the array with zeros when there is no value at all.
+    fn from_values<T: Clone + Default>(dims: Vec<usize>, values: &[T]) -> ::Result<ArrayD<T>> {
+        let len = dims.iter().product::<usize>();
+        if values.len() > len {
+            bail!(
+                "Expected {} values for a tensor of shape {:?}, got {}",
+                len,
+                dims,
+                values.len()
+            );
+        }
+        let last = values.last().cloned().unwrap_or_default();
+        let values: Vec<T> = values
+            .iter()
+            .cloned()
+            .chain(::std::iter::repeat(last))
+            .take(len)
+            .collect();
+        Ok(ArrayD::from_shape_vec(dims, values)?)
     }
 
     pub fn to_pb(&self) -> ::Result<::tfpb::tensor::TensorProto> {
@@ -144,7 +298,26 @@ impl Tensor {
                 tensor.set_dtype(DataType::I32.to_pb());
                 tensor.set_int_val(it.iter().cloned().collect());
             }
-            _ => unimplemented!("missing type"),
+            &Tensor::I64(ref it) => {
+                tensor.set_dtype(DataType::I64.to_pb());
+                tensor.set_int64_val(it.iter().cloned().collect());
+            }
+            &Tensor::I8(ref it) => {
+                tensor.set_dtype(DataType::I8.to_pb());
+                tensor.set_int_val(it.iter().map(|&v| v as i32).collect());
+            }
+            &Tensor::U8(ref it) => {
+                tensor.set_dtype(DataType::U8.to_pb());
+                tensor.set_int_val(it.iter().map(|&v| v as i32).collect());
+            }
+            &Tensor::Bool(ref it) => {
+                tensor.set_dtype(DataType::Bool.to_pb());
+                tensor.set_bool_val(it.iter().cloned().collect());
+            }
+            &Tensor::String(ref it) => {
+                tensor.set_dtype(DataType::String.to_pb());
+                tensor.set_string_val(it.iter().cloned().collect());
+            }
         }
         Ok(tensor)
     }
@@ -154,9 +327,11 @@ impl Tensor {
             &Tensor::F64(ref it) => it.shape(),
             &Tensor::F32(ref it) => it.shape(),
             &Tensor::I32(ref it) => it.shape(),
+            &Tensor::I64(ref it) => it.shape(),
             &Tensor::I8(ref it) => it.shape(),
             &Tensor::U8(ref it) => it.shape(),
-            _ => unimplemented!("missing type"),
+            &Tensor::Bool(ref it) => it.shape(),
+            &Tensor::String(ref it) => it.shape(),
         }
     }
 
@@ -165,9 +340,11 @@ impl Tensor {
             &Tensor::F64(_) => DataType::F64,
             &Tensor::F32(_) => DataType::F32,
             &Tensor::I32(_) => DataType::I32,
+            &Tensor::I64(_) => DataType::I64,
             &Tensor::I8(_) => DataType::I8,
             &Tensor::U8(_) => DataType::U8,
-            _ => unimplemented!("missing type"),
+            &Tensor::Bool(_) => DataType::Bool,
+            &Tensor::String(_) => DataType::String,
         }
     }
 
@@ -184,12 +361,36 @@ impl Tensor {
                     self.datatype(),
                     a.as_slice().unwrap()[0]
                 ),
+                &Tensor::I64(ref a) => format!(
+                    "Scalar {:?} {:?}",
+                    self.datatype(),
+                    a.as_slice().unwrap()[0]
+                ),
                 &Tensor::U8(ref a) => format!(
                     "Scalar {:?} {:?}",
                     self.datatype(),
                     a.as_slice().unwrap()[0]
                 ),
-                _ => unimplemented!("missing type"),
+                &Tensor::Bool(ref a) => format!(
+                    "Scalar {:?} {:?}",
+                    self.datatype(),
+                    a.as_slice().unwrap()[0]
+                ),
+                &Tensor::F64(ref a) => format!(
+                    "Scalar {:?} {:?}",
+                    self.datatype(),
+                    a.as_slice().unwrap()[0]
+                ),
+                &Tensor::I8(ref a) => format!(
+                    "Scalar {:?} {:?}",
+                    self.datatype(),
+                    a.as_slice().unwrap()[0]
+                ),
+                &Tensor::String(ref a) => format!(
+                    "Scalar {:?} {:?}",
+                    self.datatype(),
+                    String::from_utf8_lossy(&a.as_slice().unwrap()[0])
+                ),
             })
         } else if self.shape().iter().product::<usize>() > 8 {
             Ok(format!("shape:{:?} {:?}", self.shape(), self.datatype()))
@@ -197,30 +398,297 @@ impl Tensor {
             Ok(match self {
                 &Tensor::I32(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
                 &Tensor::F32(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
+                &Tensor::I64(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
                 &Tensor::U8(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
-                _ => unimplemented!("missing type"),
+                &Tensor::Bool(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
+                &Tensor::F64(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
+                &Tensor::I8(ref a) => format!("{:?} {:?}", self.datatype(), a).replace("\n", " "),
+                &Tensor::String(ref a) => {
+                    let a = a.map(|s| String::from_utf8_lossy(s).into_owned());
+                    format!("{:?} {:?}", self.datatype(), a).replace("\n", " ")
+                }
             })
         }
     }
 
-    fn to_f32(&self) -> Tensor {
-        match self {
+    fn to_f32(&self) -> ::Result<Tensor> {
+        Ok(match self {
+            &Tensor::F64(ref data) => Tensor::F32(data.map(|&a| a as f32)),
             &Tensor::I32(ref data) => Tensor::F32(data.map(|&a| a as f32)),
+            &Tensor::I64(ref data) => Tensor::F32(data.map(|&a| a as f32)),
+            &Tensor::I8(ref data) => Tensor::F32(data.map(|&a| a as f32)),
+            &Tensor::U8(ref data) => Tensor::F32(data.map(|&a| a as f32)),
+            &Tensor::Bool(ref data) => Tensor::F32(data.map(|&a| a as u8 as f32)),
             &Tensor::F32(_) => self.clone(),
-            _ => unimplemented!("missing type"),
+            &Tensor::String(_) => bail!("String tensors can not be compared numerically"),
+        })
+    }
+
+    /// Returns the raw bytes of the tensor data.
+    ///
+    /// The bytes are in the native endianness of the host, and the elements
+    /// are laid out in row-major order. This fails for string tensors, and
+    /// for tensors which are not contiguous in memory.
+    pub fn as_bytes(&self) -> ::Result<&[u8]> {
+        fn bytes<T>(data: Option<&[T]>) -> ::Result<&[u8]> {
+            let data = data.ok_or("Tensor is not contiguous")?;
+            Ok(unsafe {
+                ::std::slice::from_raw_parts(
+                    data.as_ptr() as *const u8,
+                    data.len() * ::std::mem::size_of::<T>(),
+                )
+            })
+        }
+        match self {
+            &Tensor::F32(ref it) => bytes(it.as_slice()),
+            &Tensor::F64(ref it) => bytes(it.as_slice()),
+            &Tensor::I32(ref it) => bytes(it.as_slice()),
+            &Tensor::I64(ref it) => bytes(it.as_slice()),
+            &Tensor::I8(ref it) => bytes(it.as_slice()),
+            &Tensor::U8(ref it) => bytes(it.as_slice()),
+            &Tensor::Bool(ref it) => bytes(it.as_slice()),
+            &Tensor::String(_) => bail!("String tensors have no raw bytes representation"),
         }
     }
 
-    pub fn close_enough(&self, other: &Self) -> bool {
-        let ma = self.to_f32().take_f32s().unwrap();
-        let mb = other.to_f32().take_f32s().unwrap();
+    /// Hashes the datatype, shape and content of the tensor, so that equal
+    /// constants can be detected and shared.
+    ///
+    /// Float bits are not canonical: NaN has many representations, and 0.0
+    /// and -0.0 compare equal. To keep the hash deterministic, every NaN is
+    /// hashed as the same value, and so are both zeros. Two tensors with NaNs
+    /// at the same positions then hash the same although they are not equal.
+    pub fn content_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+        self.datatype().hash(&mut hasher);
+        self.shape().hash(&mut hasher);
+        macro_rules! hash_floats {
+            ($m:ident, $t:ident) => {
+                for &x in $m.iter() {
+                    let x = if x.is_nan() {
+                        ::std::$t::NAN
+                    } else if x == 0.0 {
+                        0.0
+                    } else {
+                        x
+                    };
+                    x.to_bits().hash(&mut hasher);
+                }
+            };
+        };
+        match self {
+            &Tensor::F32(ref m) => hash_floats!(m, f32),
+            &Tensor::F64(ref m) => hash_floats!(m, f64),
+            &Tensor::I32(ref m) => m.iter().for_each(|x| x.hash(&mut hasher)),
+            &Tensor::I64(ref m) => m.iter().for_each(|x| x.hash(&mut hasher)),
+            &Tensor::I8(ref m) => m.iter().for_each(|x| x.hash(&mut hasher)),
+            &Tensor::U8(ref m) => m.iter().for_each(|x| x.hash(&mut hasher)),
+            &Tensor::Bool(ref m) => m.iter().for_each(|x| x.hash(&mut hasher)),
+            &Tensor::String(ref m) => m.iter().for_each(|x| x.hash(&mut hasher)),
+        }
+        hasher.finish()
+    }
+
+    /// Builds a tensor from raw bytes, as exposed by `as_bytes`.
+    ///
+    /// The bytes must be in the native endianness of the host, in row-major
+    /// order. They are copied, so they do not need to be aligned.
+    pub fn from_raw_bytes(dt: DataType, shape: &[usize], data: &[u8]) -> ::Result<Tensor> {
+        fn array<T: Copy + Default>(shape: &[usize], data: &[u8]) -> ::Result<ArrayD<T>> {
+            Tensor::from_content(shape.to_vec(), data)
+        }
+        Ok(match dt {
+            DataType::F32 => array::<f32>(shape, data)?.into(),
+            DataType::F64 => array::<f64>(shape, data)?.into(),
+            DataType::I32 => array::<i32>(shape, data)?.into(),
+            DataType::I64 => array::<i64>(shape, data)?.into(),
+            DataType::I8 => array::<i8>(shape, data)?.into(),
+            DataType::U8 => array::<u8>(shape, data)?.into(),
+            DataType::Bool => {
+                if data.iter().any(|&b| b > 1) {
+                    bail!("Invalid byte for a bool tensor");
+                }
+                array::<bool>(shape, data)?.into()
+            }
+            DataType::String => bail!("String tensors can not be built from raw bytes"),
+        })
+    }
+
+    /// Compares two numeric tensors, with a margin derived from the spread
+    /// of the values of `self`.
+    ///
+    /// String tensors can only be compared with `==`, so this fails on them.
+    pub fn close_enough(&self, other: &Self) -> ::Result<bool> {
+        let ma = self.to_f32()?.take_f32s().unwrap();
+        let mb = other.to_f32()?.take_f32s().unwrap();
         let avg = ma.iter().map(|&a| a.abs()).sum::<f32>() / ma.len() as f32;
         let dev = (ma.iter().map(|&a| (a - avg).powi(2)).sum::<f32>() / ma.len() as f32).sqrt();
         let margin = (dev / 10.0).max(avg.abs() / 10_000.0);
-        ma.shape() == mb.shape()
+        Ok(ma.shape() == mb.shape()
             && mb.iter()
                 .zip(ma.iter())
-                .all(|(&a, &b)| (b - a).abs() <= margin)
+                .all(|(&a, &b)| (b - a).abs() <= margin))
+    }
+
+    /// Like `close_enough`, but two NaNs at the same index are considered
+    /// equal, and left out of the computation of the margin. A NaN facing
+    /// any other value is still a mismatch.
+    pub fn close_enough_nan_equal(&self, other: &Self) -> ::Result<bool> {
+        let ma = self.to_f32()?.take_f32s().unwrap();
+        let mb = other.to_f32()?.take_f32s().unwrap();
+        let nans = |m: &ArrayD<f32>| m.map(|a| a.is_nan());
+        if ma.shape() != mb.shape() || nans(&ma) != nans(&mb) {
+            return Ok(false);
+        }
+        let numbers = |m: &ArrayD<f32>| -> Tensor {
+            Array1::from_iter(m.iter().cloned().filter(|a| !a.is_nan())).into()
+        };
+        let (na, nb) = (numbers(&ma), numbers(&mb));
+        Ok(na.shape() == [0] || na.close_enough(&nb)?)
+    }
+
+    /// Returns the largest relative error between two numeric tensors of the
+    /// same shape, and the index where it occurs.
+    ///
+    /// The relative error of two values is their difference divided by the
+    /// largest of their magnitudes, so it is 0 for two zeros and 2 for
+    /// opposite values. Two NaNs are equal, and a NaN facing any other value
+    /// is an infinite error, as is an infinity facing a different value.
+    /// Empty tensors have no error and no index.
+    pub fn max_rel_error(&self, other: &Self) -> ::Result<(f64, Vec<usize>)> {
+        if self.shape() != other.shape() {
+            bail!(
+                "Can not compare tensors of shapes {:?} and {:?}",
+                self.shape(),
+                other.shape()
+            );
+        }
+        let ma = self.cast_to(DataType::F64)?.take_f64s().unwrap();
+        let mb = other.cast_to(DataType::F64)?.take_f64s().unwrap();
+        let mut worst = (0.0, vec![]);
+        for ((ix, &a), &b) in ma.indexed_iter().zip(mb.iter()) {
+            let error = if a.is_nan() || b.is_nan() {
+                if a.is_nan() && b.is_nan() {
+                    0.0
+                } else {
+                    ::std::f64::INFINITY
+                }
+            } else if a == b {
+                0.0
+            } else if a.is_infinite() || b.is_infinite() {
+                ::std::f64::INFINITY
+            } else {
+                (a - b).abs() / a.abs().max(b.abs())
+            };
+            if worst.1.is_empty() || error > worst.0 {
+                worst = (error, ix.slice().to_vec());
+            }
+        }
+        Ok(worst)
+    }
+
+    /// Returns the only element of the tensor, whatever its rank.
+    ///
+    /// Fails if the tensor doesn't have exactly one element, or doesn't hold
+    /// `T` values.
+    pub fn try_into_scalar<T: Datum>(&self) -> ::Result<T> {
+        let view = T::tensor_to_view(self).map_err(|_| {
+            format!(
+                "Expected a {} scalar, got a {:?} tensor",
+                T::name(),
+                self.datatype()
+            )
+        })?;
+        if view.len() != 1 {
+            bail!(
+                "Expected a single element, got a tensor of shape {:?}",
+                self.shape()
+            );
+        }
+        Ok(*view.iter().next().unwrap())
+    }
+
+    /// Applies `f` to every element of the tensor, which must hold `T`
+    /// values.
+    pub fn map<T: Datum, F: Fn(T) -> T>(&self, f: F) -> ::Result<Tensor> {
+        match T::tensor_to_view(self) {
+            Ok(view) => Ok(T::array_into_tensor(view.mapv(f))),
+            Err(_) => bail!(
+                "Can not map a {:?} tensor with a function on {}",
+                self.datatype(),
+                T::name()
+            ),
+        }
+    }
+
+    /// Applies `f` to every element of a numeric tensor, whatever its
+    /// datatype, by casting the elements to f64 and the results back.
+    pub fn map_any_numeric<F: Fn(f64) -> f64>(&self, f: F) -> ::Result<Tensor> {
+        macro_rules! map_inner {
+            ($v:ident, $t:ident, $m:ident) => {
+                Tensor::$v($m.mapv(|x| f(x as f64) as $t))
+            };
+        };
+
+        use Tensor::*;
+        Ok(match self {
+            &F32(ref m) => map_inner!(F32, f32, m),
+            &F64(ref m) => map_inner!(F64, f64, m),
+            &I32(ref m) => map_inner!(I32, i32, m),
+            &I64(ref m) => map_inner!(I64, i64, m),
+            &I8(ref m) => map_inner!(I8, i8, m),
+            &U8(ref m) => map_inner!(U8, u8, m),
+            &Bool(_) | &String(_) => bail!("Can not map a {:?} tensor", self.datatype()),
+        })
+    }
+
+    /// Converts the tensor to another numeric datatype, with the semantics
+    /// of `as` for the elements.
+    pub fn cast_to(&self, dt: DataType) -> ::Result<Tensor> {
+        macro_rules! cast_inner {
+            ($v:ident, $t:ident) => {
+                Tensor::$v(match self {
+                    &F32(ref m) => m.mapv(|x| x as $t),
+                    &F64(ref m) => m.mapv(|x| x as $t),
+                    &I32(ref m) => m.mapv(|x| x as $t),
+                    &I64(ref m) => m.mapv(|x| x as $t),
+                    &I8(ref m) => m.mapv(|x| x as $t),
+                    &U8(ref m) => m.mapv(|x| x as $t),
+                    &Bool(_) | &String(_) => {
+                        bail!("Can not cast a {:?} tensor", self.datatype())
+                    }
+                })
+            };
+        };
+
+        use Tensor::*;
+        if self.datatype() == dt {
+            return Ok(self.clone());
+        }
+        Ok(match dt {
+            DataType::F32 => cast_inner!(F32, f32),
+            DataType::F64 => cast_inner!(F64, f64),
+            DataType::I32 => cast_inner!(I32, i32),
+            DataType::I64 => cast_inner!(I64, i64),
+            DataType::I8 => cast_inner!(I8, i8),
+            DataType::U8 => cast_inner!(U8, u8),
+            DataType::Bool | DataType::String => bail!("Can not cast a tensor to {:?}", dt),
+        })
+    }
+
+    /// Compares a quantized tensor with a float one.
+    ///
+    /// The u8 side of the comparison is first dequantized, mapping 0 to
+    /// `min` and 255 to `max`. The other side is compared as is.
+    pub fn close_enough_dequant(&self, other: &Self, min: f32, max: f32) -> ::Result<bool> {
+        let dequant = |t: &Tensor| match t {
+            &Tensor::U8(ref q) => {
+                Tensor::F32(q.map(|&q| min + q as f32 * (max - min) / 255.0))
+            }
+            t => t.clone(),
+        };
+        dequant(self).close_enough(&dequant(other))
     }
 }
 
@@ -273,15 +741,49 @@ impl Serialize for Tensor {
             F32(m) => serialize_inner!(f32, m),
             F64(m) => serialize_inner!(f64, m),
             I32(m) => serialize_inner!(i32, m),
+            I64(m) => serialize_inner!(i64, m),
             I8(m) => serialize_inner!(i8, m),
             U8(m) => serialize_inner!(u8, m),
-            String(m) => serialize_inner!(str, m),
+            Bool(m) => serialize_inner!(bool, m),
+            String(m) => serialize_inner!(string, m),
         }
     }
 }
 
+fn mismatch(expected: DataType, found: DataType) -> ::Error {
+    format!("Expected a tensor of datatype {:?}, found {:?}", expected, found).into()
+}
+
 macro_rules! tensor {
     ($t:ident, $v:ident, $as:ident, $take:ident, $make:ident) => {
+        tensor!(@array $t, $v, $as, $take, $make);
+
+        impl Datum for $t {
+            fn name() -> &'static str {
+                stringify!($t)
+            }
+
+            fn datatype() -> DataType {
+                DataType::$v
+            }
+
+            fn tensor_into_array(m: Tensor) -> ::Result<ArrayD<Self>> {
+                let found = m.datatype();
+                m.$take().ok_or_else(|| mismatch(DataType::$v, found))
+            }
+
+            fn tensor_to_view(m: &Tensor) -> ::Result<ArrayViewD<Self>> {
+                m.$as()
+                    .map(|m| m.view())
+                    .ok_or_else(|| mismatch(DataType::$v, m.datatype()))
+            }
+
+            fn array_into_tensor(m: ArrayD<Self>) -> Tensor {
+                Tensor::from(m)
+            }
+        }
+    };
+    (@array $t:ident, $v:ident, $as:ident, $take:ident, $make:ident) => {
         impl<D: ::ndarray::Dimension> From<Array<$t, D>> for Tensor {
             fn from(it: Array<$t, D>) -> Tensor {
                 Tensor::$v(it.into_dyn())
@@ -319,38 +821,17 @@ macro_rules! tensor {
                 }
             }
         }
-
-        impl Datum for $t {
-            fn name() -> &'static str {
-                stringify!($t)
-            }
-
-            fn datatype() -> DataType {
-                DataType::$v
-            }
-
-            fn tensor_into_array(m: Tensor) -> ::Result<ArrayD<Self>> {
-                m.$take().ok_or("unmatched data type".into())
-            }
-
-            fn tensor_to_view(m: &Tensor) -> ::Result<ArrayViewD<Self>> {
-                m.$as()
-                    .map(|m| m.view())
-                    .ok_or("unmatched data type".into())
-            }
-
-            fn array_into_tensor(m: ArrayD<Self>) -> Tensor {
-                Tensor::from(m)
-            }
-        }
     };
 }
 
 tensor!(f64, F64, as_f64s, take_f64s, f64s);
 tensor!(f32, F32, as_f32s, take_f32s, f32s);
 tensor!(i32, I32, as_i32s, take_i32s, i32s);
+tensor!(i64, I64, as_i64s, take_i64s, i64s);
 tensor!(u8, U8, as_u8s, take_u8s, u8s);
 tensor!(i8, I8, as_i8s, take_i8s, i8s);
+// Booleans are not numbers, so they are not a `Datum`.
+tensor!(@array bool, Bool, as_bools, take_bools, bools);
 
 #[macro_export]
 macro_rules! map_tensor {
@@ -360,9 +841,655 @@ macro_rules! map_tensor {
             F64($array) => F64($return),
             F32($array) => F32($return),
             I32($array) => I32($return),
+            I64($array) => I64($return),
             I8($array) => I8($return),
             U8($array) => U8($return),
+            Bool($array) => Bool($return),
             String($array) => String($return),
         }
     }};
 }
+
+impl Tensor {
+    /// Permutes the axes of the tensor, like the `Transpose` operator: axis
+    /// `i` of the result is axis `perm[i]` of the tensor.
+    pub fn permute_axes(&self, perm: &[usize]) -> ::Result<Tensor> {
+        let mut sorted = perm.to_vec();
+        sorted.sort();
+        if sorted != (0..self.shape().len()).collect::<Vec<_>>() {
+            bail!(
+                "{:?} is not a permutation of the axes of a tensor of rank {}",
+                perm,
+                self.shape().len()
+            );
+        }
+        Ok(map_tensor!(self.clone(), |a| {
+            let a = a.permuted_axes(perm);
+            // Store the result in standard layout.
+            ArrayD::from_shape_vec(a.shape(), a.iter().cloned().collect()).unwrap()
+        }))
+    }
+
+    /// Stacks tensors of the same datatype and shape along a new axis, like
+    /// the `Pack` operator.
+    pub fn stack(tensors: &[&Tensor], axis: usize) -> ::Result<Tensor> {
+        let first = tensors.get(0).ok_or("Can not stack an empty list of tensors")?;
+        if axis > first.shape().len() {
+            bail!(
+                "Can not stack tensors of rank {} along axis {}",
+                first.shape().len(),
+                axis
+            );
+        }
+        for t in tensors {
+            if t.datatype() != first.datatype() || t.shape() != first.shape() {
+                bail!(
+                    "Can not stack a {:?} tensor of shape {:?} with a {:?} tensor of shape {:?}",
+                    first.datatype(),
+                    first.shape(),
+                    t.datatype(),
+                    t.shape()
+                );
+            }
+        }
+
+        // `ndarray::stack` only handles `Copy` elements, which strings are
+        // not: the arrays are stacked along a new first axis, which is then
+        // moved in place.
+        fn stack_views<T: Clone>(arrays: Vec<&ArrayD<T>>, axis: usize) -> ::Result<ArrayD<T>> {
+            let mut shape = vec![arrays.len()];
+            shape.extend(arrays[0].shape());
+            let data = arrays.iter().flat_map(|a| a.iter().cloned()).collect();
+            let mut perm: Vec<usize> = (1..shape.len()).collect();
+            perm.insert(axis, 0);
+            let stacked = ArrayD::from_shape_vec(shape, data)?.permuted_axes(perm);
+            Ok(::ops::standard_layout(&stacked))
+        }
+
+        macro_rules! stack_inner {
+            ($v:ident) => {
+                Tensor::$v(stack_views(
+                    tensors
+                        .iter()
+                        .map(|t| match t {
+                            &&Tensor::$v(ref a) => a,
+                            _ => unreachable!(), // checked above
+                        })
+                        .collect(),
+                    axis,
+                )?)
+            };
+        };
+
+        use Tensor::*;
+        Ok(match first {
+            &&F32(_) => stack_inner!(F32),
+            &&F64(_) => stack_inner!(F64),
+            &&I32(_) => stack_inner!(I32),
+            &&I64(_) => stack_inner!(I64),
+            &&I8(_) => stack_inner!(I8),
+            &&U8(_) => stack_inner!(U8),
+            &&Bool(_) => stack_inner!(Bool),
+            &&String(_) => stack_inner!(String),
+        })
+    }
+
+    /// Pads a tensor with `value`, adding `before[i]` and `after[i]`
+    /// elements around its axis `i`, like the `Pad` operator.
+    pub fn pad<T: Datum>(&self, before: &[usize], after: &[usize], value: T) -> ::Result<Tensor> {
+        let data = T::tensor_to_view(self)?;
+        let rank = data.ndim();
+        if before.len() != rank || after.len() != rank {
+            bail!(
+                "Can not pad a tensor of rank {} with {:?} before and {:?} after",
+                rank,
+                before,
+                after
+            );
+        }
+        let shape: Vec<usize> = (0..rank)
+            .map(|i| before[i] + data.shape()[i] + after[i])
+            .collect();
+        let mut inner = vec![0; rank];
+        let padded = ArrayD::from_shape_fn(shape, |index| {
+            for i in 0..rank {
+                match index[i].checked_sub(before[i]) {
+                    Some(x) if x < data.shape()[i] => inner[i] = x,
+                    _ => return value,
+                }
+            }
+            data[&*inner]
+        });
+        Ok(T::array_into_tensor(padded))
+    }
+
+    /// Returns the index of the largest value along the last axis, like the
+    /// `ArgMax` operator, e.g. to decode the logits of a classifier.
+    ///
+    /// The first index wins ties.
+    pub fn argmax_last_axis(&self) -> ::Result<Tensor> {
+        self.arg_last_axis(true)
+    }
+
+    /// Returns the index of the smallest value along the last axis.
+    ///
+    /// The first index wins ties.
+    pub fn argmin_last_axis(&self) -> ::Result<Tensor> {
+        self.arg_last_axis(false)
+    }
+
+    fn arg_last_axis(&self, max: bool) -> ::Result<Tensor> {
+        fn arg<T: PartialOrd + Copy>(a: &ArrayD<T>, max: bool) -> ::Result<Tensor> {
+            let axis = match a.shape().last() {
+                Some(&0) | None => bail!(
+                    "Can not find an extremum in a tensor of shape {:?}",
+                    a.shape()
+                ),
+                Some(_) => a.ndim() - 1,
+            };
+            // Folds (position, best position, best value) along the axis.
+            let init = (0, 0, None);
+            let found = a.fold_axis(Axis(axis), init, |acc: &(i32, i32, Option<T>), &x| {
+                match *acc {
+                    (i, best, Some(m)) if !(max && x > m) && !(!max && x < m) => {
+                        (i + 1, best, Some(m))
+                    }
+                    (i, _, _) => (i + 1, i, Some(x)),
+                }
+            });
+            Ok(found.mapv(|(_, best, _)| best).into())
+        }
+
+        use Tensor::*;
+        match self {
+            &F32(ref a) => arg(a, max),
+            &F64(ref a) => arg(a, max),
+            &I32(ref a) => arg(a, max),
+            &I64(ref a) => arg(a, max),
+            &I8(ref a) => arg(a, max),
+            &U8(ref a) => arg(a, max),
+            _ => bail!("Can not find an extremum in a {:?} tensor", self.datatype()),
+        }
+    }
+
+    /// Extracts the array of a tensor of datatype `T`, failing with a
+    /// descriptive error for any other datatype.
+    pub fn to_array<T: Datum>(self) -> ::Result<ArrayD<T>> {
+        T::tensor_into_array(self)
+    }
+
+    /// Borrows the array of a tensor of datatype `T`, failing with a
+    /// descriptive error for any other datatype.
+    pub fn view_as<T: Datum>(&self) -> ::Result<ArrayViewD<T>> {
+        T::tensor_to_view(self)
+    }
+
+    /// Returns the values of the tensor as a standard layout f32 array, e.g.
+    /// to feed a matrix product.
+    ///
+    /// A f32 tensor already in standard layout is borrowed. Any other tensor
+    /// is cast or copied once.
+    pub(crate) fn as_f32_contiguous(&self) -> ::Result<Cow<ArrayD<f32>>> {
+        match self {
+            &Tensor::F32(ref a) if a.is_standard_layout() => Ok(Cow::Borrowed(a)),
+            &Tensor::F32(ref a) => Ok(Cow::Owned(::ops::standard_layout(a))),
+            _ => {
+                let a = self.cast_to(DataType::F32)?.take_f32s().unwrap(); // cast above
+                if a.is_standard_layout() {
+                    Ok(Cow::Owned(a))
+                } else {
+                    Ok(Cow::Owned(::ops::standard_layout(&a)))
+                }
+            }
+        }
+    }
+
+    /// Returns a tensor of zeros with the datatype and shape of this one.
+    pub fn zeros_like(&self) -> Tensor {
+        map_tensor!(self, |a| ArrayD::from_elem(a.shape(), Element::zero()))
+    }
+
+    /// Returns a tensor of ones with the datatype and shape of this one.
+    ///
+    /// Fails on string tensors, which have no one.
+    pub fn ones_like(&self) -> ::Result<Tensor> {
+        let one = || format!("{:?} tensors have no one", self.datatype());
+        Ok(map_tensor!(self, |a| {
+            ArrayD::from_elem(a.shape(), Element::one().ok_or_else(one)?)
+        }))
+    }
+
+    /// Returns the sum of the elements of a numeric tensor, accumulated in
+    /// f64.
+    pub fn sum_f64(&self) -> ::Result<f64> {
+        use Tensor::*;
+        Ok(match self {
+            &F32(ref a) => a.iter().map(|&x| x as f64).sum(),
+            &F64(ref a) => a.iter().sum(),
+            &I32(ref a) => a.iter().map(|&x| x as f64).sum(),
+            &I64(ref a) => a.iter().map(|&x| x as f64).sum(),
+            &I8(ref a) => a.iter().map(|&x| x as f64).sum(),
+            &U8(ref a) => a.iter().map(|&x| x as f64).sum(),
+            _ => bail!("Can not sum a {:?} tensor", self.datatype()),
+        })
+    }
+
+    /// Returns the mean of the elements of a numeric tensor, accumulated in
+    /// f64.
+    pub fn mean_f64(&self) -> ::Result<f64> {
+        let len = self.shape().iter().product::<usize>();
+        if len == 0 {
+            bail!("Can not compute the mean of an empty tensor");
+        }
+        Ok(self.sum_f64()? / len as f64)
+    }
+}
+
+/// The zero and one of every tensor element type, booleans included.
+trait Element: Sized {
+    fn zero() -> Self;
+    fn one() -> Option<Self>;
+}
+
+macro_rules! numeric_element {
+    ($($t:ty),*) => {
+        $(impl Element for $t {
+            fn zero() -> $t {
+                0 as $t
+            }
+
+            fn one() -> Option<$t> {
+                Some(1 as $t)
+            }
+        })*
+    };
+}
+
+numeric_element!(f32, f64, i32, i64, i8, u8);
+
+// The zero of strings is the empty string, and they have no one.
+impl Element for Vec<u8> {
+    fn zero() -> Vec<u8> {
+        vec![]
+    }
+
+    fn one() -> Option<Vec<u8>> {
+        None
+    }
+}
+
+impl Element for bool {
+    fn zero() -> bool {
+        false
+    }
+
+    fn one() -> Option<bool> {
+        Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_enough_dequant() {
+        let float = Tensor::f32s(&[4], &[-1.0, 0.0, 0.5, 1.0]).unwrap();
+        let quant = Tensor::u8s(&[4], &[0, 128, 191, 255]).unwrap();
+        assert!(quant.close_enough_dequant(&float, -1.0, 1.0).unwrap());
+        assert!(float.close_enough_dequant(&quant, -1.0, 1.0).unwrap());
+        assert!(!quant.close_enough_dequant(&float, 0.0, 2.0).unwrap());
+    }
+
+    #[test]
+    fn stack() {
+        let a = Tensor::f32s(&[3], &[1.0, 2.0, 3.0]).unwrap();
+        let b = Tensor::f32s(&[3], &[4.0, 5.0, 6.0]).unwrap();
+        let c = Tensor::f32s(&[3], &[7.0, 8.0, 9.0]).unwrap();
+        assert_eq!(
+            Tensor::stack(&[&a, &b, &c], 0).unwrap(),
+            Tensor::from(arr2(&[[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]))
+        );
+        assert_eq!(
+            Tensor::stack(&[&a, &b, &c], 1).unwrap(),
+            Tensor::from(arr2(&[[1.0f32, 4.0, 7.0], [2.0, 5.0, 8.0], [3.0, 6.0, 9.0]]))
+        );
+
+        let short = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap();
+        assert!(Tensor::stack(&[&a, &short], 0).is_err());
+        let ints = Tensor::i32s(&[3], &[1, 2, 3]).unwrap();
+        assert!(Tensor::stack(&[&a, &ints], 0).is_err());
+        assert!(Tensor::stack(&[&a], 2).is_err());
+    }
+
+    #[test]
+    fn pad() {
+        let t = Tensor::f32s(&[2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(
+            t.pad(&[1, 0], &[1, 0], 0.0f32).unwrap(),
+            Tensor::from(arr2(&[[0.0f32, 0.0], [1.0, 2.0], [3.0, 4.0], [0.0, 0.0]]))
+        );
+        assert_eq!(
+            t.pad(&[0, 1], &[0, 0], 9.0f32).unwrap(),
+            Tensor::from(arr2(&[[9.0f32, 1.0, 2.0], [9.0, 3.0, 4.0]]))
+        );
+        assert!(t.pad(&[1], &[1], 0.0f32).is_err());
+        assert!(t.pad(&[1, 0], &[1, 0], 0i32).is_err());
+    }
+
+    #[test]
+    fn argmax_and_argmin() {
+        let logits = Tensor::f32s(&[1, 5], &[0.1, 2.5, -1.0, 2.5, 0.3]).unwrap();
+        assert_eq!(logits.argmax_last_axis().unwrap(), Tensor::i32s(&[1], &[1]).unwrap());
+        assert_eq!(logits.argmin_last_axis().unwrap(), Tensor::i32s(&[1], &[2]).unwrap());
+        let ints = Tensor::from(arr2(&[[3, 1, 2], [4, 6, 5]]));
+        assert_eq!(ints.argmax_last_axis().unwrap(), Tensor::i32s(&[2], &[0, 1]).unwrap());
+        assert!(Tensor::f32s(&[2, 0], &[]).unwrap().argmax_last_axis().is_err());
+        assert!(Tensor::f32s(&[], &[1.0]).unwrap().argmax_last_axis().is_err());
+    }
+
+    #[test]
+    fn typed_extraction() {
+        let t = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap();
+        assert_eq!(t.view_as::<f32>().unwrap(), arr1(&[1.0f32, 2.0]).into_dyn().view());
+        let err = t.view_as::<i32>().unwrap_err();
+        assert_eq!(err.to_string(), "Expected a tensor of datatype I32, found F32");
+        let err = t.clone().to_array::<i32>().unwrap_err();
+        assert_eq!(err.to_string(), "Expected a tensor of datatype I32, found F32");
+        assert_eq!(t.to_array::<f32>().unwrap(), arr1(&[1.0f32, 2.0]).into_dyn());
+    }
+
+    #[test]
+    fn zeros_and_ones_like() {
+        let t = Tensor::f32s(&[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(t.zeros_like(), Tensor::f32s(&[2, 3], &[0.0; 6]).unwrap());
+        assert_eq!(t.ones_like().unwrap(), Tensor::f32s(&[2, 3], &[1.0; 6]).unwrap());
+
+        let t = Tensor::i64s(&[4], &[-1, 2, -3, 4]).unwrap();
+        assert_eq!(t.zeros_like(), Tensor::i64s(&[4], &[0; 4]).unwrap());
+        assert_eq!(t.ones_like().unwrap(), Tensor::i64s(&[4], &[1; 4]).unwrap());
+
+        let t = Tensor::from(arr1(&[true, false]));
+        assert_eq!(t.zeros_like(), Tensor::from(arr1(&[false, false])));
+        assert_eq!(t.ones_like().unwrap().datatype(), DataType::Bool);
+
+        let t = Tensor::String(arr1(&[b"a".to_vec()]).into_dyn());
+        assert_eq!(t.zeros_like(), Tensor::String(arr1(&[vec![]]).into_dyn()));
+        assert!(t.ones_like().is_err());
+    }
+
+    #[test]
+    fn from_pb_repeats_last_value() {
+        let dims = |dims: &[i64]| {
+            let mut shape = ::tfpb::tensor_shape::TensorShapeProto::new();
+            for &d in dims {
+                let mut dim = ::tfpb::tensor_shape::TensorShapeProto_Dim::new();
+                dim.size = d;
+                shape.mut_dim().push(dim);
+            }
+            shape
+        };
+        let mut proto = ::tfpb::tensor::TensorProto::new();
+        proto.set_dtype(DataType::F32.to_pb());
+        proto.set_tensor_shape(dims(&[2, 3]));
+        proto.set_float_val(vec![1.0, 2.0]);
+        assert_eq!(
+            Tensor::from_pb(&proto).unwrap(),
+            Tensor::f32s(&[2, 3], &[1.0, 2.0, 2.0, 2.0, 2.0, 2.0]).unwrap()
+        );
+
+        let mut proto = ::tfpb::tensor::TensorProto::new();
+        proto.set_dtype(DataType::I32.to_pb());
+        proto.set_tensor_shape(dims(&[2]));
+        assert_eq!(Tensor::from_pb(&proto).unwrap(), Tensor::i32s(&[2], &[0, 0]).unwrap());
+
+        let value = Tensor::f64s(&[2], &[0.5, 1.5]).unwrap();
+        assert_eq!(Tensor::from_pb(&value.to_pb().unwrap()).unwrap(), value);
+    }
+
+    #[test]
+    fn from_pb_content() {
+        let mut proto = ::tfpb::tensor::TensorProto::new();
+        let mut shape = ::tfpb::tensor_shape::TensorShapeProto::new();
+        let mut dim = ::tfpb::tensor_shape::TensorShapeProto_Dim::new();
+        dim.size = 2;
+        shape.mut_dim().push(dim);
+        proto.set_tensor_shape(shape);
+
+        let bytes = Tensor::f64s(&[2], &[0.5, -1.5]).unwrap().as_bytes().unwrap().to_vec();
+        proto.set_dtype(DataType::F64.to_pb());
+        proto.set_tensor_content(bytes.clone());
+        assert_eq!(Tensor::from_pb(&proto).unwrap(), Tensor::f64s(&[2], &[0.5, -1.5]).unwrap());
+        proto.set_tensor_content(bytes[..8].to_vec());
+        assert!(Tensor::from_pb(&proto).is_err());
+
+        proto.set_dtype(DataType::U8.to_pb());
+        proto.set_tensor_content(vec![3, 255]);
+        assert_eq!(Tensor::from_pb(&proto).unwrap(), Tensor::u8s(&[2], &[3, 255]).unwrap());
+        proto.set_dtype(DataType::String.to_pb());
+        assert!(Tensor::from_pb(&proto).is_err());
+        proto.set_dtype(::tfpb::types::DataType::DT_HALF);
+        proto.clear_tensor_content();
+        assert!(Tensor::from_pb(&proto).is_err());
+
+        for value in &[
+            Tensor::i8s(&[2], &[-3, 4]).unwrap(),
+            Tensor::u8s(&[2], &[3, 255]).unwrap(),
+        ] {
+            assert_eq!(&Tensor::from_pb(&value.to_pb().unwrap()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn sum_and_mean_f64() {
+        let t = Tensor::i32s(&[2, 2], &[1, 2, 3, 2_000_000_000]).unwrap();
+        assert_eq!(t.sum_f64().unwrap(), 2_000_000_006.0);
+        assert_eq!(t.mean_f64().unwrap(), 500_000_001.5);
+
+        let t = Tensor::f32s(&[4], &[0.5, 1.5, -1.0, 3.0]).unwrap();
+        assert_eq!(t.sum_f64().unwrap(), 4.0);
+        assert_eq!(t.mean_f64().unwrap(), 1.0);
+
+        assert!(Tensor::f32s(&[0], &[]).unwrap().mean_f64().is_err());
+        assert!(Tensor::from(arr1(&[true])).sum_f64().is_err());
+    }
+
+    #[test]
+    fn try_into_scalar() {
+        assert_eq!(Tensor::f32s(&[], &[2.5]).unwrap().try_into_scalar::<f32>().unwrap(), 2.5);
+        assert_eq!(Tensor::i32s(&[1, 1], &[3]).unwrap().try_into_scalar::<i32>().unwrap(), 3);
+
+        let error = Tensor::f32s(&[2], &[1.0, 2.0]).unwrap().try_into_scalar::<f32>();
+        assert!(format!("{}", error.unwrap_err()).contains("single element"));
+        let error = Tensor::f32s(&[0], &[]).unwrap().try_into_scalar::<f32>();
+        assert!(error.is_err());
+        let error = Tensor::f32s(&[], &[1.0]).unwrap().try_into_scalar::<i32>();
+        assert!(format!("{}", error.unwrap_err()).contains("i32 scalar"));
+    }
+
+    #[test]
+    fn content_hash() {
+        let a = Tensor::f32s(&[2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        let b = Tensor::f32s(&[2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(a.content_hash(), b.content_hash());
+        let other = Tensor::f32s(&[2, 2], &[1.0, 2.0, 3.0, 5.0]).unwrap();
+        assert_ne!(a.content_hash(), other.content_hash());
+        let reshaped = Tensor::f32s(&[4], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_ne!(a.content_hash(), reshaped.content_hash());
+
+        let nan = Tensor::f32s(&[2], &[::std::f32::NAN, 0.0]).unwrap();
+        let other_nan = Tensor::f32s(&[2], &[-::std::f32::NAN, -0.0]).unwrap();
+        assert_eq!(nan.content_hash(), other_nan.content_hash());
+    }
+
+    #[test]
+    fn bool_from_pb() {
+        let mask = Tensor::bools(&[3], &[true, false, true]).unwrap();
+        let pb = mask.to_pb().unwrap();
+        assert_eq!(pb.get_bool_val(), &[true, false, true]);
+        assert_eq!(Tensor::from_pb(&pb).unwrap(), mask);
+
+        let mut pb = pb;
+        pb.clear_bool_val();
+        pb.set_tensor_content(vec![1, 0, 1]);
+        assert_eq!(Tensor::from_pb(&pb).unwrap(), mask);
+    }
+
+    #[test]
+    fn scalar_from_pb() {
+        let scalar = Tensor::from(arr0(2.5f32));
+        let pb = scalar.to_pb().unwrap();
+        let loaded = Tensor::from_pb(&pb).unwrap();
+        assert_eq!(loaded.shape(), &[] as &[usize]);
+        assert_eq!(loaded, scalar);
+
+        let mut pb = pb;
+        pb.clear_float_val();
+        pb.set_tensor_content(Tensor::from(arr0(2.5f32)).as_bytes().unwrap().to_vec());
+        let loaded = Tensor::from_pb(&pb).unwrap();
+        assert_eq!(loaded.shape(), &[] as &[usize]);
+        assert_eq!(loaded, scalar);
+    }
+
+    #[test]
+    fn close_enough_nan_equal() {
+        let a = Tensor::f32s(&[3], &[::std::f32::NAN, 1.0, 2.0]).unwrap();
+        let b = Tensor::f32s(&[3], &[::std::f32::NAN, 1.0, 2.0]).unwrap();
+        assert!(!a.close_enough(&b).unwrap());
+        assert!(a.close_enough_nan_equal(&b).unwrap());
+        let c = Tensor::f32s(&[3], &[0.0, 1.0, 2.0]).unwrap();
+        assert!(!a.close_enough_nan_equal(&c).unwrap());
+        assert!(!c.close_enough_nan_equal(&a).unwrap());
+    }
+
+    #[test]
+    fn as_f32_contiguous() {
+        let tensor = Tensor::f32s(&[2, 2], &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        match (tensor.as_f32_contiguous().unwrap(), &tensor) {
+            (Cow::Borrowed(a), &Tensor::F32(ref b)) => assert_eq!(a.as_ptr(), b.as_ptr()),
+            _ => panic!("Expected a borrowed array"),
+        }
+
+        let transposed = Tensor::from(arr2(&[[1.0f32, 2.0], [3.0, 4.0]]).reversed_axes().into_dyn());
+        let a = transposed.as_f32_contiguous().unwrap();
+        assert!(a.is_standard_layout());
+        assert_eq!(a.as_slice().unwrap(), &[1.0, 3.0, 2.0, 4.0]);
+
+        let ints = Tensor::i32s(&[2], &[1, 2]).unwrap();
+        assert_eq!(ints.as_f32_contiguous().unwrap().as_slice().unwrap(), &[1.0, 2.0]);
+        assert!(Tensor::Bool(arr1(&[true]).into_dyn()).as_f32_contiguous().is_err());
+    }
+
+    #[test]
+    fn string_from_pb() {
+        let message = Tensor::String(arr0(b"x > 0".to_vec()).into_dyn());
+        let pb = message.to_pb().unwrap();
+        assert_eq!(pb.get_dtype(), ::tfpb::types::DataType::DT_STRING);
+        assert_eq!(Tensor::from_pb(&pb).unwrap(), message);
+
+        // Strings are bytes, which need not be UTF-8.
+        let bytes = Tensor::String(arr1(&[vec![0xff, 0x00]]).into_dyn());
+        let pb = bytes.to_pb().unwrap();
+        assert_eq!(Tensor::from_pb(&pb).unwrap(), bytes);
+        assert!(bytes.partial_dump(true).is_ok());
+    }
+
+    #[test]
+    fn max_rel_error() {
+        let a = Tensor::f32s(&[2, 2], &[1.0, 2.0, 4.0, 0.0]).unwrap();
+        let b = Tensor::f32s(&[2, 2], &[1.0, 2.1, 5.0, 0.0]).unwrap();
+        let (error, index) = a.max_rel_error(&b).unwrap();
+        assert!((error - 0.2).abs() < 1e-6);
+        assert_eq!(index, vec![1, 0]);
+        assert_eq!(a.max_rel_error(&a).unwrap().0, 0.0);
+        let c = Tensor::f32s(&[4], &[1.0, 2.0, 4.0, 0.0]).unwrap();
+        assert!(a.max_rel_error(&c).is_err());
+
+        let inf = ::std::f32::INFINITY;
+        let d = Tensor::f32s(&[3], &[inf, inf, -inf]).unwrap();
+        let e = Tensor::f32s(&[3], &[inf, 1.0, inf]).unwrap();
+        let (error, index) = d.max_rel_error(&e).unwrap();
+        assert_eq!(error, ::std::f64::INFINITY);
+        assert_eq!(index, vec![1]);
+        assert_eq!(d.max_rel_error(&d).unwrap().0, 0.0);
+        let f = Tensor::f32s(&[1], &[-inf]).unwrap();
+        let g = Tensor::f32s(&[1], &[inf]).unwrap();
+        assert_eq!(f.max_rel_error(&g).unwrap().0, ::std::f64::INFINITY);
+    }
+
+    #[test]
+    fn string_tensors() {
+        let strings = |s: &[&str]| {
+            Tensor::String(arr1(&s.iter().map(|s| s.as_bytes().to_vec()).collect::<Vec<_>>()).into_dyn())
+        };
+        let a = strings(&["h", "i"]);
+        let b = strings(&["h", "i"]);
+        let c = strings(&["h", "o"]);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        // Same bytes, but not the same datatype.
+        assert_ne!(a, Tensor::I8(arr1(&[104i8, 105]).into_dyn()));
+
+        let err = a.close_enough(&b).unwrap_err();
+        assert_eq!(format!("{}", err), "String tensors can not be compared numerically");
+        assert!(a.close_enough_nan_equal(&c).is_err());
+        assert!(a.max_rel_error(&b).is_err());
+
+        assert_eq!(a.datatype(), DataType::String);
+        assert_eq!(a.shape(), &[2]);
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert_ne!(a.content_hash(), c.content_hash());
+        let stacked = Tensor::stack(&[&a, &c], 0).unwrap();
+        assert_eq!(stacked.shape(), &[2, 2]);
+        assert_eq!(stacked.datatype(), DataType::String);
+    }
+
+    #[test]
+    fn permute_axes() {
+        let tensor = Tensor::from(Array::from_shape_fn((2, 3, 4), |(i, j, k)| {
+            (100 * i + 10 * j + k) as i32
+        }));
+        let permuted = tensor.permute_axes(&[2, 0, 1]).unwrap();
+        assert_eq!(permuted.shape(), &[4, 2, 3]);
+        let permuted = permuted.take_i32s().unwrap();
+        assert_eq!(permuted[[3, 1, 2]], 123);
+        assert_eq!(permuted[[0, 1, 2]], 120);
+        assert_eq!(permuted.as_slice().unwrap()[..4], [0, 10, 20, 100]);
+
+        assert!(tensor.permute_axes(&[0, 0, 1]).is_err());
+        assert!(tensor.permute_axes(&[1, 0]).is_err());
+    }
+
+    #[test]
+    fn map() {
+        let tensor = Tensor::f32s(&[3], &[-1.0, 0.5, 2.0]).unwrap();
+        let clamped = tensor.map(|x: f32| x.max(0.0).min(1.0)).unwrap();
+        assert_eq!(clamped, Tensor::f32s(&[3], &[0.0, 0.5, 1.0]).unwrap());
+        assert!(tensor.map(|x: i32| x * 2).is_err());
+    }
+
+    #[test]
+    fn map_any_numeric() {
+        let scaled = Tensor::i32s(&[3], &[-1, 2, 5])
+            .unwrap()
+            .map_any_numeric(|x| x * 1.5)
+            .unwrap();
+        assert_eq!(scaled, Tensor::i32s(&[3], &[-1, 3, 7]).unwrap());
+        let scaled = Tensor::f64s(&[2], &[0.5, 2.0])
+            .unwrap()
+            .map_any_numeric(|x| x * 1.5)
+            .unwrap();
+        assert_eq!(scaled, Tensor::f64s(&[2], &[0.75, 3.0]).unwrap());
+        assert!(Tensor::bools(&[1], &[true]).unwrap().map_any_numeric(|x| x).is_err());
+    }
+
+    #[test]
+    fn raw_bytes_round_trip() {
+        let tensor = Tensor::f32s(&[2, 2], &[1.0, -2.5, 3.25, 0.0]).unwrap();
+        let bytes = tensor.as_bytes().unwrap().to_vec();
+        assert_eq!(bytes.len(), 16);
+        let back = Tensor::from_raw_bytes(DataType::F32, &[2, 2], &bytes).unwrap();
+        assert_eq!(back, tensor);
+        assert!(Tensor::from_raw_bytes(DataType::F32, &[3], &bytes).is_err());
+    }
+}
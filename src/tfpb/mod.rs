@@ -122,19 +122,83 @@ impl node_def::NodeDef {
         }
     }
 
+    /// Returns the datatype of the first of the given attributes found on
+    /// the node, for the operators whose datatype attribute is not named
+    /// consistently across TensorFlow versions.
+    pub fn get_attr_first_datatype(&self, names: &[&str]) -> ::Result<::DataType> {
+        for name in names {
+            if let Some(dt) = self.get_attr_opt_datatype(name)? {
+                return Ok(dt);
+            }
+        }
+        bail!(
+            "Node {} ({}) expected a datatype attribute among {:?}",
+            self.get_name(),
+            self.get_op(),
+            names
+        )
+    }
+
     pub fn get_attr_tensor(&self, name: &str) -> ::Result<::tensor::Tensor> {
         Ok(self.get_attr_opt_tensor(name)?
             .ok_or_else(|| format!("Node {} ({}) expected tensor attribute '{}'", self.get_name(), self.get_op(), name))?)
     }
 
     pub fn get_attr_opt_tensor(&self, name: &str) -> ::Result<Option<::tensor::Tensor>> {
-        if let Some(t) = self.get_attr().get(name).map(|v| v.get_tensor()) {
-            Ok(Some(::tensor::Tensor::from_pb(&t)?))
-        } else {
-            Ok(None)
+        match self.get_attr().get(name) {
+            Some(v) if v.has_tensor() => Ok(Some(::tensor::Tensor::from_pb(v.get_tensor())?)),
+            Some(v) => bail!(
+                "Node {} ({}) expected tensor attribute '{}', found {:?}",
+                self.get_name(),
+                self.get_op(),
+                name,
+                v
+            ),
+            None => Ok(None),
         }
     }
 
+    /// Returns the dimensions of a shape attribute, with -1 for the unknown
+    /// ones, or None if the attribute is missing or its rank is unknown.
+    pub fn get_attr_opt_shape(&self, name: &str) -> ::Result<Option<Vec<isize>>> {
+        match self.get_attr().get(name) {
+            Some(v) if v.has_shape() => {
+                let shape = v.get_shape();
+                if shape.get_unknown_rank() {
+                    Ok(None)
+                } else {
+                    Ok(Some(shape.get_dim().iter().map(|d| d.get_size() as isize).collect()))
+                }
+            }
+            Some(v) => bail!(
+                "Node {} ({}) expected shape attribute '{}', found {:?}",
+                self.get_name(),
+                self.get_op(),
+                name,
+                v
+            ),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_attr_bool(&self, name: &str) -> ::Result<bool> {
+        Ok(self.get_attr_opt_bool(name)?
+            .ok_or_else(|| format!("Node {} ({}) expected bool attribute '{}'", self.get_name(), self.get_op(), name))?)
+    }
+
+    pub fn get_attr_opt_bool(&self, name: &str) -> ::Result<Option<bool>> {
+        Ok(self.get_attr().get(name).map(|v| v.get_b()))
+    }
+
+    pub fn get_attr_float(&self, name: &str) -> ::Result<f32> {
+        Ok(self.get_attr_opt_float(name)?
+            .ok_or_else(|| format!("Node {} ({}) expected float attribute '{}'", self.get_name(), self.get_op(), name))?)
+    }
+
+    pub fn get_attr_opt_float(&self, name: &str) -> ::Result<Option<f32>> {
+        Ok(self.get_attr().get(name).map(|v| v.get_f()))
+    }
+
     pub fn get_attr_int<T: ::num_traits::FromPrimitive>(&self, name: &str) -> ::Result<T> {
         Ok(self.get_attr_opt_int(name)?
             .ok_or_else(|| format!("Node {} ({}) expected int attribute '{}'", self.get_name(), self.get_op(), name))?)
@@ -194,6 +258,14 @@ impl From<i64> for AttrValue {
     }
 }
 
+impl From<bool> for AttrValue {
+    fn from(t: bool) -> AttrValue {
+        let mut value = attr_value::AttrValue::new();
+        value.set_b(t);
+        value
+    }
+}
+
 impl From<f32> for AttrValue {
     fn from(t: f32) -> AttrValue {
         let mut value = attr_value::AttrValue::new();
@@ -0,0 +1,48 @@
+#![cfg(feature = "capi")]
+extern crate tfdeploy;
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use tfdeploy::capi::*;
+
+#[test]
+fn capi_plus3() {
+    unsafe {
+        let path = CString::new("tests/models/plus3.pb").unwrap();
+        let model = tfd_model_load(path.as_ptr());
+        assert!(!model.is_null());
+
+        let input = CString::new("input").unwrap();
+        let output = CString::new("output").unwrap();
+        let data = [1.0f32, 2.0];
+        let shape = [2usize];
+        // 1 is DT_FLOAT.
+        assert_eq!(
+            tfd_model_set_input(model, input.as_ptr(), 1, shape.as_ptr(), 1, data.as_ptr() as _, 8),
+            0
+        );
+        assert_eq!(tfd_model_run(model, output.as_ptr()), 0);
+
+        let mut rank = 0;
+        let mut shape = [0usize; 4];
+        assert_eq!(tfd_model_output_shape(model, shape.as_mut_ptr(), 4, &mut rank), 0);
+        assert_eq!(&shape[..rank], &[2]);
+
+        let mut datatype = 0;
+        let mut data = ptr::null();
+        let mut len = 0;
+        assert_eq!(tfd_model_output_data(model, &mut datatype, &mut data, &mut len), 0);
+        assert_eq!(datatype, 1);
+        assert_eq!(len, 8);
+        let values = ::std::slice::from_raw_parts(data as *const f32, 2);
+        assert_eq!(values, &[4.0, 5.0]);
+
+        let missing = CString::new("missing").unwrap();
+        assert_eq!(tfd_model_run(model, missing.as_ptr()), -1);
+        let error = CStr::from_ptr(tfd_last_error()).to_str().unwrap();
+        assert!(error.contains("missing"));
+
+        tfd_model_free(model);
+    }
+}